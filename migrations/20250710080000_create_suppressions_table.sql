@@ -0,0 +1,14 @@
+-- Add migration script here
+CREATE TABLE suppressions (
+  suppression_id uuid NOT NULL,
+  email TEXT NULL,
+  domain TEXT NULL,
+  reason TEXT NOT NULL,
+  source TEXT NOT NULL,
+  created_at timestamptz NOT NULL,
+  PRIMARY KEY(suppression_id),
+  CHECK ((email IS NULL) <> (domain IS NULL))
+);
+
+CREATE UNIQUE INDEX suppressions_email_idx ON suppressions (lower(email)) WHERE email IS NOT NULL;
+CREATE UNIQUE INDEX suppressions_domain_idx ON suppressions (lower(domain)) WHERE domain IS NOT NULL;
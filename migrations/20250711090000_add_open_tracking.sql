@@ -0,0 +1,15 @@
+-- Add migration script here
+ALTER TABLE newsletter_issues ADD COLUMN track_opens BOOLEAN NOT NULL DEFAULT false;
+
+CREATE TABLE issue_recipients (
+  recipient_id uuid NOT NULL,
+  newsletter_issue_id uuid NOT NULL REFERENCES newsletter_issues (newsletter_issue_id),
+  subscriber_email TEXT NOT NULL,
+  sent_at timestamptz NOT NULL,
+  first_opened_at timestamptz NULL,
+  last_opened_at timestamptz NULL,
+  open_count INTEGER NOT NULL DEFAULT 0,
+  user_agent TEXT NULL,
+  PRIMARY KEY(recipient_id),
+  UNIQUE(newsletter_issue_id, subscriber_email)
+);
@@ -0,0 +1,19 @@
+-- Add migration script here
+ALTER TABLE newsletter_issues ADD COLUMN track_clicks BOOLEAN NOT NULL DEFAULT false;
+
+CREATE TABLE issue_links (
+  link_id uuid NOT NULL,
+  newsletter_issue_id uuid NOT NULL REFERENCES newsletter_issues (newsletter_issue_id),
+  url TEXT NOT NULL,
+  PRIMARY KEY(link_id),
+  UNIQUE(newsletter_issue_id, url)
+);
+
+CREATE TABLE issue_clicks (
+  recipient_id uuid NOT NULL REFERENCES issue_recipients (recipient_id),
+  link_id uuid NOT NULL REFERENCES issue_links (link_id),
+  first_clicked_at timestamptz NOT NULL,
+  last_clicked_at timestamptz NOT NULL,
+  click_count INTEGER NOT NULL,
+  PRIMARY KEY(recipient_id, link_id)
+);
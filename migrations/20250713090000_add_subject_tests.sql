@@ -0,0 +1,25 @@
+-- Add migration script here
+CREATE TABLE issue_subject_variants (
+  variant_id uuid NOT NULL,
+  newsletter_issue_id uuid NOT NULL REFERENCES newsletter_issues (newsletter_issue_id),
+  position SMALLINT NOT NULL,
+  subject TEXT NOT NULL,
+  PRIMARY KEY(variant_id),
+  UNIQUE(newsletter_issue_id, position)
+);
+
+CREATE TABLE subject_tests (
+  newsletter_issue_id uuid NOT NULL REFERENCES newsletter_issues (newsletter_issue_id),
+  metric TEXT NOT NULL CHECK (metric IN ('opens', 'clicks')),
+  decide_after timestamptz NOT NULL,
+  winning_variant_id uuid NULL REFERENCES issue_subject_variants (variant_id),
+  decided_at timestamptz NULL,
+  PRIMARY KEY(newsletter_issue_id)
+);
+
+ALTER TABLE issue_delivery_queue
+  ADD COLUMN subject_variant_id uuid NULL REFERENCES issue_subject_variants (variant_id),
+  ADD COLUMN held BOOLEAN NOT NULL DEFAULT false;
+
+ALTER TABLE issue_recipients
+  ADD COLUMN subject_variant_id uuid NULL REFERENCES issue_subject_variants (variant_id);
@@ -0,0 +1,15 @@
+-- Add migration script here
+ALTER TABLE newsletter_issues
+  ALTER COLUMN published_at TYPE timestamptz USING published_at::timestamptz,
+  ADD COLUMN is_public BOOLEAN NOT NULL DEFAULT true,
+  ADD COLUMN slug TEXT NULL;
+
+UPDATE newsletter_issues
+SET slug = COALESCE(
+    NULLIF(trim(both '-' from regexp_replace(lower(title), '[^a-z0-9]+', '-', 'g')), ''),
+    'issue'
+  ) || '-' || left(newsletter_issue_id::text, 8);
+
+ALTER TABLE newsletter_issues ALTER COLUMN slug SET NOT NULL;
+CREATE UNIQUE INDEX newsletter_issues_slug_idx ON newsletter_issues (slug);
+CREATE INDEX newsletter_issues_archive_idx ON newsletter_issues (published_at DESC) WHERE is_public;
@@ -0,0 +1,15 @@
+-- Add migration script here
+CREATE TABLE email_layouts (
+  layout_id uuid NOT NULL,
+  name TEXT NOT NULL UNIQUE,
+  html_template TEXT NOT NULL,
+  text_template TEXT NOT NULL,
+  is_default BOOLEAN NOT NULL DEFAULT false,
+  created_at timestamptz NOT NULL,
+  PRIMARY KEY(layout_id)
+);
+
+CREATE UNIQUE INDEX email_layouts_default_idx ON email_layouts (is_default) WHERE is_default;
+
+ALTER TABLE newsletter_issues
+  ADD COLUMN layout_id uuid NULL REFERENCES email_layouts (layout_id) ON DELETE SET NULL;
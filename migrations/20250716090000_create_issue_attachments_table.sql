@@ -0,0 +1,13 @@
+-- Add migration script here
+CREATE TABLE issue_attachments (
+  attachment_id uuid NOT NULL,
+  newsletter_issue_id uuid NOT NULL
+    REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE CASCADE,
+  position SMALLINT NOT NULL,
+  file_name TEXT NOT NULL,
+  content_type TEXT NOT NULL,
+  content BYTEA NOT NULL,
+  PRIMARY KEY(attachment_id)
+);
+
+CREATE INDEX issue_attachments_issue_idx ON issue_attachments (newsletter_issue_id, position);
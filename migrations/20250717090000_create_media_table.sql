@@ -0,0 +1,9 @@
+-- Add migration script here
+CREATE TABLE media (
+  media_id uuid NOT NULL,
+  file_name TEXT NOT NULL,
+  content_type TEXT NOT NULL,
+  size_bytes INTEGER NOT NULL,
+  created_at timestamptz NOT NULL,
+  PRIMARY KEY(media_id)
+);
@@ -0,0 +1,26 @@
+-- Add migration script here
+-- Messages of an automation are stored like issues, so that the delivery worker
+-- can send them with layouts, tracking and attachments.
+ALTER TABLE newsletter_issues
+  ADD COLUMN kind TEXT NOT NULL DEFAULT 'issue' CHECK (kind IN ('issue', 'automation'));
+
+CREATE TABLE automations (
+  automation_id uuid NOT NULL,
+  name TEXT NOT NULL,
+  is_active BOOLEAN NOT NULL DEFAULT true,
+  created_at timestamptz NOT NULL,
+  PRIMARY KEY(automation_id)
+);
+
+CREATE TABLE automation_steps (
+  step_id uuid NOT NULL,
+  automation_id uuid NOT NULL REFERENCES automations (automation_id) ON DELETE CASCADE,
+  delay_hours INTEGER NOT NULL CHECK (delay_hours >= 0),
+  newsletter_issue_id uuid NOT NULL UNIQUE REFERENCES newsletter_issues (newsletter_issue_id),
+  PRIMARY KEY(step_id)
+);
+
+ALTER TABLE issue_delivery_queue
+  ADD COLUMN execute_after timestamptz NOT NULL DEFAULT now();
+
+CREATE INDEX issue_delivery_queue_execute_after_idx ON issue_delivery_queue (execute_after);
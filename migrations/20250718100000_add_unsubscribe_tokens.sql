@@ -0,0 +1,7 @@
+-- Unsubscribe and preference links get their own token, so the confirmation
+-- token is only ever used to confirm.
+ALTER TABLE subscriptions ADD COLUMN unsubscribe_token TEXT;
+UPDATE subscriptions
+SET unsubscribe_token = replace(gen_random_uuid()::text || gen_random_uuid()::text, '-', '');
+ALTER TABLE subscriptions ALTER COLUMN unsubscribe_token SET NOT NULL;
+ALTER TABLE subscriptions ADD CONSTRAINT subscriptions_unsubscribe_token_key UNIQUE (unsubscribe_token);
@@ -0,0 +1,19 @@
+-- Add migration script here
+ALTER TABLE subscriptions
+  ADD COLUMN delivery_frequency TEXT NOT NULL DEFAULT 'immediate'
+    CHECK (delivery_frequency IN ('immediate', 'daily', 'weekly'));
+
+ALTER TABLE newsletter_issues DROP CONSTRAINT newsletter_issues_kind_check;
+ALTER TABLE newsletter_issues
+  ADD CONSTRAINT newsletter_issues_kind_check CHECK (kind IN ('issue', 'automation', 'digest'));
+
+-- One row per period that was processed, even when nothing was published in it,
+-- so that a digest is never built twice.
+CREATE TABLE digests (
+  frequency TEXT NOT NULL CHECK (frequency IN ('daily', 'weekly')),
+  period_start timestamptz NOT NULL,
+  period_end timestamptz NOT NULL,
+  newsletter_issue_id uuid NULL REFERENCES newsletter_issues (newsletter_issue_id),
+  created_at timestamptz NOT NULL,
+  PRIMARY KEY(frequency, period_start)
+);
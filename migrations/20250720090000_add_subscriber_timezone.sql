@@ -0,0 +1,3 @@
+-- Add migration script here
+-- An IANA time zone name such as Europe/Rome, validated against pg_timezone_names.
+ALTER TABLE subscriptions ADD COLUMN timezone TEXT NULL;
@@ -0,0 +1,20 @@
+-- Add migration script here
+-- The audience of an issue is expanded into the delivery queue in the background,
+-- one chunk at a time. The cursor is committed with each chunk so a crashed
+-- expansion resumes where it stopped.
+CREATE TABLE audience_expansions (
+  newsletter_issue_id uuid PRIMARY KEY REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE CASCADE,
+  -- Subscribers who sign up after this point are not part of the audience.
+  snapshot_at timestamptz NOT NULL,
+  -- Subscribers are walked in id order starting right after a random id, so
+  -- the test slice of a subject line test differs from issue to issue.
+  start_after uuid NOT NULL,
+  cursor_wrapped BOOLEAN NOT NULL DEFAULT false,
+  cursor_id uuid NOT NULL DEFAULT '00000000-0000-0000-0000-000000000000',
+  expanded BIGINT NOT NULL DEFAULT 0,
+  audience_size BIGINT NULL,
+  test_percentage SMALLINT NULL,
+  send_date DATE NULL,
+  send_time TIME NULL,
+  completed_at timestamptz NULL
+);
@@ -0,0 +1,10 @@
+-- Add migration script here
+-- The subscribers an issue goes to, fixed on the first pass of its expansion
+-- so every chunk and the size of the subject test slice agree on the audience.
+-- The rows are dropped once the expansion has completed.
+CREATE TABLE audience_snapshots (
+  newsletter_issue_id uuid NOT NULL REFERENCES audience_expansions (newsletter_issue_id) ON DELETE CASCADE,
+  subscriber_id uuid NOT NULL,
+  email TEXT NOT NULL,
+  PRIMARY KEY (newsletter_issue_id, subscriber_id)
+);
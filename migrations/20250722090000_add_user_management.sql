@@ -0,0 +1,21 @@
+-- Add migration script here
+-- Invited users have no password until they accept their invitation.
+ALTER TABLE users
+  ALTER COLUMN password_hash DROP NOT NULL,
+  ADD COLUMN email TEXT NULL UNIQUE,
+  ADD COLUMN is_active BOOLEAN NOT NULL DEFAULT true,
+  ADD COLUMN created_at timestamptz NOT NULL DEFAULT now();
+
+-- Saved responses are a cache, they must not keep a user from being deleted.
+ALTER TABLE idempotency
+  DROP CONSTRAINT idempotency_user_id_fkey,
+  ADD CONSTRAINT idempotency_user_id_fkey
+    FOREIGN KEY (user_id) REFERENCES users (user_id) ON DELETE CASCADE;
+
+-- Only a hash of the token is stored, the token itself is in the invitation email.
+CREATE TABLE user_invitations (
+  token_hash TEXT PRIMARY KEY,
+  user_id uuid NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+  created_at timestamptz NOT NULL,
+  expires_at timestamptz NOT NULL
+);
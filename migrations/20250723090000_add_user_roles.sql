@@ -0,0 +1,6 @@
+-- Add migration script here
+-- Everybody who could do everything so far stays an owner.
+ALTER TABLE users
+  ADD COLUMN role TEXT NOT NULL DEFAULT 'owner'
+    CHECK (role IN ('owner', 'editor', 'viewer'));
+ALTER TABLE users ALTER COLUMN role SET DEFAULT 'viewer';
@@ -0,0 +1,16 @@
+-- TOTP secrets are encrypted with the key from the configuration, a stolen
+-- database alone does not allow generating codes.
+ALTER TABLE users
+  ADD COLUMN totp_secret BYTEA NULL,
+  ADD COLUMN totp_pending_secret BYTEA NULL,
+  -- The time step of the last accepted code, so a code cannot be replayed.
+  ADD COLUMN totp_last_step BIGINT NULL;
+
+-- Recovery codes are hashed with argon2 like passwords and can be used once.
+CREATE TABLE recovery_codes (
+  recovery_code_id uuid PRIMARY KEY,
+  user_id uuid NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+  code_hash TEXT NOT NULL,
+  used_at timestamptz NULL
+);
+CREATE INDEX recovery_codes_user_id_idx ON recovery_codes (user_id);
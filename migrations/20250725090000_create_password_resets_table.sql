@@ -0,0 +1,8 @@
+-- Like invitations, only a hash of the token is stored. A row is deleted as
+-- soon as its token has been used.
+CREATE TABLE password_resets (
+  token_hash TEXT PRIMARY KEY,
+  user_id uuid NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+  created_at timestamptz NOT NULL,
+  expires_at timestamptz NOT NULL
+);
@@ -0,0 +1,8 @@
+-- Sessions are checked against their lifetime on every request, on top of
+-- the expiry of the cookie. Existing sessions have to log in again.
+ALTER TABLE user_sessions
+  ADD COLUMN expires_at timestamptz NOT NULL DEFAULT now(),
+  ADD COLUMN inactivity_timeout_seconds INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE user_sessions
+  ALTER COLUMN expires_at DROP DEFAULT,
+  ALTER COLUMN inactivity_timeout_seconds DROP DEFAULT;
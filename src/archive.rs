@@ -0,0 +1,46 @@
+/// The public url of an issue, used for the "view in browser" link.
+pub fn archive_url(base_url: &str, slug: &str) -> String {
+    format!("http://{base_url}/archive/{slug}")
+}
+
+/// Inserts the link right after the opening `<body>` tag, or at the start of
+/// fragments that have no body.
+pub fn inject_view_in_browser_link(html: &str, url: &str) -> String {
+    let link = format!(r#"<p><a href="{url}">View this issue in your browser</a></p>"#);
+    let lowercase = html.to_ascii_lowercase();
+    let body_start = lowercase
+        .find("<body")
+        .and_then(|i| lowercase[i..].find('>').map(|end| i + end + 1));
+    match body_start {
+        Some(i) => format!("{}{link}{}", &html[..i], &html[i..]),
+        None => format!("{link}{html}"),
+    }
+}
+
+pub fn prepend_view_in_browser_text(text: &str, url: &str) -> String {
+    format!("View this issue in your browser: {url}\n\n{text}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::inject_view_in_browser_link;
+
+    #[test]
+    fn the_link_is_the_first_element_of_the_body() {
+        let html = inject_view_in_browser_link(
+            r#"<html><BODY class="x"><p>Hi</p></BODY></html>"#,
+            "http://localhost/archive/hi",
+        );
+        assert_eq!(
+            html,
+            r#"<html><BODY class="x"><p><a href="http://localhost/archive/hi">View this issue in your browser</a></p><p>Hi</p></BODY></html>"#
+        );
+    }
+
+    #[test]
+    fn fragments_get_the_link_prepended() {
+        let html = inject_view_in_browser_link("<p>Hi</p>", "http://localhost/archive/hi");
+        assert!(html.starts_with(r#"<p><a href="http://localhost/archive/hi">"#));
+        assert!(html.ends_with("<p>Hi</p>"));
+    }
+}
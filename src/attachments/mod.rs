@@ -0,0 +1,188 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+
+mod persistence;
+
+pub use persistence::{get_attachments, insert_attachments};
+
+/// A single file may not be larger than this.
+pub const MAX_ATTACHMENT_SIZE: usize = 5 * 1024 * 1024;
+/// Postmark rejects messages over 10 MB and base64 grows attachments by a third.
+pub const MAX_TOTAL_ATTACHMENT_SIZE: usize = 7 * 1024 * 1024;
+
+/// The content types we accept, with the leading bytes a file of that type
+/// must start with. Text types are checked for valid UTF-8 instead.
+const ALLOWED_CONTENT_TYPES: [(&str, Option<&[u8]>); 7] = [
+    ("application/pdf", Some(b"%PDF-")),
+    ("image/png", Some(b"\x89PNG\r\n\x1a\n")),
+    ("image/jpeg", Some(b"\xff\xd8\xff")),
+    ("image/gif", Some(b"GIF8")),
+    ("text/plain", None),
+    ("text/csv", None),
+    ("text/calendar", None),
+];
+
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    pub file_name: String,
+    pub content_type: String,
+    pub content: Vec<u8>,
+}
+
+/// An attachment the way the email API takes it.
+#[derive(Debug, Clone)]
+pub struct EncodedAttachment {
+    pub file_name: String,
+    pub content_type: String,
+    /// Base64 encoded.
+    pub content: String,
+}
+
+impl Attachment {
+    pub fn encode(&self) -> EncodedAttachment {
+        EncodedAttachment {
+            file_name: self.file_name.clone(),
+            content_type: self.content_type.clone(),
+            content: STANDARD.encode(&self.content),
+        }
+    }
+
+    /// Checks the size and that the content matches its declared type.
+    pub fn parse(file_name: &str, content_type: &str, content: Vec<u8>) -> Result<Self, String> {
+        // Some browsers send the full path of the file on the author's machine.
+        let file_name = file_name
+            .rsplit(['/', '\\'])
+            .next()
+            .unwrap_or_default()
+            .trim();
+        if file_name.is_empty() {
+            return Err("Attachments need a file name.".into());
+        }
+        if content.is_empty() {
+            return Err(format!("{file_name} is empty."));
+        }
+        if content.len() > MAX_ATTACHMENT_SIZE {
+            return Err(format!(
+                "{file_name} is larger than {} MB.",
+                MAX_ATTACHMENT_SIZE / 1024 / 1024
+            ));
+        }
+        let content_type = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        let Some((_, signature)) = ALLOWED_CONTENT_TYPES
+            .iter()
+            .find(|(allowed, _)| *allowed == content_type)
+        else {
+            return Err(format!(
+                "{file_name} has an unsupported file type ({content_type})."
+            ));
+        };
+        let matches = match signature {
+            Some(signature) => content.starts_with(signature),
+            None => std::str::from_utf8(&content).is_ok(),
+        };
+        if !matches {
+            return Err(format!(
+                "The content of {file_name} does not match its file type ({content_type})."
+            ));
+        }
+        Ok(Self {
+            file_name: file_name.to_string(),
+            content_type,
+            content,
+        })
+    }
+}
+
+pub fn validate_total_size(attachments: &[Attachment]) -> Result<(), String> {
+    let total: usize = attachments.iter().map(|a| a.content.len()).sum();
+    if total > MAX_TOTAL_ATTACHMENT_SIZE {
+        return Err(format!(
+            "Attachments may not add up to more than {} MB.",
+            MAX_TOTAL_ATTACHMENT_SIZE / 1024 / 1024
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Attachment, MAX_ATTACHMENT_SIZE, validate_total_size};
+    use claim::{assert_err, assert_ok};
+
+    fn pdf(size: usize) -> Vec<u8> {
+        let mut content = b"%PDF-1.7\n".to_vec();
+        content.resize(size, b' ');
+        content
+    }
+
+    #[test]
+    fn a_pdf_is_accepted() {
+        let attachment = Attachment::parse("report.pdf", "application/pdf", pdf(100)).unwrap();
+        assert_eq!(attachment.file_name, "report.pdf");
+        assert_eq!(attachment.content_type, "application/pdf");
+    }
+
+    #[test]
+    fn the_directory_is_stripped_from_the_file_name() {
+        let attachment =
+            Attachment::parse(r"C:\Users\me\report.pdf", "application/pdf", pdf(100)).unwrap();
+        assert_eq!(attachment.file_name, "report.pdf");
+    }
+
+    #[test]
+    fn content_type_parameters_are_ignored() {
+        let attachment =
+            Attachment::parse("notes.txt", "Text/Plain; charset=utf-8", b"hello".to_vec()).unwrap();
+        assert_eq!(attachment.content_type, "text/plain");
+    }
+
+    #[test]
+    fn unsupported_types_are_rejected() {
+        assert_err!(Attachment::parse(
+            "tool.exe",
+            "application/octet-stream",
+            b"MZ".to_vec()
+        ));
+    }
+
+    #[test]
+    fn content_that_does_not_match_the_type_is_rejected() {
+        assert_err!(Attachment::parse(
+            "report.pdf",
+            "application/pdf",
+            b"MZ\x90\x00".to_vec()
+        ));
+        assert_err!(Attachment::parse(
+            "notes.txt",
+            "text/plain",
+            vec![0xff, 0xfe, 0x00]
+        ));
+    }
+
+    #[test]
+    fn empty_and_oversized_files_are_rejected() {
+        assert_err!(Attachment::parse("report.pdf", "application/pdf", vec![]));
+        assert_err!(Attachment::parse(
+            "report.pdf",
+            "application/pdf",
+            pdf(MAX_ATTACHMENT_SIZE + 1)
+        ));
+        assert_ok!(Attachment::parse(
+            "report.pdf",
+            "application/pdf",
+            pdf(MAX_ATTACHMENT_SIZE)
+        ));
+    }
+
+    #[test]
+    fn the_total_size_is_limited() {
+        let attachment =
+            Attachment::parse("report.pdf", "application/pdf", pdf(MAX_ATTACHMENT_SIZE)).unwrap();
+        assert_ok!(validate_total_size(std::slice::from_ref(&attachment)));
+        assert_err!(validate_total_size(&[attachment.clone(), attachment]));
+    }
+}
@@ -0,0 +1,49 @@
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use super::Attachment;
+
+#[tracing::instrument(name = "Store issue attachments", skip(transaction, attachments))]
+pub async fn insert_attachments(
+    transaction: &mut Transaction<'static, Postgres>,
+    newsletter_issue_id: Uuid,
+    attachments: &[Attachment],
+) -> Result<(), sqlx::Error> {
+    for (position, attachment) in (0i16..).zip(attachments) {
+        let query = sqlx::query!(
+            r#"
+            INSERT INTO issue_attachments (
+                attachment_id, newsletter_issue_id, position, file_name, content_type, content
+            )
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            Uuid::new_v4(),
+            newsletter_issue_id,
+            position,
+            attachment.file_name,
+            attachment.content_type,
+            attachment.content
+        );
+        transaction.execute(query).await?;
+    }
+    Ok(())
+}
+
+#[tracing::instrument(name = "Get issue attachments", skip(pool))]
+pub async fn get_attachments(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Vec<Attachment>, sqlx::Error> {
+    sqlx::query_as!(
+        Attachment,
+        r#"
+        SELECT file_name, content_type, content
+        FROM issue_attachments
+        WHERE newsletter_issue_id = $1
+        ORDER BY position
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,311 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::{NaiveDate, NaiveTime};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use tracing::Span;
+use tracing::field::display;
+use uuid::Uuid;
+
+use crate::configuration::Settings;
+use crate::issue_delivery_worker::ExecutionOutcome;
+use crate::startup::get_connection_pool;
+use crate::timezones::{DEFAULT_TIMEZONE, LocalSendTime};
+
+/// How many snapshot or queue rows are inserted per transaction.
+pub const CHUNK_SIZE: i64 = 1000;
+
+/// Records what the audience of a freshly published issue looks like, the
+/// queue itself is filled in by `try_expand_audience`.
+#[tracing::instrument(name = "Schedule the audience expansion", skip(transaction))]
+pub async fn schedule_audience_expansion(
+    transaction: &mut Transaction<'static, Postgres>,
+    newsletter_issue_id: Uuid,
+    send_at: Option<LocalSendTime>,
+    test_percentage: Option<u8>,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO audience_expansions (
+            newsletter_issue_id,
+            snapshot_at,
+            start_after,
+            test_percentage,
+            send_date,
+            send_time
+        )
+        VALUES ($1, now(), $2, $3, $4, $5)
+        "#,
+        newsletter_issue_id,
+        Uuid::new_v4(),
+        test_percentage.map(i16::from),
+        send_at.and_then(|s| s.date),
+        send_at.map(|s| s.time)
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+pub async fn run_audience_expansion_until_stopped(
+    configuration: Settings,
+) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database);
+    loop {
+        match try_expand_audience(&connection_pool, CHUNK_SIZE).await {
+            Ok(ExecutionOutcome::EmptyQueue) => {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            Err(_) => {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+        }
+    }
+}
+
+struct Expansion {
+    newsletter_issue_id: Uuid,
+    start_after: Uuid,
+    cursor_wrapped: bool,
+    cursor_id: Uuid,
+    expanded: i64,
+    audience_size: Option<i64>,
+    test_percentage: Option<i16>,
+    send_date: Option<NaiveDate>,
+    send_time: Option<NaiveTime>,
+}
+
+/// Enqueues the next chunk of the audience of one unfinished expansion, once
+/// its snapshot has been copied in batches of the same size.
+#[tracing::instrument(skip(pool), fields(newsletter_issue_id=tracing::field::Empty), err)]
+pub async fn try_expand_audience(
+    pool: &PgPool,
+    chunk_size: i64,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let expansion = sqlx::query_as!(
+        Expansion,
+        r#"
+        SELECT
+            newsletter_issue_id,
+            start_after,
+            cursor_wrapped,
+            cursor_id,
+            expanded,
+            audience_size,
+            test_percentage,
+            send_date,
+            send_time
+        FROM audience_expansions
+        WHERE completed_at IS NULL
+        FOR UPDATE
+        SKIP LOCKED
+        LIMIT 1
+        "#
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+    let Some(expansion) = expansion else {
+        transaction.rollback().await?;
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    let issue_id = expansion.newsletter_issue_id;
+    Span::current().record("newsletter_issue_id", display(issue_id));
+
+    let audience_size = match expansion.audience_size {
+        Some(size) => size,
+        None => {
+            // Fixed before the first chunk, so later chunks neither pick up nor
+            // lose anybody whose status or delivery frequency changes in the
+            // meantime. It is copied one batch per transaction, resuming after
+            // the highest subscriber id copied so far.
+            let query = sqlx::query!(
+                r#"
+                INSERT INTO audience_snapshots (newsletter_issue_id, subscriber_id, email)
+                SELECT e.newsletter_issue_id, s.id, s.email
+                FROM subscriptions s
+                JOIN audience_expansions e ON s.subscribed_at <= e.snapshot_at
+                WHERE e.newsletter_issue_id = $1
+                    AND s.status = 'confirmed'
+                    AND s.delivery_frequency = 'immediate'
+                    AND s.id > COALESCE(
+                        (
+                            SELECT subscriber_id
+                            FROM audience_snapshots
+                            WHERE newsletter_issue_id = $1
+                            ORDER BY subscriber_id DESC
+                            LIMIT 1
+                        ),
+                        '00000000-0000-0000-0000-000000000000'
+                    )
+                ORDER BY s.id
+                LIMIT $2
+                "#,
+                issue_id,
+                chunk_size
+            );
+            let copied = i64::try_from(transaction.execute(query).await?.rows_affected())
+                .context("The snapshot batch is too large")?;
+            if copied == chunk_size {
+                transaction.commit().await?;
+                tracing::info!(copied, "Snapshotted a batch of the audience.");
+                return Ok(ExecutionOutcome::TaskCompleted);
+            }
+            let size = sqlx::query_scalar!(
+                r#"
+                SELECT count(*) AS "count!"
+                FROM audience_snapshots
+                WHERE newsletter_issue_id = $1
+                "#,
+                issue_id
+            )
+            .fetch_one(&mut *transaction)
+            .await?;
+            let query = sqlx::query!(
+                "UPDATE audience_expansions SET audience_size = $2 WHERE newsletter_issue_id = $1",
+                issue_id,
+                size
+            );
+            transaction.execute(query).await?;
+            size
+        }
+    };
+
+    let chunk = sqlx::query!(
+        r#"
+        SELECT subscriber_id AS id, email, subscriber_id <= $2 AS "wrapped!"
+        FROM audience_snapshots
+        WHERE newsletter_issue_id = $1
+            AND (subscriber_id <= $2, subscriber_id) > ($3, $4)
+        ORDER BY subscriber_id <= $2, subscriber_id
+        LIMIT $5
+        "#,
+        issue_id,
+        expansion.start_after,
+        expansion.cursor_wrapped,
+        expansion.cursor_id,
+        chunk_size
+    )
+    .fetch_all(&mut *transaction)
+    .await?;
+
+    let emails: Vec<String> = chunk.iter().map(|row| row.email.clone()).collect();
+    let (variant_ids, winner, test_size) = match expansion.test_percentage {
+        Some(percentage) => {
+            // Serializes with the finalizer: a chunk enqueued after the winner
+            // was picked goes straight out with the winning subject line.
+            let winner = sqlx::query_scalar!(
+                r#"
+                SELECT winning_variant_id
+                FROM subject_tests
+                WHERE newsletter_issue_id = $1
+                FOR SHARE
+                "#,
+                issue_id
+            )
+            .fetch_one(&mut *transaction)
+            .await?;
+            let variant_ids = sqlx::query_scalar!(
+                r#"
+                SELECT variant_id
+                FROM issue_subject_variants
+                WHERE newsletter_issue_id = $1
+                ORDER BY position
+                "#,
+                issue_id
+            )
+            .fetch_all(&mut *transaction)
+            .await?;
+            let test_size = (audience_size * i64::from(percentage) + 99) / 100;
+            (variant_ids, winner, test_size)
+        }
+        None => (vec![], None, 0),
+    };
+    let query = sqlx::query!(
+        r#"
+        WITH audience AS (
+            SELECT
+                a.email,
+                $2 + a.ordinality AS position,
+                COALESCE(s.timezone, $7) AS tz,
+                now() AT TIME ZONE COALESCE(s.timezone, $7) AS local_now
+            FROM unnest($3::text[]) WITH ORDINALITY AS a(email, ordinality)
+            JOIN subscriptions s USING (email)
+            -- Whoever left since the snapshot is skipped, without moving
+            -- anybody else in or out of the test slice.
+            WHERE s.status = 'confirmed' AND s.delivery_frequency = 'immediate'
+        )
+        INSERT INTO issue_delivery_queue (
+            newsletter_issue_id,
+            subscriber_email,
+            execute_after,
+            subject_variant_id,
+            held
+        )
+        SELECT
+            $1,
+            email,
+            CASE WHEN $9::time IS NULL THEN now() ELSE greatest(
+                now(),
+                (
+                    COALESCE(
+                        $8::date,
+                        local_now::date + CASE WHEN local_now::time < $9::time THEN 0 ELSE 1 END
+                    ) + $9::time
+                ) AT TIME ZONE tz
+            ) END,
+            CASE
+                WHEN cardinality($4::uuid[]) = 0 THEN NULL
+                WHEN position <= $6
+                    THEN ($4::uuid[])[1 + (position % cardinality($4::uuid[]))::integer]
+                ELSE $5
+            END,
+            cardinality($4::uuid[]) > 0 AND position > $6 AND $5::uuid IS NULL
+        FROM audience
+        ON CONFLICT DO NOTHING
+        "#,
+        issue_id,
+        expansion.expanded,
+        &emails,
+        &variant_ids,
+        winner,
+        test_size,
+        DEFAULT_TIMEZONE,
+        expansion.send_date,
+        expansion.send_time
+    );
+    transaction.execute(query).await?;
+
+    let chunk_len = i64::try_from(chunk.len()).context("The chunk is too large")?;
+    let (cursor_wrapped, cursor_id) = chunk
+        .last()
+        .map(|row| (row.wrapped, row.id))
+        .unwrap_or((expansion.cursor_wrapped, expansion.cursor_id));
+    let query = sqlx::query!(
+        r#"
+        UPDATE audience_expansions
+        SET
+            cursor_wrapped = $2,
+            cursor_id = $3,
+            expanded = expanded + $4,
+            completed_at = CASE WHEN $4 < $5 THEN now() END
+        WHERE newsletter_issue_id = $1
+        "#,
+        issue_id,
+        cursor_wrapped,
+        cursor_id,
+        chunk_len,
+        chunk_size
+    );
+    transaction.execute(query).await?;
+    if chunk_len < chunk_size {
+        let query = sqlx::query!(
+            "DELETE FROM audience_snapshots WHERE newsletter_issue_id = $1",
+            issue_id
+        );
+        transaction.execute(query).await?;
+    }
+    transaction.commit().await?;
+    tracing::info!(enqueued = chunk_len, "Expanded a chunk of the audience.");
+    Ok(ExecutionOutcome::TaskCompleted)
+}
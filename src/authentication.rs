@@ -4,10 +4,13 @@ use argon2::password_hash::rand_core::OsRng;
 use argon2::{
     Algorithm, Argon2, Params, PasswordHash, PasswordVerifier, Version, password_hash::SaltString,
 };
+use async_trait::async_trait;
+use axum_login::{AuthUser, AuthnBackend};
 use secrecy::{ExposeSecret, SecretString};
 use sqlx::PgPool;
+use uuid::Uuid;
 
-use crate::telemetry::spawn_blocking_with_tracing;
+use crate::{domain::UserRole, telemetry::spawn_blocking_with_tracing};
 
 #[derive(thiserror::Error, Debug)]
 pub enum AuthError {
@@ -23,6 +26,85 @@ pub struct Credentials {
     pub password: SecretString,
 }
 
+/// The user behind a logged in admin session.
+#[derive(Debug, Clone)]
+pub struct SessionUser {
+    pub user_id: Uuid,
+    pub username: String,
+    pub role: UserRole,
+    /// Logged in sessions only stay valid as long as this does not change.
+    password_hash: SecretString,
+}
+
+impl AuthUser for SessionUser {
+    type Id = Uuid;
+
+    fn id(&self) -> Uuid {
+        self.user_id
+    }
+
+    fn session_auth_hash(&self) -> &[u8] {
+        self.password_hash.expose_secret().as_bytes()
+    }
+}
+
+/// Looks admin users up for `axum_login`.
+#[derive(Debug, Clone)]
+pub struct Backend {
+    pool: PgPool,
+}
+
+impl Backend {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl AuthnBackend for Backend {
+    type User = SessionUser;
+    type Credentials = Credentials;
+    type Error = AuthError;
+
+    async fn authenticate(
+        &self,
+        credentials: Credentials,
+    ) -> Result<Option<SessionUser>, AuthError> {
+        match validate_credentials(credentials, &self.pool).await {
+            Ok(user_id) => self.get_user(&user_id).await,
+            Err(AuthError::InvalidCredentials(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    #[tracing::instrument(name = "Get admin user", skip(self))]
+    async fn get_user(&self, user_id: &Uuid) -> Result<Option<SessionUser>, AuthError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT username, role, password_hash AS "password_hash!"
+            FROM users
+            WHERE user_id = $1 AND is_active AND password_hash IS NOT NULL
+            "#,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to load the user")?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        Ok(Some(SessionUser {
+            user_id: *user_id,
+            username: row.username,
+            role: UserRole::parse(&row.role).map_err(anyhow::Error::msg)?,
+            password_hash: SecretString::new(row.password_hash.into()),
+        }))
+    }
+}
+
+/// The `axum_login` session of the admin pages.
+pub type AuthSession = axum_login::AuthSession<Backend>;
+
 #[tracing::instrument(name = "Get stored credentials", skip(username, pool))]
 async fn get_stored_credentials(
     username: &str,
@@ -30,9 +112,9 @@ async fn get_stored_credentials(
 ) -> Result<Option<(uuid::Uuid, SecretString)>, anyhow::Error> {
     let row = sqlx::query!(
         r#"
-        SELECT user_id, password_hash
+        SELECT user_id, password_hash AS "password_hash!"
         FROM users
-        WHERE username = $1
+        WHERE username = $1 AND is_active AND password_hash IS NOT NULL
         "#,
         username,
     )
@@ -113,7 +195,16 @@ pub async fn change_password(
     Ok(())
 }
 
-fn compute_password_hash(password: SecretString) -> Result<SecretString, anyhow::Error> {
+/// The length rules every new password has to follow.
+pub fn validate_password_length(password: &SecretString) -> Result<(), String> {
+    let length = password.expose_secret().len();
+    if length <= 12 || length >= 128 {
+        return Err("Password length must be > 12  and < 128".into());
+    }
+    Ok(())
+}
+
+pub fn compute_password_hash(password: SecretString) -> Result<SecretString, anyhow::Error> {
     let salt = SaltString::generate(&mut OsRng);
 
     let password_hash = Argon2::new(
@@ -0,0 +1,278 @@
+use anyhow::Context;
+use axum::{
+    extract::{FromRequestParts, MatchedPath, Request, State},
+    http::{Method, request::Parts},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+};
+use axum_login::tower_sessions::Session;
+use reqwest::StatusCode;
+use uuid::Uuid;
+
+use crate::{
+    authentication::AuthSession, domain::UserRole, routes::session_state::get_session_id,
+    startup::AppState, users::touch_user_session,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    /// Looking at the admin pages and managing your own account.
+    View,
+    /// Writing and publishing issues, along with layouts, images and automations.
+    Publish,
+    ManageSubscribers,
+    ManageUsers,
+}
+
+impl Permission {
+    fn description(&self) -> &'static str {
+        match self {
+            Permission::View => "view the admin pages",
+            Permission::Publish => "publish issues or edit their content",
+            Permission::ManageSubscribers => "manage subscribers",
+            Permission::ManageUsers => "manage users",
+        }
+    }
+}
+
+impl UserRole {
+    pub fn can(&self, permission: Permission) -> bool {
+        match self {
+            UserRole::Owner => true,
+            UserRole::Editor => permission != Permission::ManageUsers,
+            UserRole::Viewer => permission == Permission::View,
+        }
+    }
+}
+
+/// Maps an admin route to the permission it needs. Pages can be looked at by
+/// everybody, changes need the permission of their section. A section missing
+/// here can still be looked at, but changes to it are refused to viewers.
+pub fn required_permission(method: &Method, path: &str) -> Permission {
+    let section = path
+        .trim_start_matches("/admin")
+        .trim_start_matches('/')
+        .split('/')
+        .next()
+        .unwrap_or_default();
+    let is_read = method == Method::GET || method == Method::HEAD;
+    match section {
+        "users" => Permission::ManageUsers,
+        // The publish form is of no use to anybody who cannot publish.
+        "newsletters" => Permission::Publish,
+        // The export hands out every suppressed address at once.
+        "suppressions" if !is_read || path.ends_with("/export") => Permission::ManageSubscribers,
+        // Their own account, which every role may manage.
+        "password" | "logout" | "sessions" | "two-factor" => Permission::View,
+        "automations" | "dashboard" | "issues" | "layouts" | "media" | "suppressions"
+            if is_read =>
+        {
+            Permission::View
+        }
+        "automations" | "layouts" | "media" => Permission::Publish,
+        _ if is_read => Permission::View,
+        _ => Permission::Publish,
+    }
+}
+
+/// The logged in user, available to every handler behind `authorize`.
+#[derive(Debug, Clone)]
+pub struct CurrentUser {
+    pub user_id: Uuid,
+    pub username: String,
+    pub role: UserRole,
+    /// The row of this login in `user_sessions`.
+    pub session_id: Uuid,
+}
+
+impl<S> FromRequestParts<S> for CurrentUser
+where
+    S: Send + Sync,
+{
+    type Rejection = Redirect;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<CurrentUser>()
+            .cloned()
+            .ok_or_else(|| Redirect::to("/login"))
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum AuthorizationError {
+    #[error("You are not allowed to {}.", .0.description())]
+    Forbidden(Permission),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl IntoResponse for AuthorizationError {
+    fn into_response(self) -> Response {
+        match self {
+            AuthorizationError::Forbidden(_) => {
+                (StatusCode::FORBIDDEN, self.to_string()).into_response()
+            }
+            AuthorizationError::UnexpectedError(err) => {
+                tracing::error!("{:?}", err);
+
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Something went wrong".to_owned(),
+                )
+                    .into_response()
+            }
+        }
+    }
+}
+
+/// Guards the `/admin` router, anybody else is sent to the login page.
+pub async fn is_logged_in(auth_session: AuthSession) -> bool {
+    auth_session.user.is_some()
+}
+
+/// Layered on the `/admin` router inside `login_required`: checks that the
+/// session has not been revoked and that the user's role allows the request,
+/// and makes the user available as `CurrentUser`.
+pub async fn authorize(
+    State(state): State<AppState>,
+    mut auth_session: AuthSession,
+    session: Session,
+    matched_path: MatchedPath,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, AuthorizationError> {
+    let Some(user) = auth_session.user.clone() else {
+        return Ok(Redirect::to("/login").into_response());
+    };
+    let session_id = get_session_id(&session)
+        .await
+        .context("Failed to read the session")?;
+    let is_active = match session_id {
+        Some(session_id) => touch_user_session(&state.pg_pool, user.user_id, session_id)
+            .await
+            .context("Failed to update the session")?,
+        None => false,
+    };
+    let Some(session_id) = session_id.filter(|_| is_active) else {
+        // The session has been revoked or has expired since they logged in.
+        auth_session
+            .logout()
+            .await
+            .context("Failed to log out a revoked session")?;
+        return Ok(Redirect::to("/login").into_response());
+    };
+
+    let permission = required_permission(request.method(), matched_path.as_str());
+    if !user.role.can(permission) {
+        tracing::warn!(
+            user_id = %user.user_id,
+            role = user.role.as_str(),
+            path = matched_path.as_str(),
+            "Refused a request the user has no permission for."
+        );
+        return Err(AuthorizationError::Forbidden(permission));
+    }
+    request.extensions_mut().insert(CurrentUser {
+        user_id: user.user_id,
+        username: user.username,
+        role: user.role,
+        session_id,
+    });
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Permission, required_permission};
+    use crate::domain::UserRole;
+    use axum::http::Method;
+
+    #[test]
+    fn pages_can_be_viewed_by_everybody() {
+        for path in [
+            "/admin/dashboard",
+            "/admin/issues/{issue_id}",
+            "/admin/layouts",
+            "/admin/suppressions",
+        ] {
+            assert_eq!(required_permission(&Method::GET, path), Permission::View);
+        }
+    }
+
+    #[test]
+    fn changes_need_the_permission_of_their_section() {
+        let cases = [
+            ("/admin/newsletters", Permission::Publish),
+            ("/admin/newsletters/preview", Permission::Publish),
+            ("/admin/layouts/{layout_id}/delete", Permission::Publish),
+            ("/admin/media", Permission::Publish),
+            (
+                "/admin/automations/{automation_id}/steps",
+                Permission::Publish,
+            ),
+            ("/admin/suppressions/import", Permission::ManageSubscribers),
+            ("/admin/users/{user_id}/delete", Permission::ManageUsers),
+            ("/admin/password", Permission::View),
+            ("/admin/logout", Permission::View),
+            ("/admin/sessions/{session_id}/revoke", Permission::View),
+            ("/admin/two-factor/enrol", Permission::View),
+        ];
+        for (path, permission) in cases {
+            assert_eq!(required_permission(&Method::POST, path), permission);
+        }
+    }
+
+    #[test]
+    fn the_user_list_and_the_publish_form_are_not_public_to_viewers() {
+        assert_eq!(
+            required_permission(&Method::GET, "/admin/users"),
+            Permission::ManageUsers
+        );
+        assert_eq!(
+            required_permission(&Method::GET, "/admin/newsletters"),
+            Permission::Publish
+        );
+    }
+
+    #[test]
+    fn the_suppression_export_is_not_public_to_viewers() {
+        assert_eq!(
+            required_permission(&Method::GET, "/admin/suppressions/export"),
+            Permission::ManageSubscribers
+        );
+    }
+
+    #[test]
+    fn changes_to_unknown_sections_are_refused_to_viewers() {
+        assert_eq!(
+            required_permission(&Method::GET, "/admin/reports"),
+            Permission::View
+        );
+        assert_eq!(
+            required_permission(&Method::POST, "/admin/reports"),
+            Permission::Publish
+        );
+        assert_eq!(
+            required_permission(&Method::DELETE, "/admin/issues/{issue_id}"),
+            Permission::Publish
+        );
+    }
+
+    #[test]
+    fn roles_grant_decreasing_permissions() {
+        let all = [
+            Permission::View,
+            Permission::Publish,
+            Permission::ManageSubscribers,
+            Permission::ManageUsers,
+        ];
+        assert!(all.iter().all(|p| UserRole::Owner.can(*p)));
+        assert!(!UserRole::Editor.can(Permission::ManageUsers));
+        assert!(UserRole::Editor.can(Permission::Publish));
+        assert!(UserRole::Editor.can(Permission::ManageSubscribers));
+        assert!(UserRole::Viewer.can(Permission::View));
+        assert!(!UserRole::Viewer.can(Permission::Publish));
+    }
+}
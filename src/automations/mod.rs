@@ -0,0 +1,48 @@
+mod persistence;
+
+pub use persistence::{
+    Automation, AutomationStep, cancel_pending_steps, delete_step, get_automation,
+    insert_automation, insert_step, list_automations, list_steps, schedule_automation_steps,
+    set_automation_active,
+};
+
+/// The longest delay a step can be scheduled with, one year.
+const MAX_DELAY_HOURS: u32 = 365 * 24;
+
+/// Converts the delay entered on the admin form into hours.
+pub fn delay_hours(amount: u32, unit: &str) -> Result<i32, String> {
+    let hours = match unit {
+        "hours" => Some(amount),
+        "days" => amount.checked_mul(24),
+        other => return Err(format!("{other} is not a supported delay unit.")),
+    };
+    match hours {
+        Some(hours) if hours <= MAX_DELAY_HOURS => Ok(hours as i32),
+        _ => Err("A step can not be delayed by more than a year.".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::delay_hours;
+    use claim::assert_err;
+
+    #[test]
+    fn days_are_converted_to_hours() {
+        assert_eq!(delay_hours(3, "days"), Ok(72));
+        assert_eq!(delay_hours(5, "hours"), Ok(5));
+        assert_eq!(delay_hours(0, "hours"), Ok(0));
+    }
+
+    #[test]
+    fn delays_over_a_year_are_rejected() {
+        assert_eq!(delay_hours(365, "days"), Ok(8760));
+        assert_err!(delay_hours(366, "days"));
+        assert_err!(delay_hours(u32::MAX, "days"));
+    }
+
+    #[test]
+    fn unknown_units_are_rejected() {
+        assert_err!(delay_hours(1, "weeks"));
+    }
+}
@@ -0,0 +1,239 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::domain::IssueSlug;
+
+pub struct Automation {
+    pub automation_id: Uuid,
+    pub name: String,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+pub struct AutomationStep {
+    pub step_id: Uuid,
+    pub delay_hours: i32,
+    pub newsletter_issue_id: Uuid,
+    pub subject: String,
+    /// Subscribers this step is scheduled for but that did not get it yet.
+    pub pending: i64,
+}
+
+#[tracing::instrument(name = "List automations", skip(pool))]
+pub async fn list_automations(pool: &PgPool) -> Result<Vec<Automation>, sqlx::Error> {
+    sqlx::query_as!(
+        Automation,
+        r#"
+        SELECT automation_id, name, is_active, created_at
+        FROM automations
+        ORDER BY created_at
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+#[tracing::instrument(name = "Get an automation", skip(pool))]
+pub async fn get_automation(
+    pool: &PgPool,
+    automation_id: Uuid,
+) -> Result<Option<Automation>, sqlx::Error> {
+    sqlx::query_as!(
+        Automation,
+        r#"
+        SELECT automation_id, name, is_active, created_at
+        FROM automations
+        WHERE automation_id = $1
+        "#,
+        automation_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+#[tracing::instrument(name = "Store an automation", skip(pool))]
+pub async fn insert_automation(pool: &PgPool, name: &str) -> Result<Uuid, sqlx::Error> {
+    let automation_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO automations (automation_id, name, is_active, created_at)
+        VALUES ($1, $2, true, now())
+        "#,
+        automation_id,
+        name
+    )
+    .execute(pool)
+    .await?;
+    Ok(automation_id)
+}
+
+/// Returns `false` if the automation does not exist.
+#[tracing::instrument(name = "Toggle an automation", skip(pool))]
+pub async fn set_automation_active(
+    pool: &PgPool,
+    automation_id: Uuid,
+    is_active: bool,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"UPDATE automations SET is_active = $2 WHERE automation_id = $1"#,
+        automation_id,
+        is_active
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+#[tracing::instrument(name = "List automation steps", skip(pool))]
+pub async fn list_steps(
+    pool: &PgPool,
+    automation_id: Uuid,
+) -> Result<Vec<AutomationStep>, sqlx::Error> {
+    sqlx::query_as!(
+        AutomationStep,
+        r#"
+        SELECT
+            s.step_id,
+            s.delay_hours,
+            s.newsletter_issue_id,
+            i.title AS subject,
+            (
+                SELECT count(*)
+                FROM issue_delivery_queue q
+                WHERE q.newsletter_issue_id = s.newsletter_issue_id
+            ) AS "pending!"
+        FROM automation_steps s
+        JOIN newsletter_issues i USING (newsletter_issue_id)
+        WHERE s.automation_id = $1
+        ORDER BY s.delay_hours, i.published_at
+        "#,
+        automation_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// The message of a step is stored as a private issue so that the delivery
+/// worker sends it like any other issue.
+#[tracing::instrument(
+    name = "Store an automation step",
+    skip(transaction, html_content, text_content)
+)]
+pub async fn insert_step(
+    transaction: &mut Transaction<'static, Postgres>,
+    automation_id: Uuid,
+    delay_hours: i32,
+    subject: &str,
+    html_content: &str,
+    text_content: &str,
+    layout_id: Option<Uuid>,
+) -> Result<Uuid, sqlx::Error> {
+    let newsletter_issue_id = Uuid::new_v4();
+    let slug = IssueSlug::generate(subject, newsletter_issue_id);
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id,
+            title,
+            text_content,
+            html_content,
+            published_at,
+            is_public,
+            slug,
+            layout_id,
+            kind
+        )
+        VALUES ($1, $2, $3, $4, now(), false, $5, $6, 'automation')
+        "#,
+        newsletter_issue_id,
+        subject,
+        text_content,
+        html_content,
+        slug.as_ref(),
+        layout_id
+    );
+    transaction.execute(query).await?;
+
+    let step_id = Uuid::new_v4();
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO automation_steps (step_id, automation_id, delay_hours, newsletter_issue_id)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        step_id,
+        automation_id,
+        delay_hours,
+        newsletter_issue_id
+    );
+    transaction.execute(query).await?;
+    Ok(step_id)
+}
+
+/// Also cancels the deliveries of the step that are still pending.
+/// Returns `false` if the step does not exist.
+#[tracing::instrument(name = "Delete an automation step", skip(transaction))]
+pub async fn delete_step(
+    transaction: &mut Transaction<'static, Postgres>,
+    step_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_queue
+        WHERE newsletter_issue_id = (
+            SELECT newsletter_issue_id FROM automation_steps WHERE step_id = $1
+        )
+        "#,
+        step_id
+    );
+    transaction.execute(query).await?;
+    let query = sqlx::query!(
+        r#"DELETE FROM automation_steps WHERE step_id = $1"#,
+        step_id
+    );
+    let result = transaction.execute(query).await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Enrolls a newly confirmed subscriber in every active automation.
+#[tracing::instrument(name = "Schedule automation steps", skip(transaction))]
+pub async fn schedule_automation_steps(
+    transaction: &mut Transaction<'static, Postgres>,
+    subscriber_email: &str,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email, execute_after)
+        SELECT s.newsletter_issue_id, $1, now() + make_interval(hours => s.delay_hours)
+        FROM automation_steps s
+        JOIN automations a USING (automation_id)
+        WHERE a.is_active
+        ON CONFLICT DO NOTHING
+        "#,
+        subscriber_email
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Cancel pending automation steps", skip(executor))]
+pub async fn cancel_pending_steps<'e, E>(
+    executor: E,
+    subscriber_email: &str,
+) -> Result<u64, sqlx::Error>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_queue
+        WHERE
+            subscriber_email = $1 AND
+            newsletter_issue_id IN (SELECT newsletter_issue_id FROM automation_steps)
+        "#,
+        subscriber_email
+    )
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected())
+}
@@ -1,3 +1,4 @@
+use axum_login::tower_sessions::cookie::SameSite;
 use config::Config;
 use secrecy::{ExposeSecret, SecretString};
 use serde::Deserialize;
@@ -7,7 +8,7 @@ use sqlx::{
     postgres::{PgConnectOptions, PgSslMode},
 };
 
-use crate::{domain::SubscriberEmail, email_client::EmailClient};
+use crate::{domain::SubscriberEmail, email_client::EmailClient, media::LocalStorage};
 
 pub fn get_configuration() -> Result<Settings, config::ConfigError> {
     let base_path = std::env::current_dir().expect("Failed to determine the current directory");
@@ -35,6 +36,14 @@ pub struct Settings {
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
     pub redis_uri: SecretString,
+    pub webhooks: WebhookSettings,
+    pub tracking: TrackingSettings,
+    pub media: MediaSettings,
+    pub two_factor: TwoFactorSettings,
+    pub login_throttle: LoginThrottleSettings,
+    /// Counts password reset requests rather than failed logins.
+    pub password_reset_throttle: LoginThrottleSettings,
+    pub session: SessionSettings,
 }
 #[derive(Clone, Deserialize, Debug)]
 pub struct EmailClientSettings {
@@ -65,6 +74,126 @@ impl EmailClientSettings {
     }
 }
 
+#[derive(Deserialize, Clone, Debug)]
+pub struct WebhookSettings {
+    pub username: String,
+    pub shared_secret: SecretString,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct TrackingSettings {
+    /// Privacy switch: when off no tracking pixel is sent and opens are not recorded,
+    /// regardless of what was chosen for an issue.
+    pub allow_open_tracking: bool,
+    /// Same as `allow_open_tracking`, for rewriting links through `/r/{token}`.
+    pub allow_click_tracking: bool,
+    pub link_signing_key: SecretString,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct MediaSettings {
+    /// Directory the uploaded files are stored in.
+    pub storage_path: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_upload_bytes: usize,
+}
+
+impl MediaSettings {
+    pub fn storage(&self) -> LocalStorage {
+        LocalStorage::new(&self.storage_path)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct TwoFactorSettings {
+    /// Shown next to the account name in authenticator apps.
+    pub issuer: String,
+    /// Base64 encoded AES-256 key the TOTP secrets are stored encrypted with.
+    pub encryption_key: SecretString,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct LoginThrottleSettings {
+    /// Namespaces the Redis keys, for instances that share a Redis server.
+    pub key_prefix: String,
+    /// How long a lockout lasts, and how long failures are remembered for.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub lockout_seconds: u64,
+    pub username: ThrottlePolicy,
+    /// Looser than the username policy, colleagues may share an address.
+    pub address: ThrottlePolicy,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct ThrottlePolicy {
+    /// Failures that do not slow down the next attempt.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub free_attempts: u64,
+    /// Failures after which logging in is locked for `lockout_seconds`.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub lockout_threshold: u64,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct SessionSettings {
+    pub cookie_name: String,
+    /// Only send the cookie over HTTPS. Has to be on in production.
+    pub secure: bool,
+    pub same_site: SameSitePolicy,
+    /// Leave unset for a host-only cookie.
+    pub domain: Option<String>,
+    /// Logged out after this long without a request.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub inactivity_timeout_seconds: i64,
+    /// Logged out this long after logging in, active or not.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub absolute_lifetime_seconds: i64,
+    /// Replaces both of the above when "remember me" is ticked on login.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub remember_me_seconds: i64,
+}
+
+/// How long a login lasts, idle and in total.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionLifetime {
+    pub inactivity_timeout_seconds: i64,
+    pub absolute_lifetime_seconds: i64,
+}
+
+impl SessionSettings {
+    pub fn lifetime(&self, remember_me: bool) -> SessionLifetime {
+        if remember_me {
+            SessionLifetime {
+                inactivity_timeout_seconds: self.remember_me_seconds,
+                absolute_lifetime_seconds: self.remember_me_seconds,
+            }
+        } else {
+            SessionLifetime {
+                inactivity_timeout_seconds: self.inactivity_timeout_seconds,
+                absolute_lifetime_seconds: self.absolute_lifetime_seconds,
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SameSitePolicy {
+    Strict,
+    Lax,
+    None,
+}
+
+impl From<SameSitePolicy> for SameSite {
+    fn from(policy: SameSitePolicy) -> Self {
+        match policy {
+            SameSitePolicy::Strict => SameSite::Strict,
+            SameSitePolicy::Lax => SameSite::Lax,
+            SameSitePolicy::None => SameSite::None,
+        }
+    }
+}
+
 #[derive(Deserialize, Clone, Debug)]
 pub struct DatabaseSettings {
     pub username: String,
@@ -0,0 +1,208 @@
+use anyhow::Context;
+use axum::{
+    body::{Body, Bytes, to_bytes},
+    extract::{FromRequestParts, MatchedPath, Request, State},
+    http::{Method, StatusCode, header::CONTENT_TYPE, request::Parts},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_login::tower_sessions::Session;
+use serde::Deserialize;
+use std::collections::HashMap;
+use subtle::ConstantTimeEq;
+
+use crate::users::generate_token;
+
+const CSRF_TOKEN_KEY: &str = "csrf_token";
+/// The hidden form field that sends the token back.
+pub const CSRF_FIELD: &str = "csrf_token";
+
+#[derive(thiserror::Error, Debug)]
+pub enum CsrfError {
+    #[error(
+        "The form has expired or was not sent from this site. Please reload the page and try again."
+    )]
+    InvalidToken,
+    #[error("The request body is too large.")]
+    BodyTooLarge,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl IntoResponse for CsrfError {
+    fn into_response(self) -> Response {
+        match self {
+            CsrfError::InvalidToken => (StatusCode::FORBIDDEN, self.to_string()).into_response(),
+            CsrfError::BodyTooLarge => {
+                (StatusCode::PAYLOAD_TOO_LARGE, self.to_string()).into_response()
+            }
+            CsrfError::UnexpectedError(err) => {
+                tracing::error!("{:?}", err);
+
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Something went wrong".to_owned(),
+                )
+                    .into_response()
+            }
+        }
+    }
+}
+
+/// The anti-forgery token of the session, created the first time a form
+/// is rendered.
+#[derive(Debug, Clone)]
+pub struct CsrfToken(String);
+
+impl CsrfToken {
+    /// The hidden input every form has to include. Multipart forms need it
+    /// as their first field.
+    pub fn form_field(&self) -> String {
+        format!(
+            r#"<input type="hidden" name="{CSRF_FIELD}" value="{}">"#,
+            self.0
+        )
+    }
+}
+
+impl<S> FromRequestParts<S> for CsrfToken
+where
+    S: Send + Sync,
+{
+    type Rejection = CsrfError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let session = Session::from_request_parts(parts, state)
+            .await
+            .map_err(|(_, message)| anyhow::anyhow!(message))?;
+        if let Some(token) = session
+            .get::<String>(CSRF_TOKEN_KEY)
+            .await
+            .context("Failed to read the session")?
+        {
+            return Ok(Self(token));
+        }
+        let token = generate_token();
+        session
+            .insert(CSRF_TOKEN_KEY, &token)
+            .await
+            .context("Failed to update the session")?;
+        Ok(Self(token))
+    }
+}
+
+/// How much of a body may be read to look for the token, the same limit the
+/// route itself applies.
+#[derive(Debug, Clone)]
+pub struct CsrfBodyLimits {
+    default: usize,
+    routes: HashMap<&'static str, usize>,
+}
+
+impl CsrfBodyLimits {
+    /// `default` is used for every route without a limit of its own.
+    pub fn new(default: usize) -> Self {
+        Self {
+            default,
+            routes: HashMap::new(),
+        }
+    }
+
+    pub fn route(mut self, path: &'static str, max_bytes: usize) -> Self {
+        self.routes.insert(path, max_bytes);
+        self
+    }
+
+    fn for_path(&self, path: &str) -> usize {
+        self.routes.get(path).copied().unwrap_or(self.default)
+    }
+}
+
+#[derive(Deserialize)]
+struct CsrfFormData {
+    csrf_token: Option<String>,
+}
+
+/// Layered on every route with a form: anything but a read has to send back
+/// the token of its session. Sessions without a token are refused before the
+/// body is read, otherwise it is read up to the limit of the route and handed
+/// on untouched.
+pub async fn verify_csrf_token(
+    State(limits): State<CsrfBodyLimits>,
+    session: Session,
+    request: Request,
+    next: Next,
+) -> Result<Response, CsrfError> {
+    if matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) {
+        return Ok(next.run(request).await);
+    }
+    let path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_owned())
+        .unwrap_or_default();
+    let Some(expected) = session
+        .get::<String>(CSRF_TOKEN_KEY)
+        .await
+        .context("Failed to read the session")?
+    else {
+        tracing::warn!(
+            path,
+            "Refused a request from a session without a CSRF token."
+        );
+        return Err(CsrfError::InvalidToken);
+    };
+
+    let (parts, body) = request.into_parts();
+    let body = to_bytes(body, limits.for_path(&path))
+        .await
+        .map_err(|_| CsrfError::BodyTooLarge)?;
+    let content_type = parts
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let submitted = if content_type.starts_with("multipart/form-data") {
+        multipart_token(content_type, body.clone()).await
+    } else {
+        serde_urlencoded::from_bytes::<CsrfFormData>(&body)
+            .ok()
+            .and_then(|form| form.csrf_token)
+    };
+
+    let is_valid =
+        submitted.is_some_and(|submitted| expected.as_bytes().ct_eq(submitted.as_bytes()).into());
+    if !is_valid {
+        tracing::warn!(path, "Refused a request without a valid CSRF token.");
+        return Err(CsrfError::InvalidToken);
+    }
+    Ok(next.run(Request::from_parts(parts, Body::from(body))).await)
+}
+
+async fn multipart_token(content_type: &str, body: Bytes) -> Option<String> {
+    let boundary = multer::parse_boundary(content_type).ok()?;
+    let mut multipart = multer::Multipart::new(Body::from(body).into_data_stream(), boundary);
+    while let Some(field) = multipart.next_field().await.ok()? {
+        if field.name() == Some(CSRF_FIELD) {
+            return field.text().await.ok();
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CsrfBodyLimits;
+
+    #[test]
+    fn routes_without_a_limit_of_their_own_get_the_default() {
+        let limits = CsrfBodyLimits::new(10).route("/admin/media", 100);
+
+        assert_eq!(limits.for_path("/admin/media"), 100);
+        assert_eq!(limits.for_path("/login"), 10);
+        assert_eq!(limits.for_path(""), 10);
+    }
+}
@@ -0,0 +1,347 @@
+use std::fmt::Write;
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::{DateTime, Datelike, TimeDelta, Utc};
+use sqlx::{Executor, PgPool};
+use uuid::Uuid;
+
+use crate::configuration::Settings;
+use crate::domain::{DeliveryFrequency, IssueSlug};
+use crate::html::escape_html;
+use crate::issue_delivery_worker::ExecutionOutcome;
+use crate::layouts::get_default_layout_id;
+use crate::startup::get_connection_pool;
+
+/// The last complete period of a digest: the previous day, or the previous week
+/// from Monday to Monday. Periods are in UTC.
+pub fn previous_period(
+    frequency: DeliveryFrequency,
+    now: DateTime<Utc>,
+) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let today = now.date_naive().and_hms_opt(0, 0, 0)?.and_utc();
+    match frequency {
+        DeliveryFrequency::Immediate => None,
+        DeliveryFrequency::Daily => Some((today - TimeDelta::days(1), today)),
+        DeliveryFrequency::Weekly => {
+            let days_since_monday = i64::from(now.weekday().num_days_from_monday());
+            let end = today - TimeDelta::days(days_since_monday);
+            Some((end - TimeDelta::weeks(1), end))
+        }
+    }
+}
+
+/// The oldest complete period that has no digest yet, given the end of the last
+/// one. Periods missed while nothing was running are caught up one by one; with
+/// no digest at all only the last complete period is built.
+pub fn next_period(
+    frequency: DeliveryFrequency,
+    last_period_end: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let latest = previous_period(frequency, now)?;
+    let length = match frequency {
+        DeliveryFrequency::Immediate => return None,
+        DeliveryFrequency::Daily => TimeDelta::days(1),
+        DeliveryFrequency::Weekly => TimeDelta::weeks(1),
+    };
+    match last_period_end {
+        Some(end) if end < latest.1 => Some((end, end + length)),
+        _ => Some(latest),
+    }
+}
+
+pub fn digest_title(frequency: DeliveryFrequency, period_start: DateTime<Utc>) -> String {
+    match frequency {
+        DeliveryFrequency::Weekly => {
+            format!(
+                "Weekly digest: week of {}",
+                period_start.format("%B %-d, %Y")
+            )
+        }
+        _ => format!("Daily digest: {}", period_start.format("%B %-d, %Y")),
+    }
+}
+
+pub struct DigestEntry {
+    pub title: String,
+    pub html_content: String,
+    pub text_content: String,
+}
+
+/// Puts the issues one after the other, each under its own heading.
+pub fn render_digest(entries: &[DigestEntry]) -> (String, String) {
+    let mut html = String::new();
+    let mut text = String::new();
+    for (i, entry) in entries.iter().enumerate() {
+        if i > 0 {
+            html.push_str("<hr>");
+            text.push_str("\n\n");
+        }
+        write!(
+            html,
+            "<h2>{}</h2>{}",
+            escape_html(&entry.title),
+            entry.html_content
+        )
+        .unwrap();
+        write!(
+            text,
+            "{}\n{}\n\n{}",
+            entry.title,
+            "=".repeat(entry.title.chars().count()),
+            entry.text_content.trim_end()
+        )
+        .unwrap();
+    }
+    (html, text)
+}
+
+pub async fn run_digest_scheduler_until_stopped(
+    configuration: Settings,
+) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database);
+    loop {
+        for frequency in [DeliveryFrequency::Daily, DeliveryFrequency::Weekly] {
+            // Errors are logged by `try_build_digest`, the period is retried on the next round.
+            while let Ok(ExecutionOutcome::TaskCompleted) =
+                try_build_digest(&connection_pool, frequency, Utc::now()).await
+            {}
+        }
+        tokio::time::sleep(Duration::from_secs(60)).await;
+    }
+}
+
+/// Builds the digest of the oldest complete period that has none yet and enqueues
+/// it for the subscribers who asked for it. Returns `EmptyQueue` once every
+/// period is done.
+#[tracing::instrument(skip(pool), err)]
+pub async fn try_build_digest(
+    pool: &PgPool,
+    frequency: DeliveryFrequency,
+    now: DateTime<Utc>,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let last_period_end = sqlx::query_scalar!(
+        r#"SELECT max(period_end) FROM digests WHERE frequency = $1"#,
+        frequency.as_str()
+    )
+    .fetch_one(&mut *transaction)
+    .await?;
+    let (period_start, period_end) =
+        next_period(frequency, last_period_end, now).context("Immediate delivery has no digest")?;
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO digests (frequency, period_start, period_end, created_at)
+        VALUES ($1, $2, $3, now())
+        ON CONFLICT DO NOTHING
+        "#,
+        frequency.as_str(),
+        period_start,
+        period_end
+    );
+    if transaction.execute(query).await?.rows_affected() == 0 {
+        transaction.rollback().await?;
+        return Ok(ExecutionOutcome::EmptyQueue);
+    }
+
+    let entries = sqlx::query_as!(
+        DigestEntry,
+        r#"
+        SELECT title, html_content, text_content
+        FROM newsletter_issues
+        WHERE kind = 'issue' AND published_at >= $1 AND published_at < $2
+        ORDER BY published_at
+        "#,
+        period_start,
+        period_end
+    )
+    .fetch_all(&mut *transaction)
+    .await?;
+    if entries.is_empty() {
+        tracing::info!("Nothing was published, skipping the digest.");
+        transaction.commit().await?;
+        return Ok(ExecutionOutcome::TaskCompleted);
+    }
+
+    let (html_content, text_content) = render_digest(&entries);
+    let title = digest_title(frequency, period_start);
+    let newsletter_issue_id = Uuid::new_v4();
+    let slug = IssueSlug::generate(&title, newsletter_issue_id);
+    let layout_id = get_default_layout_id(pool).await?;
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id,
+            title,
+            text_content,
+            html_content,
+            published_at,
+            is_public,
+            slug,
+            layout_id,
+            kind
+        )
+        VALUES ($1, $2, $3, $4, now(), false, $5, $6, 'digest')
+        "#,
+        newsletter_issue_id,
+        title,
+        text_content,
+        html_content,
+        slug.as_ref(),
+        layout_id
+    );
+    transaction.execute(query).await?;
+    let query = sqlx::query!(
+        r#"
+        UPDATE digests SET newsletter_issue_id = $3
+        WHERE frequency = $1 AND period_start = $2
+        "#,
+        frequency.as_str(),
+        period_start,
+        newsletter_issue_id
+    );
+    transaction.execute(query).await?;
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email)
+        SELECT $1, email
+        FROM subscriptions
+        WHERE status = 'confirmed' AND delivery_frequency = $2
+        "#,
+        newsletter_issue_id,
+        frequency.as_str()
+    );
+    let enqueued = transaction.execute(query).await?.rows_affected();
+    transaction.commit().await?;
+    tracing::info!(issues = entries.len(), enqueued, "Enqueued a digest.");
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DigestEntry, digest_title, next_period, previous_period, render_digest};
+    use crate::domain::DeliveryFrequency;
+    use chrono::{DateTime, Utc};
+    use claim::assert_none;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn the_daily_period_is_the_previous_day() {
+        let period = previous_period(DeliveryFrequency::Daily, at("2025-07-16T09:30:00Z"));
+        assert_eq!(
+            period,
+            Some((at("2025-07-15T00:00:00Z"), at("2025-07-16T00:00:00Z")))
+        );
+    }
+
+    #[test]
+    fn the_weekly_period_runs_from_monday_to_monday() {
+        // A Wednesday
+        let period = previous_period(DeliveryFrequency::Weekly, at("2025-07-16T09:30:00Z"));
+        assert_eq!(
+            period,
+            Some((at("2025-07-07T00:00:00Z"), at("2025-07-14T00:00:00Z")))
+        );
+        // Right at the start of a Monday
+        let period = previous_period(DeliveryFrequency::Weekly, at("2025-07-14T00:00:00Z"));
+        assert_eq!(
+            period,
+            Some((at("2025-07-07T00:00:00Z"), at("2025-07-14T00:00:00Z")))
+        );
+    }
+
+    #[test]
+    fn immediate_delivery_has_no_period() {
+        assert_none!(previous_period(
+            DeliveryFrequency::Immediate,
+            at("2025-07-16T09:30:00Z")
+        ));
+    }
+
+    #[test]
+    fn missed_periods_are_caught_up_oldest_first() {
+        let now = at("2025-07-16T09:30:00Z");
+        assert_eq!(
+            next_period(
+                DeliveryFrequency::Daily,
+                Some(at("2025-07-13T00:00:00Z")),
+                now
+            ),
+            Some((at("2025-07-13T00:00:00Z"), at("2025-07-14T00:00:00Z")))
+        );
+        assert_eq!(
+            next_period(
+                DeliveryFrequency::Weekly,
+                Some(at("2025-06-30T00:00:00Z")),
+                now
+            ),
+            Some((at("2025-06-30T00:00:00Z"), at("2025-07-07T00:00:00Z")))
+        );
+    }
+
+    #[test]
+    fn without_missed_periods_the_last_one_is_next() {
+        let now = at("2025-07-16T09:30:00Z");
+        let last = Some((at("2025-07-15T00:00:00Z"), at("2025-07-16T00:00:00Z")));
+        assert_eq!(next_period(DeliveryFrequency::Daily, None, now), last);
+        assert_eq!(
+            next_period(
+                DeliveryFrequency::Daily,
+                Some(at("2025-07-16T00:00:00Z")),
+                now
+            ),
+            last
+        );
+    }
+
+    #[test]
+    fn the_title_names_the_period() {
+        let start = at("2025-07-07T00:00:00Z");
+        assert_eq!(
+            digest_title(DeliveryFrequency::Weekly, start),
+            "Weekly digest: week of July 7, 2025"
+        );
+        assert_eq!(
+            digest_title(DeliveryFrequency::Daily, start),
+            "Daily digest: July 7, 2025"
+        );
+    }
+
+    #[test]
+    fn issues_are_rendered_in_order_under_their_title() {
+        let entries = [
+            DigestEntry {
+                title: "First".into(),
+                html_content: "<p>One</p>".into(),
+                text_content: "One\n".into(),
+            },
+            DigestEntry {
+                title: "Second".into(),
+                html_content: "<p>Two</p>".into(),
+                text_content: "Two".into(),
+            },
+        ];
+        let (html, text) = render_digest(&entries);
+        assert_eq!(
+            html,
+            "<h2>First</h2><p>One</p><hr><h2>Second</h2><p>Two</p>"
+        );
+        assert_eq!(text, "First\n=====\n\nOne\n\nSecond\n======\n\nTwo");
+    }
+
+    #[test]
+    fn titles_are_escaped_in_the_html() {
+        let entries = [DigestEntry {
+            title: "Fish & <chips>".into(),
+            html_content: "<p>One</p>".into(),
+            text_content: "One".into(),
+        }];
+        let (html, text) = render_digest(&entries);
+        assert_eq!(html, "<h2>Fish &amp; &lt;chips&gt;</h2><p>One</p>");
+        assert!(text.starts_with("Fish & <chips>\n"));
+    }
+}
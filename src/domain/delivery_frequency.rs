@@ -0,0 +1,56 @@
+/// How often a subscriber wants to hear from us. Digest subscribers get one
+/// combined email per period instead of every issue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryFrequency {
+    Immediate,
+    Daily,
+    Weekly,
+}
+
+impl DeliveryFrequency {
+    pub const ALL: [DeliveryFrequency; 3] = [Self::Immediate, Self::Daily, Self::Weekly];
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "immediate" => Ok(Self::Immediate),
+            "daily" => Ok(Self::Daily),
+            "weekly" => Ok(Self::Weekly),
+            other => Err(format!("{other} is not a supported delivery frequency.")),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Immediate => "immediate",
+            Self::Daily => "daily",
+            Self::Weekly => "weekly",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Immediate => "Every issue as soon as it is published",
+            Self::Daily => "A daily digest",
+            Self::Weekly => "A weekly digest",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DeliveryFrequency;
+    use claim::assert_err;
+
+    #[test]
+    fn every_frequency_can_be_parsed_back() {
+        for frequency in DeliveryFrequency::ALL {
+            assert_eq!(DeliveryFrequency::parse(frequency.as_str()), Ok(frequency));
+        }
+    }
+
+    #[test]
+    fn unknown_frequencies_are_rejected() {
+        assert_err!(DeliveryFrequency::parse("monthly"));
+        assert_err!(DeliveryFrequency::parse("Daily"));
+    }
+}
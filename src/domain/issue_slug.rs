@@ -0,0 +1,75 @@
+use uuid::Uuid;
+
+/// The url friendly name of an issue in the public archive.
+#[derive(Debug, Clone)]
+pub struct IssueSlug(String);
+
+impl IssueSlug {
+    const MAX_TITLE_LENGTH: usize = 60;
+
+    /// Builds the slug from the title. The start of the issue id keeps slugs
+    /// unique when titles repeat.
+    pub fn generate(title: &str, newsletter_issue_id: Uuid) -> IssueSlug {
+        let mut slug = String::new();
+        for c in title.chars() {
+            if c.is_ascii_alphanumeric() {
+                slug.push(c.to_ascii_lowercase());
+            } else if !slug.is_empty() && !slug.ends_with('-') {
+                slug.push('-');
+            }
+        }
+        slug.truncate(Self::MAX_TITLE_LENGTH);
+        let slug = slug.trim_end_matches('-');
+        let slug = if slug.is_empty() { "issue" } else { slug };
+        let id = newsletter_issue_id.simple().to_string();
+        Self(format!("{slug}-{}", &id[..8]))
+    }
+}
+
+impl AsRef<str> for IssueSlug {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for IssueSlug {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::IssueSlug;
+    use uuid::Uuid;
+
+    fn slug(title: &str) -> String {
+        let id = Uuid::parse_str("0f1e2d3c-4b5a-6978-8796-a5b4c3d2e1f0").unwrap();
+        IssueSlug::generate(title, id).to_string()
+    }
+
+    #[test]
+    fn titles_are_lowercased_and_joined_by_dashes() {
+        assert_eq!(
+            slug("Hello, World: Issue #3!"),
+            "hello-world-issue-3-0f1e2d3c"
+        );
+    }
+
+    #[test]
+    fn non_ascii_characters_are_dropped() {
+        assert_eq!(slug("Ümlauts über alles"), "mlauts-ber-alles-0f1e2d3c");
+    }
+
+    #[test]
+    fn titles_without_usable_characters_get_a_placeholder() {
+        assert_eq!(slug("🎉 !!"), "issue-0f1e2d3c");
+    }
+
+    #[test]
+    fn long_titles_are_truncated() {
+        let slug = slug(&"word ".repeat(50));
+        assert!(slug.len() <= 60 + 9);
+        assert!(!slug.contains("--"));
+    }
+}
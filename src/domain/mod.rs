@@ -1,7 +1,15 @@
+mod delivery_frequency;
+mod issue_slug;
 mod new_subscriber;
 mod subscriber_email;
 mod subscriber_name;
+mod suppression_target;
+mod user_role;
 
+pub use delivery_frequency::DeliveryFrequency;
+pub use issue_slug::IssueSlug;
 pub use new_subscriber::NewSubscriber;
 pub use subscriber_email::SubscriberEmail;
 pub use subscriber_name::SubscriberName;
+pub use suppression_target::SuppressionTarget;
+pub use user_role::UserRole;
@@ -0,0 +1,88 @@
+use crate::domain::SubscriberEmail;
+
+/// A single address or a whole domain that must never receive an email.
+#[derive(Debug, Clone)]
+pub enum SuppressionTarget {
+    Email(SubscriberEmail),
+    Domain(String),
+}
+
+impl SuppressionTarget {
+    pub fn parse(s: String) -> Result<SuppressionTarget, String> {
+        let s = s.trim().to_lowercase();
+        if s.contains('@') {
+            return SubscriberEmail::parse(s).map(Self::Email);
+        }
+        let is_valid_label = |label: &str| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        };
+        let labels: Vec<&str> = s.split('.').collect();
+        if s.len() <= 253 && labels.len() >= 2 && labels.iter().all(|l| is_valid_label(l)) {
+            Ok(Self::Domain(s))
+        } else {
+            Err(format!("{s} is neither a valid email nor a valid domain."))
+        }
+    }
+
+    pub fn email(&self) -> Option<&str> {
+        match self {
+            Self::Email(email) => Some(email.as_ref()),
+            Self::Domain(_) => None,
+        }
+    }
+
+    pub fn domain(&self) -> Option<&str> {
+        match self {
+            Self::Email(_) => None,
+            Self::Domain(domain) => Some(domain),
+        }
+    }
+}
+
+impl std::fmt::Display for SuppressionTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Email(email) => email.fmt(f),
+            Self::Domain(domain) => domain.fmt(f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SuppressionTarget;
+    use claim::{assert_err, assert_matches};
+
+    #[test]
+    fn an_email_address_is_parsed_as_an_email() {
+        let target = SuppressionTarget::parse("ursula@domain.com".to_string());
+        assert_matches!(target, Ok(SuppressionTarget::Email(_)));
+    }
+
+    #[test]
+    fn a_domain_is_parsed_as_a_domain() {
+        let target = SuppressionTarget::parse(" Mail.Example.com ".to_string()).unwrap();
+        assert_eq!(target.domain(), Some("mail.example.com"));
+    }
+
+    #[test]
+    fn a_domain_without_a_dot_is_rejected() {
+        assert_err!(SuppressionTarget::parse("localhost".to_string()));
+    }
+
+    #[test]
+    fn a_domain_with_invalid_characters_is_rejected() {
+        for domain in ["exa mple.com", "-example.com", "example..com", ""] {
+            assert_err!(SuppressionTarget::parse(domain.to_string()));
+        }
+    }
+
+    #[test]
+    fn an_invalid_email_is_rejected() {
+        assert_err!(SuppressionTarget::parse("@domain.com".to_string()));
+    }
+}
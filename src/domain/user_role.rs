@@ -0,0 +1,58 @@
+/// What an admin user is allowed to do, see `crate::authorization`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserRole {
+    /// Everything, including managing the other users.
+    Owner,
+    /// Publishes issues and manages content and subscribers.
+    Editor,
+    /// Read-only access to the admin pages.
+    Viewer,
+}
+
+impl UserRole {
+    pub const ALL: [UserRole; 3] = [Self::Owner, Self::Editor, Self::Viewer];
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "owner" => Ok(Self::Owner),
+            "editor" => Ok(Self::Editor),
+            "viewer" => Ok(Self::Viewer),
+            other => Err(format!("{other} is not a valid role.")),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Owner => "owner",
+            Self::Editor => "editor",
+            Self::Viewer => "viewer",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Owner => "Owner",
+            Self::Editor => "Editor",
+            Self::Viewer => "Viewer",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UserRole;
+    use claim::assert_err;
+
+    #[test]
+    fn every_role_can_be_parsed_back() {
+        for role in UserRole::ALL {
+            assert_eq!(UserRole::parse(role.as_str()), Ok(role));
+        }
+    }
+
+    #[test]
+    fn unknown_roles_are_rejected() {
+        assert_err!(UserRole::parse("admin"));
+        assert_err!(UserRole::parse("Owner"));
+    }
+}
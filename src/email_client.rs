@@ -2,7 +2,7 @@ use reqwest::Client;
 use secrecy::{ExposeSecret, SecretString};
 use serde::Serialize;
 
-use crate::domain::SubscriberEmail;
+use crate::{attachments::EncodedAttachment, domain::SubscriberEmail};
 #[derive(Debug)]
 pub struct EmailClient {
     http_client: Client,
@@ -18,6 +18,17 @@ struct SendEmailRequest<'a> {
     subject: &'a str,
     html_body: &'a str,
     text_body: &'a str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<EmailAttachment<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct EmailAttachment<'a> {
+    name: &'a str,
+    /// Base64 encoded.
+    content: &'a str,
+    content_type: &'a str,
 }
 
 impl EmailClient {
@@ -42,6 +53,18 @@ impl EmailClient {
         subject: &str,
         html_content: &str,
         text_content: &str,
+    ) -> Result<(), reqwest::Error> {
+        self.send_email_with_attachments(recipient, subject, html_content, text_content, &[])
+            .await
+    }
+
+    pub async fn send_email_with_attachments(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        attachments: &[EncodedAttachment],
     ) -> Result<(), reqwest::Error> {
         let url = format!("{}/email", self.base_url);
         let request_body = SendEmailRequest {
@@ -50,6 +73,14 @@ impl EmailClient {
             subject,
             html_body: html_content,
             text_body: text_content,
+            attachments: attachments
+                .iter()
+                .map(|a| EmailAttachment {
+                    name: &a.file_name,
+                    content: &a.content,
+                    content_type: &a.content_type,
+                })
+                .collect(),
         };
         self.http_client
             .post(&url)
@@ -78,10 +109,10 @@ mod tests {
     use secrecy::SecretString;
     use wiremock::{
         Mock, MockServer, ResponseTemplate,
-        matchers::{any, header, header_exists, method, path},
+        matchers::{any, body_partial_json, header, header_exists, method, path},
     };
 
-    use crate::{domain::SubscriberEmail, email_client::EmailClient};
+    use crate::{attachments::Attachment, domain::SubscriberEmail, email_client::EmailClient};
 
     fn subject() -> String {
         Sentence(1..2).fake()
@@ -196,6 +227,43 @@ mod tests {
         assert_err!(outcome);
     }
 
+    #[tokio::test]
+    async fn attachments_are_sent_base64_encoded() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        let attachment = Attachment {
+            file_name: "notes.txt".into(),
+            content_type: "text/plain".into(),
+            content: b"hello".to_vec(),
+        };
+
+        Mock::given(body_partial_json(serde_json::json!({
+            "Attachments": [{
+                "Name": "notes.txt",
+                "Content": "aGVsbG8=",
+                "ContentType": "text/plain"
+            }]
+        })))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+        // Act
+        let outcome = email_client
+            .send_email_with_attachments(
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+                &[attachment.encode()],
+            )
+            .await;
+
+        assert_ok!(outcome);
+    }
+
     struct SendEmailBodyMatcher;
 
     impl wiremock::Match for SendEmailBodyMatcher {
@@ -0,0 +1,408 @@
+use lol_html::html_content::Element;
+use lol_html::{
+    ElementContentHandlers, HandlerResult, RewriteStrSettings, Selector, element, rewrite_str, text,
+};
+use std::borrow::Cow;
+
+/// Keeps the original `style` attribute out of the way while rules are applied, so
+/// it can be added last and still win, just like it would in a browser.
+const STASHED_STYLE: &str = "data-original-style";
+
+pub struct InlinedCss {
+    pub html: String,
+    /// Parts of the stylesheets that could not be inlined and were dropped.
+    pub warnings: Vec<String>,
+}
+
+struct Rule {
+    selector: Selector,
+    declarations: Declarations,
+    specificity: (usize, usize, usize),
+}
+
+/// The declarations of a rule or a `style` attribute, split by whether they
+/// are marked `!important`.
+#[derive(Default)]
+struct Declarations {
+    normal: String,
+    important: String,
+}
+
+/// Moves the rules of every `<style>` block into the `style` attributes of the
+/// elements they match and removes the blocks. Many mail clients ignore them.
+///
+/// The declarations are written in cascade order, so the last one of a property
+/// is the one that applies: rules by specificity, then the existing `style`
+/// attribute, then `!important` rules by specificity, then `!important`
+/// declarations of the `style` attribute. Rules the rewriter can not match
+/// statically (`@media`, `:hover`, pseudo-elements and the like) are dropped and
+/// reported in the warnings. Shorthands and longhands are not merged.
+pub fn inline_css(html: &str) -> Result<InlinedCss, lol_html::errors::RewritingError> {
+    let mut css = String::new();
+    rewrite_str(
+        html,
+        RewriteStrSettings {
+            element_content_handlers: vec![text!("style", |chunk| {
+                css.push_str(chunk.as_str());
+                Ok(())
+            })],
+            ..RewriteStrSettings::new()
+        },
+    )?;
+    let (mut rules, warnings) = parse_stylesheet(&css);
+    // Elements run their handlers in registration order, so the most specific
+    // rule is registered last and gets the final say.
+    rules.sort_by_key(|rule| rule.specificity);
+
+    let mut handlers = vec![
+        element!("style", |el| {
+            el.remove();
+            Ok(())
+        }),
+        element!("[style]", |el| {
+            let style = el.get_attribute("style").unwrap_or_default();
+            el.set_attribute(STASHED_STYLE, &style)?;
+            el.remove_attribute("style");
+            Ok(())
+        }),
+    ];
+    let mut important_rules = Vec::new();
+    for rule in rules {
+        let Declarations { normal, important } = rule.declarations;
+        if !important.is_empty() {
+            important_rules.push((rule.selector.clone(), important));
+        }
+        handlers.push(style_handler(rule.selector, move |el| {
+            append_style(el, &normal)
+        }));
+    }
+    handlers.push(element!("[style]", |el| {
+        let style = el.get_attribute(STASHED_STYLE).unwrap_or_default();
+        append_style(el, &split_declarations(&style).normal)
+    }));
+    for (selector, important) in important_rules {
+        handlers.push(style_handler(selector, move |el| {
+            append_style(el, &important)
+        }));
+    }
+    handlers.push(element!("[style]", |el| {
+        let style = el.get_attribute(STASHED_STYLE).unwrap_or_default();
+        el.remove_attribute(STASHED_STYLE);
+        append_style(el, &split_declarations(&style).important)
+    }));
+
+    let html = rewrite_str(
+        html,
+        RewriteStrSettings {
+            element_content_handlers: handlers,
+            ..RewriteStrSettings::new()
+        },
+    )?;
+    Ok(InlinedCss { html, warnings })
+}
+
+fn style_handler<'h>(
+    selector: Selector,
+    handler: impl FnMut(&mut Element) -> HandlerResult + 'h,
+) -> (Cow<'static, Selector>, ElementContentHandlers<'h>) {
+    (
+        Cow::Owned(selector),
+        ElementContentHandlers::default().element(handler),
+    )
+}
+
+fn append_style(el: &mut Element, declarations: &str) -> HandlerResult {
+    let declarations = declarations.trim().trim_end_matches(';').trim();
+    if declarations.is_empty() {
+        return Ok(());
+    }
+    let style = match el.get_attribute("style") {
+        Some(existing) if !existing.trim().is_empty() => {
+            format!("{}; {declarations}", existing.trim().trim_end_matches(';'))
+        }
+        _ => declarations.to_string(),
+    };
+    el.set_attribute("style", &style)?;
+    Ok(())
+}
+
+fn parse_stylesheet(css: &str) -> (Vec<Rule>, Vec<String>) {
+    let css = strip_comments(css);
+    let mut rules = Vec::new();
+    let mut warnings = Vec::new();
+    let mut rest = css.trim_start();
+    while !rest.is_empty() {
+        let Some(open) = rest.find('{') else {
+            warnings.push(format!("Ignored CSS outside of a rule: {}", rest.trim()));
+            break;
+        };
+        // Statements like @import or @charset have no block.
+        if rest.starts_with('@')
+            && let Some(end) = rest.find(';').filter(|end| *end < open)
+        {
+            warnings.push(format!(
+                "{} can not be inlined and was dropped.",
+                rest[..end].trim()
+            ));
+            rest = rest[end + 1..].trim_start();
+            continue;
+        }
+        let Some(close) = find_closing_brace(rest, open) else {
+            warnings.push("Ignored a CSS rule that is never closed.".into());
+            break;
+        };
+        let prelude = rest[..open].trim();
+        let declarations = rest[open + 1..close].trim();
+        if prelude.starts_with('@') {
+            warnings.push(format!("{prelude} can not be inlined and was dropped."));
+        } else {
+            for selector in prelude.split(',').map(str::trim) {
+                match selector.parse::<Selector>() {
+                    Ok(parsed) => rules.push(Rule {
+                        selector: parsed,
+                        declarations: split_declarations(declarations),
+                        specificity: specificity(selector),
+                    }),
+                    Err(_) => warnings.push(format!(
+                        "The selector {selector} can not be inlined and was dropped."
+                    )),
+                }
+            }
+        }
+        rest = rest[close + 1..].trim_start();
+    }
+    (rules, warnings)
+}
+
+fn strip_comments(css: &str) -> String {
+    let mut stripped = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = rest.find("/*") {
+        stripped.push_str(&rest[..start]);
+        rest = match rest[start + 2..].find("*/") {
+            Some(end) => &rest[start + 2 + end + 2..],
+            None => "",
+        };
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+fn find_closing_brace(css: &str, open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in css[open..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(open + i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn split_declarations(declarations: &str) -> Declarations {
+    let mut split = Declarations::default();
+    for declaration in split_outside_of_strings(declarations, ';') {
+        let declaration = declaration.trim();
+        if declaration.is_empty() {
+            continue;
+        }
+        let is_important = declaration
+            .to_ascii_lowercase()
+            .replace(char::is_whitespace, "")
+            .ends_with("!important");
+        let target = if is_important {
+            &mut split.important
+        } else {
+            &mut split.normal
+        };
+        if !target.is_empty() {
+            target.push_str("; ");
+        }
+        target.push_str(declaration);
+    }
+    split
+}
+
+/// Splits on `separator` unless it is quoted or inside parentheses, like the
+/// `;` of `url("data:image/png;base64,...")`.
+fn split_outside_of_strings(s: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quote = None;
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => depth = depth.saturating_sub(1),
+            (None, c) if c == separator && depth == 0 => {
+                parts.push(&s[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+/// The specificity of a selector the rewriter accepted: (ids, classes,
+/// attributes and pseudo-classes, element names). `:not()` counts as its
+/// argument, like in CSS.
+fn specificity(selector: &str) -> (usize, usize, usize) {
+    let (mut ids, mut classes, mut elements) = (0, 0, 0);
+    let mut chars = selector.chars().peekable();
+    let mut starts_compound = true;
+    while let Some(c) = chars.next() {
+        match c {
+            '#' => ids += 1,
+            '.' => classes += 1,
+            '[' => {
+                classes += 1;
+                skip_past(&mut chars, ']');
+            }
+            ':' => {
+                let name: String =
+                    std::iter::from_fn(|| chars.next_if(|c| c.is_alphanumeric() || *c == '-'))
+                        .collect();
+                if chars.next_if_eq(&'(').is_some() {
+                    if name.eq_ignore_ascii_case("not") {
+                        starts_compound = true;
+                        continue;
+                    }
+                    skip_past(&mut chars, ')');
+                }
+                classes += 1;
+            }
+            c if starts_compound && c.is_alphabetic() => elements += 1,
+            _ => {}
+        }
+        starts_compound = matches!(c, ' ' | '>' | '+' | '~' | '(' | ')');
+        if c.is_alphanumeric() || matches!(c, '-' | '_') {
+            // Still inside the name of the element, class or id.
+            starts_compound = false;
+        }
+    }
+    (ids, classes, elements)
+}
+
+fn skip_past(chars: &mut impl Iterator<Item = char>, end: char) {
+    let mut quote = None;
+    for c in chars.by_ref() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, c) if c == end => return,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{inline_css, specificity};
+
+    #[test]
+    fn rules_are_moved_into_style_attributes() {
+        let inlined = inline_css(
+            r#"<style>p { color: red; } .big { font-size: 20px }</style><p class="big">Hi</p>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            inlined.html,
+            r#"<p class="big" style="color: red; font-size: 20px">Hi</p>"#
+        );
+        assert!(inlined.warnings.is_empty());
+    }
+
+    #[test]
+    fn more_specific_rules_and_inline_styles_win() {
+        let inlined = inline_css(
+            r#"<style>#x { color: blue } .y { color: green } p { color: red }</style><p id="x" class="y" style="margin: 0">Hi</p>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            inlined.html,
+            r#"<p id="x" class="y" style="color: red; color: green; color: blue; margin: 0">Hi</p>"#
+        );
+    }
+
+    #[test]
+    fn important_declarations_win_over_more_specific_rules_and_inline_styles() {
+        let inlined = inline_css(
+            r#"<style>p { color: red !important; margin: 1px } #x { color: blue }</style><p id="x" style="color: green; margin: 0">Hi</p>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            inlined.html,
+            r#"<p id="x" style="margin: 1px; color: blue; color: green; margin: 0; color: red !important">Hi</p>"#
+        );
+    }
+
+    #[test]
+    fn important_inline_styles_win_over_important_rules() {
+        let inlined = inline_css(
+            r#"<style>#x { color: blue !important }</style><p id="x" style="color: green !important">Hi</p>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            inlined.html,
+            r#"<p id="x" style="color: blue !important; color: green !important">Hi</p>"#
+        );
+    }
+
+    #[test]
+    fn semicolons_inside_values_do_not_split_declarations() {
+        let inlined = inline_css(
+            r#"<style>p { background: url("data:image/png;base64,AA") !important }</style><p>Hi</p>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            inlined.html,
+            r#"<p style="background: url(&quot;data:image/png;base64,AA&quot;) !important">Hi</p>"#
+        );
+    }
+
+    #[test]
+    fn specificity_follows_css() {
+        let cases = [
+            ("p", (0, 0, 1)),
+            ("div > p.intro", (0, 1, 2)),
+            ("#main a[href$=\".pdf\"]", (1, 1, 1)),
+            ("li:nth-child(2n+1)", (0, 1, 1)),
+            ("p:not(.intro)", (0, 1, 1)),
+            ("ul li:first-child span", (0, 1, 3)),
+            ("*", (0, 0, 0)),
+        ];
+        for (selector, expected) in cases {
+            assert_eq!(specificity(selector), expected, "{selector}");
+        }
+    }
+
+    #[test]
+    fn rules_that_can_not_be_inlined_are_reported() {
+        let inlined = inline_css(
+            "<style>/* comment */ @import url(a.css); @media (max-width: 600px) { p { color: red } } a:hover { color: red } p::first-line { color: red }</style><p>Hi</p>",
+        )
+        .unwrap();
+        assert_eq!(inlined.html, "<p>Hi</p>");
+        assert_eq!(inlined.warnings.len(), 4);
+        assert!(inlined.warnings[0].starts_with("@import"));
+        assert!(inlined.warnings[1].starts_with("@media"));
+    }
+
+    #[test]
+    fn html_without_styles_is_left_alone() {
+        let html = r#"<p style="color: red">Hi</p>"#;
+        assert_eq!(inline_css(html).unwrap().html, html);
+    }
+}
@@ -0,0 +1,31 @@
+mod css_inline;
+mod plain_text;
+mod sanitize;
+
+pub use css_inline::inline_css;
+pub use plain_text::html_to_text;
+pub use sanitize::{find_disallowed_content, sanitize};
+
+/// The html of an issue, ready to be stored and sent.
+pub struct PreparedHtml {
+    pub html: String,
+    /// Things that were dropped along the way without making the issue unsendable.
+    pub warnings: Vec<String>,
+}
+
+/// Inlines the stylesheets and sanitizes the result. Instead of silently removing
+/// content the author most likely meant to send, it is reported back as problems.
+pub fn prepare_html(html: &str) -> Result<PreparedHtml, Vec<String>> {
+    let unparseable = |e: lol_html::errors::RewritingError| {
+        vec![format!("The HTML content could not be parsed: {e}")]
+    };
+    let inlined = inline_css(html).map_err(unparseable)?;
+    let problems = find_disallowed_content(&inlined.html).map_err(unparseable)?;
+    if !problems.is_empty() {
+        return Err(problems);
+    }
+    Ok(PreparedHtml {
+        html: sanitize(&inlined.html),
+        warnings: inlined.warnings,
+    })
+}
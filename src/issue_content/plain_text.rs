@@ -0,0 +1,32 @@
+/// Wide enough for most mail clients without making them wrap lines themselves.
+const LINE_WIDTH: usize = 78;
+
+/// Renders a readable plain text alternative. Links become numbered footnotes and
+/// lists keep their bullets and numbers.
+pub fn html_to_text(html: &str) -> Result<String, html2text::Error> {
+    html2text::config::plain()
+        .link_footnotes(true)
+        .string_from_read(html.as_bytes(), LINE_WIDTH)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::html_to_text;
+
+    #[test]
+    fn links_become_footnotes() {
+        let text = html_to_text(r#"<p>Read <a href="https://example.com/post">the post</a>.</p>"#)
+            .unwrap();
+        assert!(text.contains("[the post][1]"));
+        assert!(text.contains("[1]: https://example.com/post"));
+    }
+
+    #[test]
+    fn lists_keep_their_structure() {
+        let text =
+            html_to_text("<ul><li>First</li><li>Second</li></ul><ol><li>One</li><li>Two</li></ol>")
+                .unwrap();
+        assert!(text.contains("* First\n* Second"));
+        assert!(text.contains("1. One\n2. Two"));
+    }
+}
@@ -0,0 +1,118 @@
+use ammonia::Builder;
+use lol_html::{RewriteStrSettings, element, rewrite_str};
+use std::collections::HashSet;
+
+/// Elements that only wrap the document. They are dropped quietly, keeping their content.
+const DOCUMENT_TAGS: [&str; 6] = ["html", "head", "body", "meta", "title", "style"];
+/// Presentational attributes that table based email layouts still rely on.
+const LAYOUT_ATTRIBUTES: [&str; 9] = [
+    "style",
+    "align",
+    "valign",
+    "bgcolor",
+    "width",
+    "height",
+    "border",
+    "cellpadding",
+    "cellspacing",
+];
+
+fn builder() -> Builder<'static> {
+    let mut builder = Builder::default();
+    builder
+        .add_generic_attributes(LAYOUT_ATTRIBUTES)
+        .add_clean_content_tags(["title"]);
+    builder
+}
+
+/// Strips everything outside of the allowlist.
+pub fn sanitize(html: &str) -> String {
+    builder().clean(html).to_string()
+}
+
+/// Lists the content `sanitize` would remove that was most likely meant to be sent,
+/// such as scripts or embedded frames.
+pub fn find_disallowed_content(
+    html: &str,
+) -> Result<Vec<String>, lol_html::errors::RewritingError> {
+    let builder = builder();
+    let allowed_tags = builder.clone_tags();
+    let mut problems = Vec::new();
+    let mut reported = HashSet::new();
+    rewrite_str(
+        html,
+        RewriteStrSettings {
+            element_content_handlers: vec![element!("*", |el| {
+                let tag = el.tag_name();
+                if !allowed_tags.contains(tag.as_str()) && !DOCUMENT_TAGS.contains(&tag.as_str()) {
+                    if reported.insert(tag.clone()) {
+                        problems.push(format!("{tag} elements are not allowed."));
+                    }
+                    return Ok(());
+                }
+                for attribute in el.attributes() {
+                    let name = attribute.name();
+                    let value = attribute.value();
+                    if name.starts_with("on") && reported.insert(name.clone()) {
+                        problems.push(format!("Event handlers such as {name} are not allowed."));
+                    }
+                    if value.trim().to_ascii_lowercase().starts_with("javascript:")
+                        && reported.insert("javascript:".into())
+                    {
+                        problems.push("javascript: links are not allowed.".into());
+                    }
+                }
+                Ok(())
+            })],
+            ..RewriteStrSettings::new()
+        },
+    )?;
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_disallowed_content, sanitize};
+
+    #[test]
+    fn scripts_and_event_handlers_are_reported() {
+        let problems = find_disallowed_content(
+            r#"<p onclick="x()">Hi</p><script>a()</script><script>b()</script><a href="javascript:c()">c</a>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            problems,
+            [
+                "Event handlers such as onclick are not allowed.",
+                "script elements are not allowed.",
+                "javascript: links are not allowed.",
+            ]
+        );
+    }
+
+    #[test]
+    fn regular_email_markup_is_fine() {
+        let html = r#"<html><head><title>Hi</title></head><body>
+            <table width="100%" cellpadding="0"><tr><td style="color: red">
+            <a href="https://example.com">Link</a><img src="https://example.com/a.png" alt="">
+            </td></tr></table></body></html>"#;
+        assert!(find_disallowed_content(html).unwrap().is_empty());
+    }
+
+    #[test]
+    fn sanitizing_keeps_layout_attributes_and_drops_the_document_wrapper() {
+        let html = sanitize(
+            r#"<html><head><title>Title</title></head><body><table><tr><td style="color: red" width="10">x</td></tr></table><script>a()</script></body></html>"#,
+        );
+        assert!(!html.contains("Title"));
+        assert!(!html.contains("<body>"));
+        assert!(!html.contains("script"));
+        assert!(html.contains(r#"style="color: red""#));
+        assert!(html.contains(r#"width="10""#));
+    }
+
+    #[test]
+    fn broken_markup_is_closed() {
+        assert_eq!(sanitize("<p><b>unclosed"), "<p><b>unclosed</b></p>");
+    }
+}
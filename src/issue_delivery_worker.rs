@@ -1,9 +1,19 @@
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 
-use crate::configuration::Settings;
+use crate::archive::{archive_url, inject_view_in_browser_link, prepend_view_in_browser_text};
+use crate::attachments::{EncodedAttachment, get_attachments};
+use crate::configuration::{Settings, TrackingSettings};
 use crate::domain::SubscriberEmail;
 use crate::email_client::EmailClient;
-use crate::startup::get_connection_pool;
+use crate::layouts::render;
+use crate::startup::{ApplicationBaseUrl, get_connection_pool};
+use crate::suppressions::send_unless_suppressed;
+use crate::tracking::{get_or_create_recipient, inject_tracking_pixel, rewrite_links};
+use crate::unsubscribe::{
+    append_unsubscribe_link, append_unsubscribe_text, preferences_url, unsubscribe_url,
+};
 use sqlx::PgPool;
 use sqlx::{Executor, Postgres, Transaction};
 use tracing::Span;
@@ -12,21 +22,72 @@ use uuid::Uuid;
 
 type PgTransaction = Transaction<'static, Postgres>;
 
+struct DeliveryTask {
+    newsletter_issue_id: Uuid,
+    subscriber_email: String,
+    subject_variant_id: Option<Uuid>,
+    unsubscribe_token: Option<String>,
+}
+
 pub enum ExecutionOutcome {
     TaskCompleted,
     EmptyQueue,
 }
 
+/// The attachments of the issues a worker is delivering, so they are loaded
+/// and encoded once per issue rather than once per recipient.
+#[derive(Default)]
+pub struct AttachmentCache(HashMap<Uuid, Arc<Vec<EncodedAttachment>>>);
+
+impl AttachmentCache {
+    async fn get(
+        &mut self,
+        pool: &PgPool,
+        issue_id: Uuid,
+    ) -> Result<Arc<Vec<EncodedAttachment>>, sqlx::Error> {
+        if let Some(attachments) = self.0.get(&issue_id) {
+            return Ok(Arc::clone(attachments));
+        }
+        let attachments = get_attachments(pool, issue_id)
+            .await?
+            .iter()
+            .map(|attachment| attachment.encode())
+            .collect();
+        let attachments = Arc::new(attachments);
+        self.0.insert(issue_id, Arc::clone(&attachments));
+        Ok(attachments)
+    }
+
+    /// Published attachments never change, this only keeps the cache small.
+    fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
 pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
     let connection_pool = get_connection_pool(&configuration.database);
     let email_client = configuration.email_client.client();
-    worker_loop(&connection_pool, &email_client).await
+    let base_url = ApplicationBaseUrl(configuration.application.base_url);
+    worker_loop(
+        &connection_pool,
+        &email_client,
+        &base_url,
+        &configuration.tracking,
+    )
+    .await
 }
 
-async fn worker_loop(pool: &PgPool, email_client: &EmailClient) -> Result<(), anyhow::Error> {
+async fn worker_loop(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &ApplicationBaseUrl,
+    tracking: &TrackingSettings,
+) -> Result<(), anyhow::Error> {
+    let mut attachments = AttachmentCache::default();
     loop {
-        match try_execute_task(pool, email_client).await {
+        match try_execute_task(pool, email_client, base_url, tracking, &mut attachments).await {
             Ok(ExecutionOutcome::EmptyQueue) => {
+                attachments.clear();
                 tokio::time::sleep(Duration::from_secs(10)).await;
             }
             Err(_) => {
@@ -41,26 +102,88 @@ async fn worker_loop(pool: &PgPool, email_client: &EmailClient) -> Result<(), an
 pub async fn try_execute_task(
     pool: &PgPool,
     email_client: &EmailClient,
+    base_url: &ApplicationBaseUrl,
+    tracking: &TrackingSettings,
+    attachments: &mut AttachmentCache,
 ) -> Result<ExecutionOutcome, anyhow::Error> {
     let task = dequeue_task(pool).await?;
     if task.is_none() {
         return Ok(ExecutionOutcome::EmptyQueue);
     }
-    let (transaction, issue_id, email) = task.unwrap();
+    let (
+        transaction,
+        DeliveryTask {
+            newsletter_issue_id: issue_id,
+            subscriber_email: email,
+            subject_variant_id,
+            unsubscribe_token,
+        },
+    ) = task.unwrap();
     Span::current()
         .record("newsletter_issue_id", display(issue_id))
         .record("subscriber_email", display(&email));
+    if !is_deliverable(pool, &email).await? {
+        tracing::info!("Skipping a subscriber that is no longer confirmed.");
+        delete_task(transaction, issue_id, &email).await?;
+        return Ok(ExecutionOutcome::TaskCompleted);
+    }
     match SubscriberEmail::parse(email.clone()) {
         Ok(email) => {
             let issue = get_issue(pool, issue_id).await?;
-            if let Err(e) = email_client
-                .send_email(
-                    &email,
-                    &issue.title,
-                    &issue.html_content,
-                    &issue.text_content,
-                )
-                .await
+            let subject = match subject_variant_id {
+                Some(variant_id) => get_subject_variant(pool, variant_id).await?,
+                None => issue.title,
+            };
+            // The layout is looked up at send time, so its changes apply to older issues too.
+            let (mut html_content, mut text_content) = match issue.layout {
+                Some(layout) => (
+                    render(&layout.html_template, &issue.html_content),
+                    render(&layout.text_template, &issue.text_content),
+                ),
+                None => (issue.html_content, issue.text_content),
+            };
+            let track_opens = issue.track_opens && tracking.allow_open_tracking;
+            let track_clicks = issue.track_clicks && tracking.allow_click_tracking;
+            if track_opens || track_clicks {
+                let recipient_id =
+                    get_or_create_recipient(pool, issue_id, email.as_ref(), subject_variant_id)
+                        .await?;
+                if track_clicks {
+                    html_content = rewrite_links(
+                        &html_content,
+                        &base_url.0,
+                        &tracking.link_signing_key,
+                        recipient_id,
+                    )?;
+                }
+                if track_opens {
+                    let pixel_url = format!("http://{}/o/{recipient_id}", base_url.0);
+                    html_content = inject_tracking_pixel(&html_content, &pixel_url);
+                }
+            }
+            // Added after the links were rewritten, it is not worth tracking.
+            if issue.is_public {
+                let url = archive_url(&base_url.0, &issue.slug);
+                html_content = inject_view_in_browser_link(&html_content, &url);
+                text_content = prepend_view_in_browser_text(&text_content, &url);
+            }
+            if let Some(token) = unsubscribe_token {
+                let url = unsubscribe_url(&base_url.0, &token);
+                let preferences = preferences_url(&base_url.0, &token);
+                html_content = append_unsubscribe_link(&html_content, &url, &preferences);
+                text_content = append_unsubscribe_text(&text_content, &url, &preferences);
+            }
+            let attachments = attachments.get(pool, issue_id).await?;
+            if let Err(e) = send_unless_suppressed(
+                pool,
+                email_client,
+                &email,
+                &subject,
+                &html_content,
+                &text_content,
+                &attachments,
+            )
+            .await
             {
                 tracing::error!(
                     error.cause_chain = ?e,
@@ -81,15 +204,26 @@ pub async fn try_execute_task(
     Ok(ExecutionOutcome::TaskCompleted)
 }
 
+/// Tasks held back until a subject line test has picked its winner, and
+/// automation steps that are not due yet, are skipped.
 #[tracing::instrument(skip_all)]
 async fn dequeue_task(
     pool: &PgPool,
-) -> Result<Option<(PgTransaction, Uuid, String)>, anyhow::Error> {
+) -> Result<Option<(PgTransaction, DeliveryTask)>, anyhow::Error> {
     let mut transaction = pool.begin().await?;
-    let r = sqlx::query!(
+    let r = sqlx::query_as!(
+        DeliveryTask,
         r#"
-        SELECT newsletter_issue_id, subscriber_email
-        FROM issue_delivery_queue
+        SELECT
+            q.newsletter_issue_id,
+            q.subscriber_email,
+            q.subject_variant_id,
+            (
+                SELECT s.unsubscribe_token FROM subscriptions s
+                WHERE s.email = q.subscriber_email
+            ) AS unsubscribe_token
+        FROM issue_delivery_queue q
+        WHERE NOT held AND execute_after <= now()
         FOR UPDATE
         SKIP LOCKED
         LIMIT 1
@@ -98,11 +232,7 @@ async fn dequeue_task(
     .fetch_optional(&mut *transaction)
     .await?;
     if let Some(r) = r {
-        Ok(Some((
-            transaction,
-            r.newsletter_issue_id,
-            r.subscriber_email,
-        )))
+        Ok(Some((transaction, r)))
     } else {
         transaction.rollback().await?;
         Ok(None)
@@ -130,25 +260,83 @@ async fn delete_task(
     Ok(())
 }
 
+/// Subscribers can bounce, complain or unsubscribe after a task has been enqueued.
+#[tracing::instrument(skip_all)]
+async fn is_deliverable(pool: &PgPool, email: &str) -> Result<bool, anyhow::Error> {
+    let status = sqlx::query_scalar!(
+        r#"SELECT status FROM subscriptions WHERE email = $1"#,
+        email
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(status.as_deref() == Some("confirmed"))
+}
+
 struct NewsletterIssue {
     title: String,
     text_content: String,
     html_content: String,
+    track_opens: bool,
+    track_clicks: bool,
+    is_public: bool,
+    slug: String,
+    layout: Option<IssueLayout>,
+}
+
+struct IssueLayout {
+    html_template: String,
+    text_template: String,
 }
 
 #[tracing::instrument(skip_all)]
 async fn get_issue(pool: &PgPool, issue_id: Uuid) -> Result<NewsletterIssue, anyhow::Error> {
-    let issue = sqlx::query_as!(
-        NewsletterIssue,
+    let row = sqlx::query!(
         r#"
-        SELECT title, text_content, html_content
-        FROM newsletter_issues
+        SELECT
+            i.title,
+            i.text_content,
+            i.html_content,
+            i.track_opens,
+            i.track_clicks,
+            i.is_public,
+            i.slug,
+            l.html_template AS "html_template?",
+            l.text_template AS "text_template?"
+        FROM newsletter_issues i
+        LEFT JOIN email_layouts l USING (layout_id)
         WHERE
-            newsletter_issue_id =$1 
+            i.newsletter_issue_id = $1
         "#,
         issue_id
     )
     .fetch_one(pool)
     .await?;
-    Ok(issue)
+    let layout = match (row.html_template, row.text_template) {
+        (Some(html_template), Some(text_template)) => Some(IssueLayout {
+            html_template,
+            text_template,
+        }),
+        _ => None,
+    };
+    Ok(NewsletterIssue {
+        title: row.title,
+        text_content: row.text_content,
+        html_content: row.html_content,
+        track_opens: row.track_opens,
+        track_clicks: row.track_clicks,
+        is_public: row.is_public,
+        slug: row.slug,
+        layout,
+    })
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_subject_variant(pool: &PgPool, variant_id: Uuid) -> Result<String, anyhow::Error> {
+    let subject = sqlx::query_scalar!(
+        r#"SELECT subject FROM issue_subject_variants WHERE variant_id = $1"#,
+        variant_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(subject)
 }
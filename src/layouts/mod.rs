@@ -0,0 +1,55 @@
+mod persistence;
+
+pub use persistence::{
+    EmailLayout, delete_layout, get_default_layout_id, get_layout, insert_layout, list_layouts,
+    set_default_layout, update_layout,
+};
+
+/// Where the content of an issue goes in a layout template.
+pub const CONTENT_SLOT: &str = "{{content}}";
+
+/// A layout template needs exactly one slot for the content of the issue.
+pub fn validate_template(template: &str, kind: &str) -> Result<(), String> {
+    match template.matches(CONTENT_SLOT).count() {
+        1 => Ok(()),
+        0 => Err(format!("The {kind} layout must contain {CONTENT_SLOT}.")),
+        _ => Err(format!(
+            "The {kind} layout must contain {CONTENT_SLOT} only once."
+        )),
+    }
+}
+
+/// Layouts are rendered at send time, so changing a layout affects every issue
+/// sent afterwards without touching their content.
+pub fn render(template: &str, content: &str) -> String {
+    template.replacen(CONTENT_SLOT, content, 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, validate_template};
+    use claim::{assert_err, assert_ok};
+
+    #[test]
+    fn a_template_needs_exactly_one_content_slot() {
+        assert_ok!(validate_template("<div>{{content}}</div>", "HTML"));
+        assert_err!(validate_template("<div></div>", "HTML"));
+        assert_err!(validate_template("{{content}}{{content}}", "HTML"));
+    }
+
+    #[test]
+    fn the_content_replaces_the_slot() {
+        assert_eq!(
+            render(
+                "<header>Hi</header>{{content}}<footer>Bye</footer>",
+                "<p>News</p>"
+            ),
+            "<header>Hi</header><p>News</p><footer>Bye</footer>"
+        );
+    }
+
+    #[test]
+    fn slots_inside_the_content_are_left_alone() {
+        assert_eq!(render("[{{content}}]", "{{content}}"), "[{{content}}]");
+    }
+}
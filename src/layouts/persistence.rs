@@ -0,0 +1,126 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+pub struct EmailLayout {
+    pub layout_id: Uuid,
+    pub name: String,
+    pub html_template: String,
+    pub text_template: String,
+    pub is_default: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "List email layouts", skip(pool))]
+pub async fn list_layouts(pool: &PgPool) -> Result<Vec<EmailLayout>, sqlx::Error> {
+    sqlx::query_as!(
+        EmailLayout,
+        r#"
+        SELECT layout_id, name, html_template, text_template, is_default, created_at
+        FROM email_layouts
+        ORDER BY name
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+#[tracing::instrument(name = "Get an email layout", skip(pool))]
+pub async fn get_layout(
+    pool: &PgPool,
+    layout_id: Uuid,
+) -> Result<Option<EmailLayout>, sqlx::Error> {
+    sqlx::query_as!(
+        EmailLayout,
+        r#"
+        SELECT layout_id, name, html_template, text_template, is_default, created_at
+        FROM email_layouts
+        WHERE layout_id = $1
+        "#,
+        layout_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+#[tracing::instrument(name = "Get the default email layout", skip(pool))]
+pub async fn get_default_layout_id(pool: &PgPool) -> Result<Option<Uuid>, sqlx::Error> {
+    sqlx::query_scalar!(r#"SELECT layout_id FROM email_layouts WHERE is_default"#)
+        .fetch_optional(pool)
+        .await
+}
+
+#[tracing::instrument(name = "Insert an email layout", skip_all)]
+pub async fn insert_layout(
+    transaction: &mut Transaction<'static, Postgres>,
+    name: &str,
+    html_template: &str,
+    text_template: &str,
+) -> Result<Uuid, sqlx::Error> {
+    let layout_id = Uuid::new_v4();
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO email_layouts (layout_id, name, html_template, text_template, created_at)
+        VALUES ($1, $2, $3, $4, now())
+        "#,
+        layout_id,
+        name,
+        html_template,
+        text_template
+    );
+    transaction.execute(query).await?;
+    Ok(layout_id)
+}
+
+#[tracing::instrument(
+    name = "Update an email layout",
+    skip(transaction, html_template, text_template)
+)]
+pub async fn update_layout(
+    transaction: &mut Transaction<'static, Postgres>,
+    layout_id: Uuid,
+    name: &str,
+    html_template: &str,
+    text_template: &str,
+) -> Result<bool, sqlx::Error> {
+    let query = sqlx::query!(
+        r#"
+        UPDATE email_layouts
+        SET name = $2, html_template = $3, text_template = $4
+        WHERE layout_id = $1
+        "#,
+        layout_id,
+        name,
+        html_template,
+        text_template
+    );
+    Ok(transaction.execute(query).await?.rows_affected() == 1)
+}
+
+/// There is at most one default layout, the previous one stops being the default.
+#[tracing::instrument(name = "Set the default email layout", skip(transaction))]
+pub async fn set_default_layout(
+    transaction: &mut Transaction<'static, Postgres>,
+    layout_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(r#"UPDATE email_layouts SET is_default = false WHERE is_default"#);
+    transaction.execute(query).await?;
+    let query = sqlx::query!(
+        r#"UPDATE email_layouts SET is_default = true WHERE layout_id = $1"#,
+        layout_id
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+/// Issues that used the layout are sent without one from now on.
+#[tracing::instrument(name = "Delete an email layout", skip(pool))]
+pub async fn delete_layout(pool: &PgPool, layout_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"DELETE FROM email_layouts WHERE layout_id = $1"#,
+        layout_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
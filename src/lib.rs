@@ -1,9 +1,28 @@
+pub mod archive;
+pub mod attachments;
+pub mod audience;
 pub mod authentication;
+pub mod authorization;
+pub mod automations;
 pub mod configuration;
+pub mod csrf;
+pub mod digests;
 pub mod domain;
 pub mod email_client;
+pub mod html;
 pub mod idempotency;
+pub mod issue_content;
 pub mod issue_delivery_worker;
+pub mod layouts;
+pub mod login_throttle;
+pub mod media;
 pub mod routes;
 pub mod startup;
+pub mod subject_tests;
+pub mod suppressions;
 pub mod telemetry;
+pub mod timezones;
+pub mod tracking;
+pub mod two_factor;
+pub mod unsubscribe;
+pub mod users;
@@ -0,0 +1,229 @@
+use anyhow::Context;
+use std::net::IpAddr;
+use tower_sessions_redis_store::fred::prelude::{Expiration, KeysInterface, Pool};
+
+use crate::configuration::{LoginThrottleSettings, ThrottlePolicy};
+
+/// The longest delay before a lockout kicks in.
+const MAX_DELAY_SECONDS: u64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Block {
+    /// Slowing down guesses, doubling with every failure.
+    Delay(u64),
+    /// Too many failures, logging in is refused until it expires.
+    Lockout(u64),
+}
+
+impl Block {
+    pub fn seconds(&self) -> u64 {
+        match self {
+            Block::Delay(seconds) | Block::Lockout(seconds) => *seconds,
+        }
+    }
+}
+
+/// What follows `failures` consecutive failed logins.
+pub fn block_after(failures: u64, policy: &ThrottlePolicy, lockout_seconds: u64) -> Option<Block> {
+    if failures >= policy.lockout_threshold {
+        return Some(Block::Lockout(lockout_seconds));
+    }
+    let delayed = failures.checked_sub(policy.free_attempts + 1)?;
+    let delay = 1u64
+        .checked_shl(delayed.min(63) as u32)
+        .unwrap_or(MAX_DELAY_SECONDS);
+    Some(Block::Delay(delay.min(MAX_DELAY_SECONDS)))
+}
+
+/// A human readable version of the time left before the next attempt.
+pub fn wait_description(seconds: u64) -> String {
+    match seconds {
+        0..=1 => "1 second".into(),
+        2..=90 => format!("{seconds} seconds"),
+        _ => format!("{} minutes", seconds.div_ceil(60)),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Scope {
+    Username,
+    Address,
+}
+
+impl Scope {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Scope::Username => "username",
+            Scope::Address => "address",
+        }
+    }
+}
+
+/// Counts failed logins per username and per client address in Redis.
+#[derive(Debug)]
+pub struct LoginThrottle {
+    redis: Pool,
+    settings: LoginThrottleSettings,
+}
+
+impl LoginThrottle {
+    pub fn new(redis: Pool, settings: LoginThrottleSettings) -> Self {
+        Self { redis, settings }
+    }
+
+    fn key(&self, kind: &str, scope: Scope, identity: &str) -> String {
+        format!(
+            "{}:{kind}:{}:{identity}",
+            self.settings.key_prefix,
+            scope.as_str()
+        )
+    }
+
+    fn policy(&self, scope: Scope) -> &ThrottlePolicy {
+        match scope {
+            Scope::Username => &self.settings.username,
+            Scope::Address => &self.settings.address,
+        }
+    }
+
+    /// Seconds until the next attempt is allowed, if either the username or
+    /// the address is blocked.
+    pub async fn retry_after(
+        &self,
+        username: &str,
+        address: IpAddr,
+    ) -> Result<Option<u64>, anyhow::Error> {
+        let address = address.to_string();
+        let mut retry_after = None;
+        for (scope, identity) in [(Scope::Username, username), (Scope::Address, &address)] {
+            let ttl: i64 = self
+                .redis
+                .ttl(self.key("blocked", scope, identity))
+                .await
+                .context("Failed to read a login block from Redis")?;
+            if ttl > 0 {
+                retry_after = retry_after.max(Some(ttl as u64));
+            }
+        }
+        Ok(retry_after)
+    }
+
+    /// Returns the longest block the failure caused, if any.
+    pub async fn record_failure(
+        &self,
+        username: &str,
+        address: IpAddr,
+    ) -> Result<Option<Block>, anyhow::Error> {
+        let address = address.to_string();
+        let mut longest: Option<Block> = None;
+        for (scope, identity) in [(Scope::Username, username), (Scope::Address, &address)] {
+            let failures_key = self.key("failures", scope, identity);
+            let failures: u64 = self
+                .redis
+                .incr(&failures_key)
+                .await
+                .context("Failed to count a failed login in Redis")?;
+            let () = self
+                .redis
+                .expire(&failures_key, self.settings.lockout_seconds as i64, None)
+                .await
+                .context("Failed to set the expiry of a login counter")?;
+
+            let Some(block) =
+                block_after(failures, self.policy(scope), self.settings.lockout_seconds)
+            else {
+                continue;
+            };
+            let () = self
+                .redis
+                .set(
+                    self.key("blocked", scope, identity),
+                    1,
+                    Some(Expiration::EX(block.seconds() as i64)),
+                    None,
+                    false,
+                )
+                .await
+                .context("Failed to store a login block in Redis")?;
+            if let Block::Lockout(seconds) = block {
+                tracing::warn!(
+                    target: "audit",
+                    event = "login_lockout",
+                    scope = scope.as_str(),
+                    identity,
+                    failures,
+                    lockout_seconds = seconds,
+                    "Logging in has been locked after too many failed attempts."
+                );
+            }
+            if longest.is_none_or(|longest| longest.seconds() < block.seconds()) {
+                longest = Some(block);
+            }
+        }
+        Ok(longest)
+    }
+
+    /// Clears the failures of the username. Those of the address are kept,
+    /// knowing one password must not unlock guessing all the others.
+    pub async fn record_success(&self, username: &str) -> Result<(), anyhow::Error> {
+        let keys = vec![
+            self.key("failures", Scope::Username, username),
+            self.key("blocked", Scope::Username, username),
+        ];
+        let _: i64 = self
+            .redis
+            .del(keys)
+            .await
+            .context("Failed to reset the failed logins in Redis")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Block, block_after, wait_description};
+    use crate::configuration::ThrottlePolicy;
+    use claim::{assert_none, assert_some_eq};
+
+    const POLICY: ThrottlePolicy = ThrottlePolicy {
+        free_attempts: 3,
+        lockout_threshold: 10,
+    };
+
+    #[test]
+    fn the_first_failures_are_free() {
+        for failures in 0..=3 {
+            assert_none!(block_after(failures, &POLICY, 900));
+        }
+    }
+
+    #[test]
+    fn delays_double_with_every_failure() {
+        assert_some_eq!(block_after(4, &POLICY, 900), Block::Delay(1));
+        assert_some_eq!(block_after(5, &POLICY, 900), Block::Delay(2));
+        assert_some_eq!(block_after(9, &POLICY, 900), Block::Delay(32));
+    }
+
+    #[test]
+    fn delays_are_capped() {
+        let policy = ThrottlePolicy {
+            free_attempts: 0,
+            lockout_threshold: 1000,
+        };
+        assert_some_eq!(block_after(8, &policy, 900), Block::Delay(60));
+        assert_some_eq!(block_after(999, &policy, 900), Block::Delay(60));
+    }
+
+    #[test]
+    fn reaching_the_threshold_locks_the_login() {
+        assert_some_eq!(block_after(10, &POLICY, 900), Block::Lockout(900));
+        assert_some_eq!(block_after(11, &POLICY, 900), Block::Lockout(900));
+    }
+
+    #[test]
+    fn waits_are_described_in_the_right_unit() {
+        assert_eq!(wait_description(1), "1 second");
+        assert_eq!(wait_description(32), "32 seconds");
+        assert_eq!(wait_description(900), "15 minutes");
+    }
+}
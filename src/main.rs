@@ -2,9 +2,12 @@ use std::fmt::{Debug, Display};
 
 use tokio::task::JoinError;
 use zero2prod::{
+    audience::run_audience_expansion_until_stopped,
     configuration::get_configuration,
+    digests::run_digest_scheduler_until_stopped,
     issue_delivery_worker::run_worker_until_stopped,
     startup::Application,
+    subject_tests::run_finalizer_until_stopped,
     telemetry::{get_subscriber, init_subscriber},
 };
 
@@ -20,12 +23,21 @@ async fn main() -> anyhow::Result<()> {
     let application = Application::build(configuration.clone()).await?;
     let application_task = tokio::spawn(application.run_until_stopped());
     let worker_task = tokio::spawn(run_worker_until_stopped(configuration.clone()));
+    let expansion_task = tokio::spawn(run_audience_expansion_until_stopped(configuration.clone()));
+    let finalizer_task = tokio::spawn(run_finalizer_until_stopped(configuration.clone()));
+    let digest_task = tokio::spawn(run_digest_scheduler_until_stopped(configuration.clone()));
     tracing::info!(
         "Starting application with following config {:?}",
         configuration
     );
 
-    tokio::select! {o = application_task => {report_exit("API", o);}, o = worker_task => {report_exit("Background workder", o);}}
+    tokio::select! {
+        o = application_task => {report_exit("API", o);},
+        o = worker_task => {report_exit("Background workder", o);},
+        o = expansion_task => {report_exit("Audience expansion", o);},
+        o = finalizer_task => {report_exit("Subject test finalizer", o);},
+        o = digest_task => {report_exit("Digest scheduler", o);},
+    }
     Ok(())
 }
 
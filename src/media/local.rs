@@ -0,0 +1,88 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use async_trait::async_trait;
+
+use super::MediaStorage;
+
+/// Keeps every file in a single directory on the local filesystem.
+#[derive(Debug, Clone)]
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, anyhow::Error> {
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            anyhow::bail!("{key} is not a valid media key");
+        }
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait]
+impl MediaStorage for LocalStorage {
+    async fn put(&self, key: &str, content: &[u8]) -> Result<(), anyhow::Error> {
+        let path = self.path(key)?;
+        tokio::fs::create_dir_all(&self.root)
+            .await
+            .context("Failed to create the media directory")?;
+        // Written under a temporary name first, a half written file is never served.
+        let tmp_path = path.with_extension("tmp");
+        tokio::fs::write(&tmp_path, content)
+            .await
+            .context("Failed to write the media file")?;
+        tokio::fs::rename(&tmp_path, &path)
+            .await
+            .context("Failed to move the media file in place")?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        match tokio::fs::read(self.path(key)?).await {
+            Ok(content) => Ok(Some(content)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(anyhow::Error::new(e).context("Failed to read the media file")),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), anyhow::Error> {
+        match tokio::fs::remove_file(self.path(key)?).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(anyhow::Error::new(e).context("Failed to delete the media file")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LocalStorage;
+    use crate::media::MediaStorage;
+    use claim::{assert_err, assert_none, assert_ok};
+
+    fn storage() -> LocalStorage {
+        LocalStorage::new(std::env::temp_dir().join(uuid::Uuid::new_v4().to_string()))
+    }
+
+    #[tokio::test]
+    async fn a_stored_file_can_be_read_back_and_deleted() {
+        let storage = storage();
+        assert_ok!(storage.put("abc-123", b"content").await);
+        assert_eq!(storage.get("abc-123").await.unwrap().unwrap(), b"content");
+
+        assert_ok!(storage.delete("abc-123").await);
+        assert_none!(storage.get("abc-123").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn keys_can_not_escape_the_directory() {
+        let storage = storage();
+        assert_err!(storage.put("../escape", b"content").await);
+        assert_err!(storage.get("/etc/passwd").await);
+    }
+}
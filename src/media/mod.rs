@@ -0,0 +1,95 @@
+mod local;
+mod persistence;
+
+pub use local::LocalStorage;
+pub use persistence::{MediaItem, delete_media, get_media, insert_media, list_media};
+
+use async_trait::async_trait;
+use lol_html::{RewriteStrSettings, element, rewrite_str};
+
+/// Where uploaded files are kept. Files are addressed by an opaque key and
+/// never change once stored.
+#[async_trait]
+pub trait MediaStorage: Send + Sync + std::fmt::Debug {
+    async fn put(&self, key: &str, content: &[u8]) -> Result<(), anyhow::Error>;
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, anyhow::Error>;
+    async fn delete(&self, key: &str) -> Result<(), anyhow::Error>;
+}
+
+/// The image formats mail clients display, with the leading bytes they start with.
+/// SVG is left out on purpose since it can carry scripts.
+const IMAGE_SIGNATURES: [(&str, &[u8]); 3] = [
+    ("image/png", b"\x89PNG\r\n\x1a\n"),
+    ("image/jpeg", b"\xff\xd8\xff"),
+    ("image/gif", b"GIF8"),
+];
+
+/// Detects the image type from the content, the type declared by the browser
+/// is not trusted.
+pub fn detect_image_type(content: &[u8]) -> Option<&'static str> {
+    if content.len() >= 12 && &content[..4] == b"RIFF" && &content[8..12] == b"WEBP" {
+        return Some("image/webp");
+    }
+    IMAGE_SIGNATURES
+        .iter()
+        .find(|(_, signature)| content.starts_with(signature))
+        .map(|(content_type, _)| *content_type)
+}
+
+pub fn media_path(media_id: uuid::Uuid) -> String {
+    format!("/media/{media_id}")
+}
+
+/// Mail clients can not resolve relative urls, so images uploaded through the
+/// admin are referenced by their absolute url in the published issue.
+pub fn absolutize_media_urls(html: &str, base_url: &str) -> Result<String, anyhow::Error> {
+    let rewritten = rewrite_str(
+        html,
+        RewriteStrSettings {
+            element_content_handlers: vec![element!("img[src]", |el| {
+                let src = el.get_attribute("src").unwrap_or_default();
+                if src.trim().starts_with("/media/") {
+                    el.set_attribute("src", &format!("http://{base_url}{}", src.trim()))?;
+                }
+                Ok(())
+            })],
+            ..RewriteStrSettings::new()
+        },
+    )?;
+    Ok(rewritten)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{absolutize_media_urls, detect_image_type};
+    use claim::{assert_none, assert_some_eq};
+
+    #[test]
+    fn images_are_detected_from_their_content() {
+        assert_some_eq!(detect_image_type(b"\x89PNG\r\n\x1a\n...."), "image/png");
+        assert_some_eq!(detect_image_type(b"\xff\xd8\xff\xe0...."), "image/jpeg");
+        assert_some_eq!(detect_image_type(b"GIF89a...."), "image/gif");
+        assert_some_eq!(
+            detect_image_type(b"RIFF\x00\x00\x00\x00WEBPVP8 "),
+            "image/webp"
+        );
+    }
+
+    #[test]
+    fn other_content_is_not_an_image() {
+        assert_none!(detect_image_type(
+            b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>"
+        ));
+        assert_none!(detect_image_type(b"%PDF-1.7"));
+        assert_none!(detect_image_type(b""));
+    }
+
+    #[test]
+    fn uploaded_images_get_absolute_urls() {
+        let html = r#"<p><img src="/media/abc"><img src="https://example.com/a.png"></p>"#;
+        assert_eq!(
+            absolutize_media_urls(html, "localhost:8000").unwrap(),
+            r#"<p><img src="http://localhost:8000/media/abc"><img src="https://example.com/a.png"></p>"#
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub struct MediaItem {
+    pub media_id: Uuid,
+    pub file_name: String,
+    pub content_type: String,
+    pub size_bytes: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "Store media details", skip(pool))]
+pub async fn insert_media(
+    pool: &PgPool,
+    media_id: Uuid,
+    file_name: &str,
+    content_type: &str,
+    size_bytes: i32,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO media (media_id, file_name, content_type, size_bytes, created_at)
+        VALUES ($1, $2, $3, $4, now())
+        "#,
+        media_id,
+        file_name,
+        content_type,
+        size_bytes
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Get media details", skip(pool))]
+pub async fn get_media(pool: &PgPool, media_id: Uuid) -> Result<Option<MediaItem>, sqlx::Error> {
+    sqlx::query_as!(
+        MediaItem,
+        r#"
+        SELECT media_id, file_name, content_type, size_bytes, created_at
+        FROM media
+        WHERE media_id = $1
+        "#,
+        media_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+#[tracing::instrument(name = "List media", skip(pool))]
+pub async fn list_media(pool: &PgPool) -> Result<Vec<MediaItem>, sqlx::Error> {
+    sqlx::query_as!(
+        MediaItem,
+        r#"
+        SELECT media_id, file_name, content_type, size_bytes, created_at
+        FROM media
+        ORDER BY created_at DESC
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Returns `false` if there was nothing to delete.
+#[tracing::instrument(name = "Delete media details", skip(pool))]
+pub async fn delete_media(pool: &PgPool, media_id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(r#"DELETE FROM media WHERE media_id = $1"#, media_id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
@@ -0,0 +1,215 @@
+use anyhow::Context;
+use axum::{
+    extract::{Path, State},
+    response::{Html, IntoResponse},
+};
+use axum_messages::Messages;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::{
+    automations::{get_automation, list_automations, list_steps},
+    csrf::CsrfToken,
+    html::escape_html,
+    layouts::list_layouts,
+    routes::AutomationError,
+    startup::AppState,
+};
+
+pub async fn automations_page(
+    messages: Messages,
+    csrf_token: CsrfToken,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AutomationError> {
+    let csrf_field = csrf_token.form_field();
+    let mut msg_html = String::new();
+    for m in messages.into_iter() {
+        writeln!(msg_html, "<p><i>{m}</i></p>").unwrap();
+    }
+
+    let automations = list_automations(&state.pg_pool)
+        .await
+        .context("Failed to load the automations")?;
+    let mut rows_html = String::new();
+    for automation in automations {
+        writeln!(
+            rows_html,
+            r#"<tr>
+                <td><a href="/admin/automations/{}">{}</a></td>
+                <td>{}</td>
+                <td>{}</td>
+            </tr>"#,
+            automation.automation_id,
+            escape_html(&automation.name),
+            if automation.is_active {
+                "Active"
+            } else {
+                "Paused"
+            },
+            automation.created_at.format("%Y-%m-%d %H:%M"),
+        )
+        .unwrap();
+    }
+
+    Ok(Html(format!(
+        r#"<!DOCTYPE html>
+        <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8">
+            <title>Automations</title>
+        </head>
+        <body>
+            {msg_html}
+            <p>Active automations send their steps to every subscriber after they confirm their subscription.</p>
+            <table>
+                <tr><th>Name</th><th>Status</th><th>Created</th></tr>
+                {rows_html}
+            </table>
+            <h2>New automation</h2>
+            <form action="/admin/automations" method="post">
+                {csrf_field}
+                <label>Name
+                    <input type="text" name="name" placeholder="Welcome sequence">
+                </label>
+                <button type="submit">Create</button>
+            </form>
+            <p><a href="/admin/dashboard">&lt;- Back</a></p>
+        </body>
+        </html>"#,
+    )))
+}
+
+fn format_delay(hours: i32) -> String {
+    match hours {
+        0 => "Right after confirmation".to_string(),
+        h if h % 24 == 0 => format!("{} day(s) after confirmation", h / 24),
+        h => format!("{h} hour(s) after confirmation"),
+    }
+}
+
+pub async fn automation_page(
+    messages: Messages,
+    csrf_token: CsrfToken,
+    State(state): State<AppState>,
+    Path(automation_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AutomationError> {
+    let csrf_field = csrf_token.form_field();
+    let mut msg_html = String::new();
+    for m in messages.into_iter() {
+        writeln!(msg_html, "<p><i>{m}</i></p>").unwrap();
+    }
+    let automation = get_automation(&state.pg_pool, automation_id)
+        .await
+        .context("Failed to load the automation")?
+        .ok_or(AutomationError::NotFound)?;
+
+    let steps = list_steps(&state.pg_pool, automation_id)
+        .await
+        .context("Failed to load the automation steps")?;
+    let mut rows_html = String::new();
+    for step in steps {
+        writeln!(
+            rows_html,
+            r#"<tr>
+                <td>{}</td>
+                <td>{}</td>
+                <td>{}</td>
+                <td>
+                    <form action="/admin/automations/{automation_id}/steps/{}/delete" method="post">
+                        {csrf_field}
+                        <button type="submit">Delete</button>
+                    </form>
+                </td>
+            </tr>"#,
+            format_delay(step.delay_hours),
+            escape_html(&step.subject),
+            step.pending,
+            step.step_id,
+        )
+        .unwrap();
+    }
+
+    let layouts = list_layouts(&state.pg_pool)
+        .await
+        .context("Failed to load the email layouts")?;
+    let mut layout_options = String::from(r#"<option value="">No layout</option>"#);
+    for layout in layouts {
+        writeln!(
+            layout_options,
+            r#"<option value="{}"{}>{}</option>"#,
+            layout.layout_id,
+            if layout.is_default { " selected" } else { "" },
+            escape_html(&layout.name)
+        )
+        .unwrap();
+    }
+
+    let (status, toggle_label) = if automation.is_active {
+        ("Active", "Pause")
+    } else {
+        ("Paused, new subscribers are not enrolled", "Resume")
+    };
+    Ok(Html(format!(
+        r#"<!DOCTYPE html>
+        <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8">
+            <title>{name}</title>
+        </head>
+        <body>
+            {msg_html}
+            <h1>{name}</h1>
+            <p>{status}</p>
+            <form action="/admin/automations/{automation_id}/toggle" method="post">
+                {csrf_field}
+                <input hidden type="text" name="is_active" value="{is_active}">
+                <button type="submit">{toggle_label}</button>
+            </form>
+            <table>
+                <tr><th>When</th><th>Subject</th><th>Scheduled</th><th></th></tr>
+                {rows_html}
+            </table>
+            <h2>New step</h2>
+            <form action="/admin/automations/{automation_id}/steps" method="post">
+                {csrf_field}
+                <label>Send
+                    <input type="number" name="delay" value="1" min="0">
+                    <select name="delay_unit">
+                        <option value="hours">hours</option>
+                        <option value="days" selected>days</option>
+                    </select>
+                    after confirmation
+                </label>
+                <br>
+                <label>Subject:<br>
+                    <input type="text" name="subject">
+                </label>
+                <br>
+                <label>Plain text content:<br>
+                    <textarea
+                        placeholder="Leave empty to generate it from the HTML content"
+                        name="text"
+                        rows="10"
+                        cols="50"
+                    ></textarea>
+                </label>
+                <br>
+                <label>HTML content:<br>
+                    <textarea name="html" rows="10" cols="50"></textarea>
+                </label>
+                <br>
+                <label>Layout:
+                    <select name="layout_id">
+                        {layout_options}
+                    </select>
+                </label>
+                <br>
+                <button type="submit">Add step</button>
+            </form>
+            <p><a href="/admin/automations">&lt;- Back</a></p>
+        </body>
+        </html>"#,
+        name = escape_html(&automation.name),
+        is_active = !automation.is_active,
+    )))
+}
@@ -0,0 +1,35 @@
+mod get;
+mod post;
+
+use axum::response::{IntoResponse, Response};
+pub use get::{automation_page, automations_page};
+pub use post::{
+    add_step_handler, create_automation_handler, delete_step_handler, toggle_automation_handler,
+};
+use reqwest::StatusCode;
+
+#[derive(thiserror::Error, Debug)]
+pub enum AutomationError {
+    #[error("The automation does not exist.")]
+    NotFound,
+
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl IntoResponse for AutomationError {
+    fn into_response(self) -> Response {
+        match self {
+            AutomationError::NotFound => (StatusCode::NOT_FOUND, self.to_string()).into_response(),
+            AutomationError::UnexpectedError(err) => {
+                tracing::error!("{:?}", err);
+
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Something went wrong".to_owned(),
+                )
+                    .into_response()
+            }
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use anyhow::Context;
+use axum::{
+    Form,
+    extract::{Path, State},
+    response::Redirect,
+};
+use axum_messages::Messages;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    automations::{
+        delay_hours, delete_step, get_automation, insert_automation, insert_step,
+        set_automation_active,
+    },
+    issue_content::{html_to_text, prepare_html},
+    media::absolutize_media_urls,
+    routes::AutomationError,
+    startup::AppState,
+};
+
+#[derive(Deserialize)]
+pub struct AutomationFormData {
+    name: String,
+}
+
+#[tracing::instrument(name = "Create an automation", skip_all)]
+pub async fn create_automation_handler(
+    messages: Messages,
+    State(state): State<AppState>,
+    Form(form): Form<AutomationFormData>,
+) -> Result<Redirect, AutomationError> {
+    let name = form.name.trim();
+    if name.is_empty() {
+        messages.error("The automation needs a name.");
+        return Ok(Redirect::to("/admin/automations"));
+    }
+    let automation_id = insert_automation(&state.pg_pool, name)
+        .await
+        .context("Failed to store the automation")?;
+    messages.info("The automation has been created, add its first step.");
+    Ok(Redirect::to(&format!("/admin/automations/{automation_id}")))
+}
+
+#[derive(Deserialize)]
+pub struct ToggleFormData {
+    is_active: bool,
+}
+
+#[tracing::instrument(name = "Toggle an automation", skip(messages, state, form))]
+pub async fn toggle_automation_handler(
+    messages: Messages,
+    State(state): State<AppState>,
+    Path(automation_id): Path<Uuid>,
+    Form(form): Form<ToggleFormData>,
+) -> Result<Redirect, AutomationError> {
+    if !set_automation_active(&state.pg_pool, automation_id, form.is_active)
+        .await
+        .context("Failed to update the automation")?
+    {
+        return Err(AutomationError::NotFound);
+    }
+    messages.info(if form.is_active {
+        "The automation has been resumed."
+    } else {
+        "The automation has been paused. Steps already scheduled will still be sent."
+    });
+    Ok(Redirect::to(&format!("/admin/automations/{automation_id}")))
+}
+
+#[derive(Deserialize)]
+pub struct StepFormData {
+    subject: String,
+    delay: u32,
+    delay_unit: String,
+    html: String,
+    text: String,
+    /// An empty value means no layout at all.
+    #[serde(default)]
+    layout_id: String,
+}
+
+#[tracing::instrument(name = "Add an automation step", skip(messages, state, form))]
+pub async fn add_step_handler(
+    messages: Messages,
+    State(state): State<AppState>,
+    Path(automation_id): Path<Uuid>,
+    Form(form): Form<StepFormData>,
+) -> Result<Redirect, AutomationError> {
+    let automation_page = format!("/admin/automations/{automation_id}");
+    get_automation(&state.pg_pool, automation_id)
+        .await
+        .context("Failed to load the automation")?
+        .ok_or(AutomationError::NotFound)?;
+
+    let mut problems = Vec::new();
+    if form.subject.trim().is_empty() {
+        problems.push("The step needs a subject.".to_string());
+    }
+    let delay_hours = delay_hours(form.delay, &form.delay_unit).map_err(|e| problems.push(e));
+    let layout_id = match form.layout_id.trim() {
+        "" => Ok(None),
+        id => Uuid::parse_str(id)
+            .map(Some)
+            .map_err(|_| problems.push(format!("{id} is not a valid layout id."))),
+    };
+    let html = prepare_html(&form.html).map_err(|e| problems.extend(e));
+    let (delay_hours, layout_id, html) = match (delay_hours, layout_id, html) {
+        (Ok(delay_hours), Ok(layout_id), Ok(html)) if problems.is_empty() => {
+            (delay_hours, layout_id, html)
+        }
+        _ => {
+            problems.into_iter().fold(messages, Messages::error);
+            return Ok(Redirect::to(&automation_page));
+        }
+    };
+
+    let html_content = absolutize_media_urls(&html.html, &state.base_url.0)
+        .context("Failed to rewrite the image urls")?;
+    let text_content = if form.text.trim().is_empty() {
+        html_to_text(&html_content).context("Failed to generate the plain text content")?
+    } else {
+        form.text.clone()
+    };
+    let mut transaction = state
+        .pg_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    insert_step(
+        &mut transaction,
+        automation_id,
+        delay_hours,
+        form.subject.trim(),
+        &html_content,
+        &text_content,
+        layout_id,
+    )
+    .await
+    .context("Failed to store the automation step")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the automation step")?;
+
+    html.warnings.into_iter().fold(
+        messages.info("The step has been added, it applies to subscribers confirming from now on."),
+        Messages::warning,
+    );
+    Ok(Redirect::to(&automation_page))
+}
+
+#[tracing::instrument(name = "Delete an automation step", skip(messages, state))]
+pub async fn delete_step_handler(
+    messages: Messages,
+    State(state): State<AppState>,
+    Path((automation_id, step_id)): Path<(Uuid, Uuid)>,
+) -> Result<Redirect, AutomationError> {
+    let mut transaction = state
+        .pg_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    if !delete_step(&mut transaction, step_id)
+        .await
+        .context("Failed to delete the automation step")?
+    {
+        return Err(AutomationError::NotFound);
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the deleted step")?;
+    messages.info("The step has been deleted and its pending deliveries cancelled.");
+    Ok(Redirect::to(&format!("/admin/automations/{automation_id}")))
+}
@@ -1,37 +1,48 @@
-use anyhow::Context;
-use axum::{
-    extract::State,
-    response::{Html, IntoResponse, Redirect},
-};
-use sqlx::PgPool;
-use uuid::Uuid;
-
-use crate::{routes::session_state::TypedSession, startup::AppState};
+use axum::response::{Html, IntoResponse};
+use std::fmt::Write;
 
-pub async fn admin_dashboard(
-    State(state): State<AppState>,
-    session: TypedSession,
-) -> Result<impl IntoResponse, Redirect> {
-    let username = if let Some(user_id) = session
-        .get_user_id()
-        .await
-        .expect("failed to get user_id from session.")
-    {
-        get_username(user_id, &state.pg_pool)
-            .await
-            .map_err(|_| Redirect::to("/login"))
-    } else {
-        return Err(Redirect::to("/login"));
-    };
+use crate::{
+    authorization::{CurrentUser, Permission},
+    csrf::CsrfToken,
+    html::escape_html,
+};
 
-    let usernamestring = match username {
-        Ok(username) => username,
-        Err(_) => {
-            return Err(Redirect::to("/login"));
+pub async fn admin_dashboard(csrf_token: CsrfToken, user: CurrentUser) -> impl IntoResponse {
+    let csrf_field = csrf_token.form_field();
+    let username = escape_html(&user.username);
+    let role = user.role.label();
+    let mut actions = String::from(
+        r#"<li><a href="/admin/password">Change password</a></li>
+                <li><a href="/admin/two-factor">Two-factor authentication</a></li>
+                <li><a href="/admin/sessions">Active sessions</a></li>"#,
+    );
+    for (permission, href, label) in [
+        (
+            Permission::Publish,
+            "/admin/newsletters",
+            "Send a newsletter",
+        ),
+        (Permission::View, "/admin/issues", "Published issues"),
+        (
+            Permission::View,
+            "/admin/suppressions",
+            "Manage the suppression list",
+        ),
+        (Permission::View, "/admin/layouts", "Manage email layouts"),
+        (Permission::View, "/admin/media", "Upload images"),
+        (
+            Permission::View,
+            "/admin/automations",
+            "Welcome emails and other automations",
+        ),
+        (Permission::ManageUsers, "/admin/users", "Manage users"),
+    ] {
+        if user.role.can(permission) {
+            write!(actions, r#"<li><a href="{href}">{label}</a></li>"#).unwrap();
         }
-    };
+    }
 
-    let body = Html(format!(
+    Html(format!(
         r#"<!DOCTYPE html>
         <html lang="en">
         <head>
@@ -39,36 +50,19 @@ pub async fn admin_dashboard(
             <title>Admin dashboard</title>
         </head>
         <body>
-            <p>Welcome {usernamestring}!</p>
+            <p>Welcome {username}!</p>
+            <p>You are signed in as: {role}</p>
             <p>Available actions:</p>
             <ol>
-                <li><a href="/admin/password">Change password</a></li>
-                <li><a href="/admin/newsletters">Send a newsletter</a></li>
+                {actions}
                 <li>
                     <form name="logoutForm" action="/admin/logout" method="post">
+                        {csrf_field}
                         <input type="submit" value"Logout">
                     </form>
                 </li>
             </ol>
         </body>
         </html>"#
-    ));
-
-    Ok(body)
-}
-
-#[tracing::instrument(name = "Get username", skip(pool))]
-pub async fn get_username(user_id: Uuid, pool: &PgPool) -> Result<String, anyhow::Error> {
-    let row = sqlx::query!(
-        r#"
-        SELECT username
-        FROM users
-        WHERE user_id = $1
-    "#,
-        user_id
-    )
-    .fetch_one(pool)
-    .await
-    .context("Failed to perform a query to retrieve a username")?;
-    Ok(row.username)
+    ))
 }
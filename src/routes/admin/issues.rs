@@ -0,0 +1,264 @@
+use anyhow::Context;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::{html::escape_html, startup::AppState, subject_tests::get_variant_results};
+
+#[derive(thiserror::Error, Debug)]
+pub enum IssueStatsError {
+    #[error("The issue does not exist.")]
+    NotFound,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl IntoResponse for IssueStatsError {
+    fn into_response(self) -> Response {
+        match self {
+            IssueStatsError::NotFound => (StatusCode::NOT_FOUND, self.to_string()).into_response(),
+            IssueStatsError::UnexpectedError(err) => {
+                tracing::error!("{:?}", err);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Something went wrong".to_owned(),
+                )
+                    .into_response()
+            }
+        }
+    }
+}
+
+struct IssueStats {
+    newsletter_issue_id: Uuid,
+    title: String,
+    published_at: DateTime<Utc>,
+    track_opens: bool,
+    track_clicks: bool,
+    recipients: i64,
+    unique_opens: i64,
+    total_opens: i64,
+    total_clicks: i64,
+}
+
+struct LinkStats {
+    url: String,
+    unique_clicks: i64,
+    total_clicks: i64,
+}
+
+pub async fn issues_page(
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, IssueStatsError> {
+    let issues = get_issue_stats(&state.pg_pool)
+        .await
+        .context("Failed to load issue statistics")?;
+
+    let mut rows_html = String::new();
+    for issue in issues {
+        let opens = if issue.track_opens {
+            format!(
+                "{} of {} ({} total)",
+                issue.unique_opens, issue.recipients, issue.total_opens
+            )
+        } else {
+            "not tracked".to_string()
+        };
+        let clicks = if issue.track_clicks {
+            issue.total_clicks.to_string()
+        } else {
+            "not tracked".to_string()
+        };
+        writeln!(
+            rows_html,
+            r#"<tr id="issue-{id}"><td><a href="/admin/issues/{id}">{}</a></td><td>{}</td><td>{opens}</td><td>{clicks}</td></tr>"#,
+            escape_html(&issue.title),
+            issue.published_at,
+            id = issue.newsletter_issue_id,
+        )
+        .unwrap();
+    }
+
+    Ok(Html(format!(
+        r#"<!DOCTYPE html>
+        <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8">
+            <title>Published issues</title>
+        </head>
+        <body>
+            <table>
+                <tr><th>Title</th><th>Published at</th><th>Opens</th><th>Clicks</th></tr>
+                {rows_html}
+            </table>
+            <p><a href="/admin/dashboard">&lt;- Back</a></p>
+        </body>
+        </html>"#
+    )))
+}
+
+#[tracing::instrument(name = "Get issue statistics", skip(pool))]
+async fn get_issue_stats(pool: &PgPool) -> Result<Vec<IssueStats>, sqlx::Error> {
+    sqlx::query_as!(
+        IssueStats,
+        r#"
+        SELECT
+            i.newsletter_issue_id,
+            i.title,
+            i.published_at,
+            i.track_opens,
+            i.track_clicks,
+            count(r.recipient_id) AS "recipients!",
+            count(r.first_opened_at) AS "unique_opens!",
+            COALESCE(sum(r.open_count), 0) AS "total_opens!",
+            COALESCE(sum(c.click_count), 0)::bigint AS "total_clicks!"
+        FROM newsletter_issues i
+        LEFT JOIN issue_recipients r USING (newsletter_issue_id)
+        LEFT JOIN (
+            SELECT recipient_id, sum(click_count) AS click_count
+            FROM issue_clicks
+            GROUP BY recipient_id
+        ) c USING (recipient_id)
+        WHERE i.kind = 'issue'
+        GROUP BY i.newsletter_issue_id
+        ORDER BY i.published_at DESC
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+#[tracing::instrument(name = "Get the statistics of an issue", skip(pool))]
+async fn get_single_issue_stats(
+    pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<Option<IssueStats>, sqlx::Error> {
+    sqlx::query_as!(
+        IssueStats,
+        r#"
+        SELECT
+            i.newsletter_issue_id,
+            i.title,
+            i.published_at,
+            i.track_opens,
+            i.track_clicks,
+            count(r.recipient_id) AS "recipients!",
+            count(r.first_opened_at) AS "unique_opens!",
+            COALESCE(sum(r.open_count), 0) AS "total_opens!",
+            COALESCE(sum(c.click_count), 0)::bigint AS "total_clicks!"
+        FROM newsletter_issues i
+        LEFT JOIN issue_recipients r USING (newsletter_issue_id)
+        LEFT JOIN (
+            SELECT recipient_id, sum(click_count) AS click_count
+            FROM issue_clicks
+            GROUP BY recipient_id
+        ) c USING (recipient_id)
+        WHERE i.kind = 'issue' AND i.newsletter_issue_id = $1
+        GROUP BY i.newsletter_issue_id
+        "#,
+        issue_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn issue_stats_page(
+    State(state): State<AppState>,
+    Path(issue_id): Path<Uuid>,
+) -> Result<impl IntoResponse, IssueStatsError> {
+    let issue = get_single_issue_stats(&state.pg_pool, issue_id)
+        .await
+        .context("Failed to load issue statistics")?
+        .ok_or(IssueStatsError::NotFound)?;
+    let links = get_link_stats(&state.pg_pool, issue_id)
+        .await
+        .context("Failed to load link statistics")?;
+    let variants = get_variant_results(&*state.pg_pool, issue_id)
+        .await
+        .context("Failed to load subject line test results")?;
+
+    let mut variants_html = String::new();
+    if !variants.is_empty() {
+        variants_html.push_str(
+            "<table>\n<tr><th>Subject line</th><th>Tested on</th><th>Opened by</th><th>Clicked by</th></tr>\n",
+        );
+        for variant in variants {
+            let winner = if variant.is_winner { " (winner)" } else { "" };
+            writeln!(
+                variants_html,
+                r#"<tr><td>{}{winner}</td><td>{}</td><td>{}</td><td>{}</td></tr>"#,
+                escape_html(&variant.subject),
+                variant.recipients,
+                variant.opens,
+                variant.clickers
+            )
+            .unwrap();
+        }
+        variants_html.push_str("</table>");
+    }
+
+    let mut rows_html = String::new();
+    for link in links {
+        writeln!(
+            rows_html,
+            r#"<tr><td>{}</td><td>{}</td><td>{}</td></tr>"#,
+            escape_html(&link.url),
+            link.unique_clicks,
+            link.total_clicks
+        )
+        .unwrap();
+    }
+
+    Ok(Html(format!(
+        r#"<!DOCTYPE html>
+        <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8">
+            <title>{title}</title>
+        </head>
+        <body>
+            <h1>{title}</h1>
+            <p>Published at {published_at}</p>
+            <p>Opened by {unique_opens} of {recipients} tracked recipients ({total_opens} opens in total).</p>
+            {variants_html}
+            <table>
+                <tr><th>Link</th><th>Clicked by</th><th>Clicks</th></tr>
+                {rows_html}
+            </table>
+            <p><a href="/admin/issues">&lt;- Back</a></p>
+        </body>
+        </html>"#,
+        title = escape_html(&issue.title),
+        published_at = issue.published_at,
+        unique_opens = issue.unique_opens,
+        recipients = issue.recipients,
+        total_opens = issue.total_opens,
+    )))
+}
+
+#[tracing::instrument(name = "Get link statistics", skip(pool))]
+async fn get_link_stats(pool: &PgPool, issue_id: Uuid) -> Result<Vec<LinkStats>, sqlx::Error> {
+    sqlx::query_as!(
+        LinkStats,
+        r#"
+        SELECT
+            l.url,
+            count(c.recipient_id) AS "unique_clicks!",
+            COALESCE(sum(c.click_count), 0) AS "total_clicks!"
+        FROM issue_links l
+        LEFT JOIN issue_clicks c USING (link_id)
+        WHERE l.newsletter_issue_id = $1
+        GROUP BY l.link_id
+        ORDER BY 3 DESC, l.url
+        "#,
+        issue_id
+    )
+    .fetch_all(pool)
+    .await
+}
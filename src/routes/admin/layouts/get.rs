@@ -0,0 +1,167 @@
+use anyhow::Context;
+use axum::{
+    extract::{Path, State},
+    response::{Html, IntoResponse},
+};
+use axum_messages::Messages;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::{
+    csrf::CsrfToken,
+    html::escape_html,
+    layouts::{CONTENT_SLOT, get_layout, list_layouts},
+    routes::LayoutError,
+    startup::AppState,
+};
+
+pub async fn layouts_page(
+    messages: Messages,
+    csrf_token: CsrfToken,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, LayoutError> {
+    let csrf_field = csrf_token.form_field();
+    let mut msg_html = String::new();
+    for m in messages.into_iter() {
+        writeln!(msg_html, "<p><i>{m}</i></p>").unwrap();
+    }
+
+    let layouts = list_layouts(&state.pg_pool)
+        .await
+        .context("Failed to load the email layouts")?;
+    let mut rows_html = String::new();
+    for layout in layouts {
+        let default_html = if layout.is_default {
+            "Default".to_string()
+        } else {
+            format!(
+                r#"<form action="/admin/layouts/{}/default" method="post">
+                        {csrf_field}
+                        <button type="submit">Make default</button>
+                    </form>"#,
+                layout.layout_id
+            )
+        };
+        writeln!(
+            rows_html,
+            r#"<tr>
+                <td><a href="/admin/layouts/{id}">{}</a></td>
+                <td>{}</td>
+                <td>{default_html}</td>
+                <td>
+                    <form action="/admin/layouts/{id}/delete" method="post">
+                        {csrf_field}
+                        <button type="submit">Delete</button>
+                    </form>
+                </td>
+            </tr>"#,
+            escape_html(&layout.name),
+            layout.created_at.format("%Y-%m-%d %H:%M"),
+            id = layout.layout_id,
+        )
+        .unwrap();
+    }
+
+    Ok(Html(format!(
+        r#"<!DOCTYPE html>
+        <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8">
+            <title>Email layouts</title>
+        </head>
+        <body>
+            {msg_html}
+            <table>
+                <tr><th>Name</th><th>Created</th><th></th><th></th></tr>
+                {rows_html}
+            </table>
+            <h2>New layout</h2>
+            {form_html}
+            <p><a href="/admin/dashboard">&lt;- Back</a></p>
+        </body>
+        </html>"#,
+        form_html = layout_form(&csrf_field, "/admin/layouts", "", "", "", true),
+    )))
+}
+
+pub async fn edit_layout_page(
+    messages: Messages,
+    csrf_token: CsrfToken,
+    State(state): State<AppState>,
+    Path(layout_id): Path<Uuid>,
+) -> Result<impl IntoResponse, LayoutError> {
+    let csrf_field = csrf_token.form_field();
+    let mut msg_html = String::new();
+    for m in messages.into_iter() {
+        writeln!(msg_html, "<p><i>{m}</i></p>").unwrap();
+    }
+    let layout = get_layout(&state.pg_pool, layout_id)
+        .await
+        .context("Failed to load the email layout")?
+        .ok_or(LayoutError::NotFound)?;
+
+    Ok(Html(format!(
+        r#"<!DOCTYPE html>
+        <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8">
+            <title>Edit layout</title>
+        </head>
+        <body>
+            {msg_html}
+            <p>Changes apply to every issue sent from now on.</p>
+            {form_html}
+            <p><a href="/admin/layouts">&lt;- Back</a></p>
+        </body>
+        </html>"#,
+        form_html = layout_form(
+            &csrf_field,
+            &format!("/admin/layouts/{layout_id}"),
+            &layout.name,
+            &layout.html_template,
+            &layout.text_template,
+            false
+        ),
+    )))
+}
+
+fn layout_form(
+    csrf_field: &str,
+    action: &str,
+    name: &str,
+    html_template: &str,
+    text_template: &str,
+    with_default_option: bool,
+) -> String {
+    let default_option = if with_default_option {
+        r#"<label>
+                    <input type="checkbox" name="is_default">
+                    Use for new issues by default
+                </label>
+                <br>"#
+    } else {
+        ""
+    };
+    format!(
+        r#"<form action="{action}" method="post">
+                {csrf_field}
+                <label>Name
+                    <input type="text" name="name" value="{name}">
+                </label>
+                <br>
+                <label>HTML layout, with {CONTENT_SLOT} where the issue goes:<br>
+                    <textarea name="html_template" rows="20" cols="50">{html_template}</textarea>
+                </label>
+                <br>
+                <label>Plain text layout, with {CONTENT_SLOT} where the issue goes:<br>
+                    <textarea name="text_template" rows="10" cols="50">{text_template}</textarea>
+                </label>
+                <br>
+                {default_option}
+                <button type="submit">Save</button>
+            </form>"#,
+        name = escape_html(name),
+        html_template = escape_html(html_template),
+        text_template = escape_html(text_template),
+    )
+}
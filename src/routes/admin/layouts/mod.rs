@@ -0,0 +1,35 @@
+mod get;
+mod post;
+
+use axum::response::{IntoResponse, Response};
+pub use get::{edit_layout_page, layouts_page};
+pub use post::{
+    create_layout_handler, delete_layout_handler, set_default_layout_handler, update_layout_handler,
+};
+use reqwest::StatusCode;
+
+#[derive(thiserror::Error, Debug)]
+pub enum LayoutError {
+    #[error("The layout does not exist.")]
+    NotFound,
+
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl IntoResponse for LayoutError {
+    fn into_response(self) -> Response {
+        match self {
+            LayoutError::NotFound => (StatusCode::NOT_FOUND, self.to_string()).into_response(),
+            LayoutError::UnexpectedError(err) => {
+                tracing::error!("{:?}", err);
+
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Something went wrong".to_owned(),
+                )
+                    .into_response()
+            }
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use anyhow::Context;
+use axum::{
+    Form,
+    extract::{Path, State},
+    response::Redirect,
+};
+use axum_messages::Messages;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    issue_content::prepare_html,
+    layouts::{delete_layout, insert_layout, set_default_layout, update_layout, validate_template},
+    routes::LayoutError,
+    startup::AppState,
+};
+
+#[derive(Deserialize)]
+pub struct LayoutFormData {
+    name: String,
+    html_template: String,
+    text_template: String,
+    /// Checkboxes are only submitted when they are checked.
+    is_default: Option<String>,
+}
+
+/// The layout html goes through the same pipeline as the content of an issue.
+fn validate_layout(form: &LayoutFormData) -> Result<String, Vec<String>> {
+    if form.name.trim().is_empty() {
+        return Err(vec!["The layout needs a name.".into()]);
+    }
+    let html = prepare_html(&form.html_template)?.html;
+    let problems: Vec<_> = [
+        validate_template(&html, "HTML"),
+        validate_template(&form.text_template, "plain text"),
+    ]
+    .into_iter()
+    .filter_map(Result::err)
+    .collect();
+    if !problems.is_empty() {
+        return Err(problems);
+    }
+    Ok(html)
+}
+
+fn is_duplicate_name(e: &sqlx::Error) -> bool {
+    matches!(e, sqlx::Error::Database(e) if e.is_unique_violation())
+}
+
+#[tracing::instrument(name = "Create an email layout", skip_all)]
+pub async fn create_layout_handler(
+    messages: Messages,
+    State(state): State<AppState>,
+    Form(form): Form<LayoutFormData>,
+) -> Result<Redirect, LayoutError> {
+    let html = match validate_layout(&form) {
+        Ok(html) => html,
+        Err(problems) => {
+            problems.into_iter().fold(messages, Messages::error);
+            return Ok(Redirect::to("/admin/layouts"));
+        }
+    };
+
+    let mut transaction = state
+        .pg_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let layout_id = match insert_layout(
+        &mut transaction,
+        form.name.trim(),
+        &html,
+        &form.text_template,
+    )
+    .await
+    {
+        Ok(layout_id) => layout_id,
+        Err(e) if is_duplicate_name(&e) => {
+            messages.error(format!(
+                "A layout named {} already exists.",
+                form.name.trim()
+            ));
+            return Ok(Redirect::to("/admin/layouts"));
+        }
+        Err(e) => {
+            return Err(anyhow::Error::new(e)
+                .context("Failed to store the layout")
+                .into());
+        }
+    };
+    if form.is_default.is_some() {
+        set_default_layout(&mut transaction, layout_id)
+            .await
+            .context("Failed to make the layout the default")?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the new layout")?;
+
+    messages.info("The layout has been created.");
+    Ok(Redirect::to("/admin/layouts"))
+}
+
+#[tracing::instrument(name = "Update an email layout", skip(messages, state, form))]
+pub async fn update_layout_handler(
+    messages: Messages,
+    State(state): State<AppState>,
+    Path(layout_id): Path<Uuid>,
+    Form(form): Form<LayoutFormData>,
+) -> Result<Redirect, LayoutError> {
+    let edit_page = format!("/admin/layouts/{layout_id}");
+    let html = match validate_layout(&form) {
+        Ok(html) => html,
+        Err(problems) => {
+            problems.into_iter().fold(messages, Messages::error);
+            return Ok(Redirect::to(&edit_page));
+        }
+    };
+
+    let mut transaction = state
+        .pg_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    match update_layout(
+        &mut transaction,
+        layout_id,
+        form.name.trim(),
+        &html,
+        &form.text_template,
+    )
+    .await
+    {
+        Ok(true) => {}
+        Ok(false) => return Err(LayoutError::NotFound),
+        Err(e) if is_duplicate_name(&e) => {
+            messages.error(format!(
+                "A layout named {} already exists.",
+                form.name.trim()
+            ));
+            return Ok(Redirect::to(&edit_page));
+        }
+        Err(e) => {
+            return Err(anyhow::Error::new(e)
+                .context("Failed to update the layout")
+                .into());
+        }
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the layout changes")?;
+
+    messages.info("The layout has been saved.");
+    Ok(Redirect::to(&edit_page))
+}
+
+#[tracing::instrument(name = "Set the default email layout", skip(messages, state))]
+pub async fn set_default_layout_handler(
+    messages: Messages,
+    State(state): State<AppState>,
+    Path(layout_id): Path<Uuid>,
+) -> Result<Redirect, LayoutError> {
+    let mut transaction = state
+        .pg_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    set_default_layout(&mut transaction, layout_id)
+        .await
+        .context("Failed to make the layout the default")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the default layout")?;
+    messages.info("The default layout has been changed.");
+    Ok(Redirect::to("/admin/layouts"))
+}
+
+#[tracing::instrument(name = "Delete an email layout", skip(messages, state))]
+pub async fn delete_layout_handler(
+    messages: Messages,
+    State(state): State<AppState>,
+    Path(layout_id): Path<Uuid>,
+) -> Result<Redirect, LayoutError> {
+    delete_layout(&state.pg_pool, layout_id)
+        .await
+        .context("Failed to delete the layout")?;
+    messages.info("The layout has been deleted.");
+    Ok(Redirect::to("/admin/layouts"))
+}
@@ -1,52 +1,45 @@
-use axum::response::{IntoResponse, Redirect};
+use anyhow::Context;
+use axum::{
+    extract::State,
+    response::{IntoResponse, Redirect},
+};
 use axum_messages::Messages;
 use reqwest::StatusCode;
 
-use crate::routes::session_state::TypedSession;
+use crate::{
+    authentication::AuthSession, authorization::CurrentUser, startup::AppState,
+    users::revoke_user_session,
+};
 
 #[derive(thiserror::Error, Debug)]
 pub enum LogoutError {
-    #[error("Authentication failed")]
-    AuthError(#[source] anyhow::Error),
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
 
 impl IntoResponse for LogoutError {
     fn into_response(self) -> axum::response::Response {
-        let (status, message) = match self {
-            LogoutError::AuthError(rejection) => (StatusCode::UNAUTHORIZED, rejection.to_string()),
-            LogoutError::UnexpectedError(err) => {
-                tracing::error!("{:?}", err);
+        let LogoutError::UnexpectedError(err) = self;
+        tracing::error!("{:?}", err);
 
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "Something went wrong".to_owned(),
-                )
-            }
-        };
-
-        (status, message).into_response()
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Something went wrong".to_owned(),
+        )
+            .into_response()
     }
 }
 
 pub async fn log_out(
     messages: Messages,
-    session: TypedSession,
+    mut auth_session: AuthSession,
+    user: CurrentUser,
+    State(state): State<AppState>,
 ) -> Result<impl IntoResponse, LogoutError> {
-    if session
-        .get_user_id()
+    revoke_user_session(&state.pg_pool, user.user_id, user.session_id)
         .await
-        .map_err(|e| LogoutError::AuthError(e.into()))?
-        .is_none()
-    {
-        Ok(Redirect::to("/login"))
-    } else {
-        session
-            .log_out()
-            .await
-            .map_err(|e| LogoutError::UnexpectedError(e.into()))?;
-        messages.info("You have successfully logged out.");
-        Ok(Redirect::to("/login"))
-    }
+        .context("Failed to end the session")?;
+    auth_session.logout().await.context("Failed to log out")?;
+    messages.info("You have successfully logged out.");
+    Ok(Redirect::to("/login"))
 }
@@ -0,0 +1,84 @@
+use anyhow::Context;
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse},
+};
+use axum_messages::Messages;
+use std::fmt::Write;
+
+use crate::{
+    csrf::CsrfToken,
+    html::escape_html,
+    media::{list_media, media_path},
+    routes::MediaError,
+    startup::AppState,
+};
+
+pub async fn media_page(
+    messages: Messages,
+    csrf_token: CsrfToken,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, MediaError> {
+    let csrf_field = csrf_token.form_field();
+    let mut msg_html = String::new();
+    for m in messages.into_iter() {
+        writeln!(msg_html, "<p><i>{m}</i></p>").unwrap();
+    }
+
+    let items = list_media(&state.pg_pool)
+        .await
+        .context("Failed to load the uploaded images")?;
+    let mut rows_html = String::new();
+    for item in items {
+        let path = media_path(item.media_id);
+        writeln!(
+            rows_html,
+            r#"<tr>
+                <td><img src="{path}" alt="" height="60"></td>
+                <td>{}</td>
+                <td><code>{path}</code></td>
+                <td>{} KB</td>
+                <td>{}</td>
+                <td>
+                    <form action="/admin/media/{}/delete" method="post">
+                        {csrf_field}
+                        <button type="submit">Delete</button>
+                    </form>
+                </td>
+            </tr>"#,
+            escape_html(&item.file_name),
+            (item.size_bytes + 1023) / 1024,
+            item.created_at.format("%Y-%m-%d %H:%M"),
+            item.media_id,
+        )
+        .unwrap();
+    }
+
+    Ok(Html(format!(
+        r#"<!DOCTYPE html>
+        <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8">
+            <title>Images</title>
+        </head>
+        <body>
+            {msg_html}
+            <p>Use the url of an image as the <code>src</code> of an <code>img</code> tag in an issue.</p>
+            <table>
+                <tr><th></th><th>File</th><th>Url</th><th>Size</th><th>Uploaded</th><th></th></tr>
+                {rows_html}
+            </table>
+            <h2>Upload an image</h2>
+            <form action="/admin/media" method="post" enctype="multipart/form-data">
+                {csrf_field}
+                <label>PNG, JPEG, GIF or WebP, up to {max_kb} KB:
+                    <input type="file" name="file" accept="image/png,image/jpeg,image/gif,image/webp">
+                </label>
+                <button type="submit">Upload</button>
+            </form>
+            <p><a href="/admin/dashboard">&lt;- Back</a></p>
+        </body>
+        </html>"#,
+        max_kb = state.media_settings.max_upload_bytes / 1024,
+    )))
+}
@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::media_page;
+pub use post::{delete_media_handler, upload_media_handler};
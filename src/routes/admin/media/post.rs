@@ -0,0 +1,91 @@
+use anyhow::Context;
+use axum::{
+    extract::{Multipart, Path, State},
+    response::Redirect,
+};
+use axum_messages::Messages;
+use uuid::Uuid;
+
+use crate::{
+    html::escape_html,
+    media::{delete_media, detect_image_type, insert_media, media_path},
+    routes::MediaError,
+    startup::AppState,
+};
+
+#[tracing::instrument(name = "Upload an image", skip_all)]
+pub async fn upload_media_handler(
+    messages: Messages,
+    State(state): State<AppState>,
+    mut multipart: Multipart,
+) -> Result<Redirect, MediaError> {
+    let mut upload = None;
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .context("Failed to read the upload")?
+    {
+        if field.name() == Some("file") {
+            let file_name = field.file_name().unwrap_or_default().to_string();
+            let content = field.bytes().await.context("Failed to read the upload")?;
+            upload = Some((file_name, content));
+        }
+    }
+    let Some((file_name, content)) = upload.filter(|(_, content)| !content.is_empty()) else {
+        messages.error("Please choose an image to upload.");
+        return Ok(Redirect::to("/admin/media"));
+    };
+    // Flash messages are shown as html.
+    let shown_name = escape_html(&file_name);
+    let max_upload_bytes = state.media_settings.max_upload_bytes;
+    if content.len() > max_upload_bytes {
+        messages.error(format!(
+            "{shown_name} is larger than {} KB.",
+            max_upload_bytes / 1024
+        ));
+        return Ok(Redirect::to("/admin/media"));
+    }
+    let Some(content_type) = detect_image_type(&content) else {
+        messages.error(format!(
+            "{shown_name} is not a PNG, JPEG, GIF or WebP image."
+        ));
+        return Ok(Redirect::to("/admin/media"));
+    };
+
+    let media_id = Uuid::new_v4();
+    state.media.put(&media_id.to_string(), &content).await?;
+    insert_media(
+        &state.pg_pool,
+        media_id,
+        &file_name,
+        content_type,
+        i32::try_from(content.len()).context("The upload is too large")?,
+    )
+    .await
+    .context("Failed to store the media details")?;
+
+    messages.info(format!(
+        "{shown_name} has been uploaded, its url is {}",
+        media_path(media_id)
+    ));
+    Ok(Redirect::to("/admin/media"))
+}
+
+/// Issues that were already sent keep pointing at the deleted file and will show
+/// a broken image.
+#[tracing::instrument(name = "Delete an image", skip(messages, state))]
+pub async fn delete_media_handler(
+    messages: Messages,
+    State(state): State<AppState>,
+    Path(media_id): Path<Uuid>,
+) -> Result<Redirect, MediaError> {
+    if !delete_media(&state.pg_pool, media_id)
+        .await
+        .context("Failed to delete the media details")?
+    {
+        return Err(MediaError::NotFound);
+    }
+    state.media.delete(&media_id.to_string()).await?;
+    messages.info("The image has been deleted.");
+    Ok(Redirect::to("/admin/media"))
+}
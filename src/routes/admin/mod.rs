@@ -1,9 +1,25 @@
+mod automations;
 mod dashboard;
+mod issues;
+mod layouts;
 mod logout;
+mod media;
 mod newsletters;
 mod password;
+mod sessions;
+mod suppressions;
+mod two_factor;
+mod users;
 
+pub use automations::*;
 pub use dashboard::*;
+pub use issues::*;
+pub use layouts::*;
 pub use logout::*;
+pub use media::*;
 pub use newsletters::*;
 pub use password::*;
+pub use sessions::*;
+pub use suppressions::*;
+pub use two_factor::*;
+pub use users::*;
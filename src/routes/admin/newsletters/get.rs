@@ -1,69 +1,152 @@
-use axum::response::IntoResponse;
+use anyhow::Context;
+use axum::{extract::State, response::IntoResponse};
 use axum_messages::Messages;
 
-use crate::routes::{PublishError, session_state::TypedSession};
+use crate::{
+    attachments::{MAX_ATTACHMENT_SIZE, MAX_TOTAL_ATTACHMENT_SIZE},
+    csrf::CsrfToken,
+    html::escape_html,
+    layouts::list_layouts,
+    routes::PublishError,
+    startup::AppState,
+};
 use std::fmt::Write;
 
 pub async fn publish_newsletters_form(
-    session: TypedSession,
     messages: Messages,
+    csrf_token: CsrfToken,
+    State(state): State<AppState>,
 ) -> Result<impl IntoResponse, PublishError> {
-    if session
-        .get_user_id()
+    let csrf_field = csrf_token.form_field();
+    let mut msg_html = String::new();
+    for m in messages.into_iter() {
+        writeln!(msg_html, "<p><i>{m}</i></p>").expect("failed to insert header in message");
+    }
+    let layouts = list_layouts(&state.pg_pool)
         .await
-        .expect("failed to get user_id from session.")
-        .is_some()
-    {
-        let mut msg_html = String::new();
-        for m in messages.into_iter() {
-            writeln!(msg_html, "<p><i>{m}</i></p>").expect("failed to insert header in message");
-        }
-        let idempotency_key = uuid::Uuid::new_v4();
-        let body = format!(
-            r#"<!DOCTYPE html>
-                <html lang="en">
-                <head>
-                    <meta http-equiv="content-type" content="text/html; charset=utf-8">
-                    <title>Publish Newsletter Issue</title>
-                </head>
-                <body>
-                    {msg_html}
-                    <form action="/admin/newsletters" method="post">
-                        <label>Title:<br>
-                            <input
-                                type="text"
-                                placeholder="Enter the issue title"
-                                name="title"
-                            >
+        .context("Failed to load the email layouts")?;
+    let mut layout_options = String::from(r#"<option value="">No layout</option>"#);
+    for layout in layouts {
+        writeln!(
+            layout_options,
+            r#"<option value="{}"{}>{}</option>"#,
+            layout.layout_id,
+            if layout.is_default { " selected" } else { "" },
+            escape_html(&layout.name)
+        )
+        .unwrap();
+    }
+    let idempotency_key = uuid::Uuid::new_v4();
+    let body = format!(
+        r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Publish Newsletter Issue</title>
+            </head>
+            <body>
+                {msg_html}
+                <form action="/admin/newsletters" method="post" enctype="multipart/form-data">
+                    {csrf_field}
+                    <label>Title:<br>
+                        <input
+                            type="text"
+                            placeholder="Enter the issue title"
+                            name="title"
+                        >
+                    </label>
+                    <br>
+                    <label>Plain text content:<br>
+                        <textarea
+                            placeholder="Leave empty to generate it from the HTML content"
+                            name="text_content"
+                            rows="20"
+                            cols="50"
+                        ></textarea>
+                    </label>
+                    <br>
+                    <label>HTML content:<br>
+                        <textarea
+                            placeholder="Enter the content in HTML format"
+                            name="html_content"
+                            rows="20"
+                            cols="50"
+                        ></textarea>
+                    </label>
+                    <br>
+                    <label>Attachments (PDF, images, text; up to {max_attachment_mb} MB each, {max_total_mb} MB in total):<br>
+                        <input type="file" name="attachments" multiple>
+                    </label>
+                    <br>
+                    <label>Layout:
+                        <select name="layout_id">
+                            {layout_options}
+                        </select>
+                    </label>
+                    <br>
+                    <label>
+                        <input type="checkbox" name="track_opens">
+                        Track opens
+                    </label>
+                    <br>
+                    <label>
+                        <input type="checkbox" name="track_clicks">
+                        Track link clicks
+                    </label>
+                    <br>
+                    <label>
+                        <input type="checkbox" name="keep_private">
+                        Keep out of the public archive
+                    </label>
+                    <br>
+                    <fieldset>
+                        <legend>Delivery</legend>
+                        <label>Deliver at (local time of each subscriber):
+                            <input type="time" name="send_time">
+                        </label>
+                        <label>on
+                            <input type="date" name="send_date">
                         </label>
                         <br>
-                        <label>Plain text content:<br>
+                        <small>Leave the time empty to send right away, or the date empty for its next occurrence.</small>
+                    </fieldset>
+                    <fieldset>
+                        <legend>Subject line test</legend>
+                        <label>Alternative subject lines, one per line:<br>
                             <textarea
-                                placeholder="Enter the content in plain text"
-                                name="text_content"
-                                rows="20"
+                                placeholder="Leave empty to send the title to everybody"
+                                name="subject_variants"
+                                rows="3"
                                 cols="50"
                             ></textarea>
                         </label>
                         <br>
-                        <label>HTML content:<br>
-                            <textarea
-                                placeholder="Enter the content in HTML format"
-                                name="html_content"
-                                rows="20"
-                                cols="50"
-                            ></textarea>
+                        <label>Test slice (% of the audience):
+                            <input type="number" name="test_percentage" value="20" min="1" max="99">
                         </label>
                         <br>
-                        <input hidden type="text" name="idempotency_key" value="{idempotency_key}">
-                        <button type="submit">Publish</button>
-                    </form>
-                    <p><a href="/admin/dashboard">&lt;- Back</a></p>
-                </body>
-                </html>"#
-        );
-        Ok(body)
-    } else {
-        Err(PublishError::Unauthenticated("Please log in.".into()))
-    }
+                        <label>Pick the winner after (hours):
+                            <input type="number" name="test_window_hours" value="4" min="1">
+                        </label>
+                        <br>
+                        <label>Winning metric:
+                            <select name="winning_metric">
+                                <option value="opens">Opens</option>
+                                <option value="clicks">Clicks</option>
+                            </select>
+                        </label>
+                    </fieldset>
+                    <input hidden type="text" name="idempotency_key" value="{idempotency_key}">
+                    <button type="submit" formaction="/admin/newsletters/preview" formtarget="_blank">
+                        Preview plain text
+                    </button>
+                    <button type="submit">Publish</button>
+                </form>
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+            </html>"#,
+        max_attachment_mb = MAX_ATTACHMENT_SIZE / 1024 / 1024,
+        max_total_mb = MAX_TOTAL_ATTACHMENT_SIZE / 1024 / 1024,
+    );
+    Ok(body)
 }
@@ -1,5 +1,8 @@
 mod get;
+mod multipart_form;
 mod post;
+mod preview;
 
 pub use get::*;
 pub use post::{PublishError, publish_newsletters_handler};
+pub use preview::preview_newsletter_handler;
@@ -0,0 +1,111 @@
+use axum::{
+    Form, RequestExt,
+    extract::{FromRequest, Multipart, Request, multipart::MultipartError},
+    http::header::CONTENT_TYPE,
+    response::{IntoResponse, Response},
+};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+
+/// A file from a `multipart/form-data` body.
+pub struct UploadedFile {
+    pub file_name: String,
+    pub content_type: String,
+    pub content: Vec<u8>,
+}
+
+/// Like `Form`, but also accepts `multipart/form-data` bodies so that a form
+/// can carry file uploads next to its regular fields.
+pub struct MultipartForm<T> {
+    pub fields: T,
+    pub files: Vec<UploadedFile>,
+}
+
+impl<S, T> FromRequest<S> for MultipartForm<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let is_multipart = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("multipart/form-data"));
+        if !is_multipart {
+            let Form(fields) = Form::<T>::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            return Ok(Self {
+                fields,
+                files: vec![],
+            });
+        }
+
+        let mut multipart: Multipart = req
+            .extract_with_state(state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let mut pairs = Vec::new();
+        let mut files = Vec::new();
+        while let Some(field) = multipart
+            .next_field()
+            .await
+            .map_err(|e| rejection(e, "the form"))?
+        {
+            let name = field.name().unwrap_or_default().to_string();
+            match field.file_name().map(str::to_string) {
+                Some(file_name) => {
+                    let content_type = field
+                        .content_type()
+                        .unwrap_or("application/octet-stream")
+                        .to_string();
+                    let content = field
+                        .bytes()
+                        .await
+                        .map_err(|e| rejection(e, &format!("the attachment {file_name}")))?;
+                    // Browsers submit an empty part for a file input left blank.
+                    if file_name.is_empty() && content.is_empty() {
+                        continue;
+                    }
+                    files.push(UploadedFile {
+                        file_name,
+                        content_type,
+                        content: content.to_vec(),
+                    });
+                }
+                None => {
+                    let value = field
+                        .text()
+                        .await
+                        .map_err(|e| rejection(e, &format!("the field {name}")))?;
+                    pairs.push((name, value));
+                }
+            }
+        }
+
+        // Reuse the `Form` deserialization rules for the regular fields.
+        let fields = serde_urlencoded::to_string(&pairs)
+            .map_err(|e| e.to_string())
+            .and_then(|encoded| serde_urlencoded::from_str(&encoded).map_err(|e| e.to_string()))
+            .map_err(|e| {
+                (
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    format!("Failed to deserialize the form fields: {e}"),
+                )
+                    .into_response()
+            })?;
+        Ok(Self { fields, files })
+    }
+}
+
+/// Keeps the status axum picked, but says which part of the form failed.
+fn rejection(err: MultipartError, part: &str) -> Response {
+    (
+        err.status(),
+        format!("Failed to read {part}: {}", err.body_text()),
+    )
+        .into_response()
+}
@@ -1,11 +1,22 @@
+use super::multipart_form::{MultipartForm, UploadedFile};
 use crate::{
+    attachments::{Attachment, insert_attachments, validate_total_size},
+    audience::schedule_audience_expansion,
+    authorization::CurrentUser,
+    configuration::TrackingSettings,
+    domain::IssueSlug,
+    html::escape_html,
     idempotency::{IdempotencyKey, NextAction, save_response, try_processing},
-    routes::session_state::TypedSession,
+    issue_content::{html_to_text, prepare_html},
+    layouts::get_default_layout_id,
+    media::absolutize_media_urls,
     startup::AppState,
+    subject_tests::{WinningMetric, create_subject_test},
+    timezones::LocalSendTime,
+    tracking::{insert_issue_links, trackable_links},
 };
 use anyhow::Context;
 use axum::{
-    Form,
     extract::State,
     http::status::StatusCode,
     response::{IntoResponse, Redirect, Response},
@@ -14,14 +25,46 @@ use axum_messages::Messages;
 use serde::Deserialize;
 use sqlx::Executor;
 use sqlx::{Postgres, Transaction};
+use std::time::Duration;
 use uuid::Uuid;
 
 #[derive(Deserialize)]
 pub struct FormData {
     title: String,
+    /// The publish form sends `html_content` and `text_content`.
+    #[serde(alias = "html_content")]
     html: String,
+    #[serde(alias = "text_content")]
     text: String,
     idempotency_key: String,
+    /// Checkboxes are only submitted when they are checked.
+    track_opens: Option<String>,
+    track_clicks: Option<String>,
+    keep_private: Option<String>,
+    /// Missing means the default layout, an empty value means no layout at all.
+    layout_id: Option<String>,
+    /// Alternative subject lines, one per line. The title is always the first variant.
+    #[serde(default)]
+    subject_variants: String,
+    #[serde(default = "default_test_percentage")]
+    test_percentage: u8,
+    #[serde(default = "default_test_window_hours")]
+    test_window_hours: u32,
+    #[serde(default)]
+    winning_metric: WinningMetric,
+    /// Deliver at this local time in each subscriber's time zone instead of right away.
+    #[serde(default)]
+    send_date: String,
+    #[serde(default)]
+    send_time: String,
+}
+
+fn default_test_percentage() -> u8 {
+    20
+}
+
+fn default_test_window_hours() -> u32 {
+    4
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -29,62 +72,136 @@ pub enum PublishError {
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
     #[error("{0}")]
-    Unauthenticated(String),
-    #[error("{0}")]
     ValidationError(String),
 }
 
 #[tracing::instrument(
     name = "Publishing new newsletter",
-    skip(session, messages, state, form)
+    skip(user, messages, state, form, files)
 )]
 pub async fn publish_newsletters_handler(
-    session: TypedSession,
+    user: CurrentUser,
     messages: Messages,
     state: State<AppState>,
-    form: Form<FormData>,
+    MultipartForm {
+        fields: form,
+        files,
+    }: MultipartForm<FormData>,
 ) -> Result<impl IntoResponse, PublishError> {
-    if let Some(user_id) = session
-        .get_user_id()
-        .await
-        .expect("failed to get user_id from session.")
-    {
-        let idempotency_key: IdempotencyKey = form
-            .idempotency_key
-            .clone()
-            .try_into()
-            .map_err(|e: anyhow::Error| PublishError::ValidationError(e.to_string()))?;
-
-        let mut transaction = match try_processing(&state.pg_pool, &idempotency_key, user_id)
-            .await
-            .map_err(PublishError::UnexpectedError)?
-        {
-            NextAction::StartProcessing(t) => t,
-            NextAction::ReturnSavedResponse(saved_response) => {
-                messages.info("The newsletter issue has been published!");
-                return Ok(saved_response);
-            }
-        };
-        let issue_id =
-            insert_newsletter_issue(&mut transaction, &form.title, &form.text, &form.html)
-                .await
-                .context("Failed to store newsletter issue details")
-                .map_err(PublishError::UnexpectedError)?;
-        enqueue_deliver_tasks(&mut transaction, issue_id)
+    let idempotency_key: IdempotencyKey = form
+        .idempotency_key
+        .clone()
+        .try_into()
+        .map_err(|e: anyhow::Error| PublishError::ValidationError(e.to_string()))?;
+    let subjects = subject_lines(&form.title, &form.subject_variants);
+    if subjects.len() > 1 {
+        validate_subject_test(&form, &state.tracking)?;
+    }
+    let send_at = LocalSendTime::parse(&form.send_date, &form.send_time)
+        .map_err(PublishError::ValidationError)?;
+    if send_at.is_some() && subjects.len() > 1 {
+        return Err(PublishError::ValidationError(
+            "Subject line tests cannot be delivered at a local time yet.".into(),
+        ));
+    }
+    // Problems are reported before anything is stored, so the author can fix them.
+    let mut html = match prepare_html(&form.html) {
+        Ok(html) => html,
+        Err(problems) => {
+            problems.into_iter().fold(messages, |messages, problem| {
+                messages.error(escape_html(&problem))
+            });
+            return Ok(Redirect::to("/admin/newsletters").into_response());
+        }
+    };
+    html.html = absolutize_media_urls(&html.html, &state.base_url.0)
+        .context("Failed to rewrite the image urls")?;
+    let attachments = match parse_attachments(files) {
+        Ok(attachments) => attachments,
+        Err(problems) => {
+            problems.into_iter().fold(messages, |messages, problem| {
+                messages.error(escape_html(&problem))
+            });
+            return Ok(Redirect::to("/admin/newsletters").into_response());
+        }
+    };
+    let layout_id = match form.layout_id.as_deref().map(str::trim) {
+        None => get_default_layout_id(&state.pg_pool)
             .await
-            .context("Failed to enqueue delivery tasks")
-            .map_err(PublishError::UnexpectedError)?;
+            .context("Failed to look up the default layout")?,
+        Some("") => None,
+        Some(id) => Some(Uuid::parse_str(id).map_err(|_| {
+            PublishError::ValidationError(format!("{id} is not a valid layout id."))
+        })?),
+    };
+    let text = if form.text.trim().is_empty() {
+        html_to_text(&html.html).context("Failed to generate the plain text content")?
+    } else {
+        form.text.clone()
+    };
 
-        //Old
-        messages.info("The newsletter issue has been published!");
-        let response = Redirect::to("/admin/newsletters").into_response();
-        let response = save_response(transaction, &idempotency_key, user_id, response)
+    let mut transaction = match try_processing(&state.pg_pool, &idempotency_key, user.user_id)
+        .await
+        .map_err(PublishError::UnexpectedError)?
+    {
+        NextAction::StartProcessing(t) => t,
+        NextAction::ReturnSavedResponse(saved_response) => {
+            messages.info("The newsletter issue has been published!");
+            return Ok(saved_response);
+        }
+    };
+    let issue = NewIssue {
+        title: &form.title,
+        text_content: &text,
+        html_content: &html.html,
+        track_opens: form.track_opens.is_some(),
+        track_clicks: form.track_clicks.is_some(),
+        is_public: form.keep_private.is_none(),
+        layout_id,
+    };
+    let issue_id = insert_newsletter_issue(&mut transaction, &issue)
+        .await
+        .context("Failed to store newsletter issue details")
+        .map_err(PublishError::UnexpectedError)?;
+    if issue.track_clicks {
+        let links = trackable_links(&html.html).context("Failed to collect the links")?;
+        insert_issue_links(&mut transaction, issue_id, &links)
             .await
-            .map_err(PublishError::UnexpectedError)?;
-        Ok(response)
-    } else {
-        return Err(PublishError::Unauthenticated("Please log in.".into()));
+            .context("Failed to store the links")?;
     }
+    insert_attachments(&mut transaction, issue_id, &attachments)
+        .await
+        .context("Failed to store the attachments")?;
+    let test_percentage = if subjects.len() > 1 {
+        create_subject_test(
+            &mut transaction,
+            issue_id,
+            &subjects,
+            form.winning_metric,
+            Duration::from_secs(u64::from(form.test_window_hours) * 60 * 60),
+        )
+        .await
+        .context("Failed to store the subject line test")
+        .map_err(PublishError::UnexpectedError)?;
+        Some(form.test_percentage)
+    } else {
+        None
+    };
+    schedule_audience_expansion(&mut transaction, issue_id, send_at, test_percentage)
+        .await
+        .context("Failed to schedule the delivery")
+        .map_err(PublishError::UnexpectedError)?;
+
+    //Old
+    html.warnings.into_iter().fold(
+        messages.info("The newsletter issue has been published!"),
+        |messages, warning| messages.warning(escape_html(&warning)),
+    );
+    let response = Redirect::to("/admin/newsletters").into_response();
+    let response = save_response(transaction, &idempotency_key, user.user_id, response)
+        .await
+        .map_err(PublishError::UnexpectedError)?;
+    Ok(response)
 }
 
 impl IntoResponse for PublishError {
@@ -98,7 +215,6 @@ impl IntoResponse for PublishError {
                     "Something went wrong".to_owned(),
                 )
             }
-            PublishError::Unauthenticated(e) => (StatusCode::UNAUTHORIZED, e),
             PublishError::ValidationError(e) => (StatusCode::BAD_REQUEST, e.to_string()),
         };
 
@@ -106,14 +222,23 @@ impl IntoResponse for PublishError {
     }
 }
 
+struct NewIssue<'a> {
+    title: &'a str,
+    text_content: &'a str,
+    html_content: &'a str,
+    track_opens: bool,
+    track_clicks: bool,
+    is_public: bool,
+    layout_id: Option<Uuid>,
+}
+
 #[tracing::instrument(name = "insert newsletter issue", skip_all)]
 async fn insert_newsletter_issue(
     transaction: &mut Transaction<'static, Postgres>,
-    title: &str,
-    text_content: &str,
-    html_content: &str,
+    issue: &NewIssue<'_>,
 ) -> Result<Uuid, sqlx::Error> {
     let newsletter_issue_id = Uuid::new_v4();
+    let slug = IssueSlug::generate(issue.title, newsletter_issue_id);
     let query = sqlx::query!(
         r#"
     INSERT INTO newsletter_issues (
@@ -121,36 +246,86 @@ async fn insert_newsletter_issue(
         title, 
         text_content,
         html_content,
-        published_at
+        published_at,
+        track_opens,
+        track_clicks,
+        is_public,
+        slug,
+        layout_id
     )
-    VALUES ($1, $2, $3, $4, now())
+    VALUES ($1, $2, $3, $4, now(), $5, $6, $7, $8, $9)
     "#,
         newsletter_issue_id,
-        title,
-        text_content,
-        html_content
+        issue.title,
+        issue.text_content,
+        issue.html_content,
+        issue.track_opens,
+        issue.track_clicks,
+        issue.is_public,
+        slug.as_ref(),
+        issue.layout_id
     );
     transaction.execute(query).await?;
     Ok(newsletter_issue_id)
 }
 
-async fn enqueue_deliver_tasks(
-    transaction: &mut Transaction<'static, Postgres>,
-    newsletter_issue_id: Uuid,
-) -> Result<(), sqlx::Error> {
-    let query = sqlx::query!(
-        r#"
-            INSERT INTO issue_delivery_queue (
-                newsletter_issue_id,
-                subscriber_email
-            )
-            SELECT $1, email
-            FROM subscriptions
-            WHERE status = 'confirmed'
-        "#,
-        newsletter_issue_id
-    );
-    transaction.execute(query).await?;
+/// The title followed by every distinct, non-blank alternative.
+fn subject_lines(title: &str, alternatives: &str) -> Vec<String> {
+    let mut subjects = vec![title.trim().to_string()];
+    for line in alternatives.lines().map(str::trim) {
+        if !line.is_empty() && !subjects.iter().any(|s| s == line) {
+            subjects.push(line.to_string());
+        }
+    }
+    subjects
+}
+
+fn parse_attachments(files: Vec<UploadedFile>) -> Result<Vec<Attachment>, Vec<String>> {
+    let mut attachments = Vec::with_capacity(files.len());
+    let mut problems = Vec::new();
+    for file in files {
+        match Attachment::parse(&file.file_name, &file.content_type, file.content) {
+            Ok(attachment) => attachments.push(attachment),
+            Err(problem) => problems.push(problem),
+        }
+    }
+    if let Err(problem) = validate_total_size(&attachments) {
+        problems.push(problem);
+    }
+    if problems.is_empty() {
+        Ok(attachments)
+    } else {
+        Err(problems)
+    }
+}
+
+fn validate_subject_test(form: &FormData, settings: &TrackingSettings) -> Result<(), PublishError> {
+    if !(1..=99).contains(&form.test_percentage) {
+        return Err(PublishError::ValidationError(
+            "The test slice must be between 1 and 99 percent of the audience.".into(),
+        ));
+    }
+    if form.test_window_hours == 0 {
+        return Err(PublishError::ValidationError(
+            "The test window must be at least one hour.".into(),
+        ));
+    }
+    let (allowed, tracked) = match form.winning_metric {
+        WinningMetric::Opens => (settings.allow_open_tracking, form.track_opens.is_some()),
+        WinningMetric::Clicks => (settings.allow_click_tracking, form.track_clicks.is_some()),
+    };
+    if !allowed {
+        return Err(PublishError::ValidationError(format!(
+            "Tracking {} is turned off for this newsletter, so they cannot pick a winning subject line.",
+            form.winning_metric.as_str()
+        )));
+    }
+    if !tracked {
+        return Err(PublishError::ValidationError(format!(
+            "Tracking {} has to be enabled to pick a winning subject line by them.",
+            form.winning_metric.as_str()
+        )));
+    }
     Ok(())
 }
 
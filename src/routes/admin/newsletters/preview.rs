@@ -0,0 +1,62 @@
+use anyhow::Context;
+use axum::response::Html;
+use serde::Deserialize;
+use std::fmt::Write;
+
+use crate::{
+    html::escape_html,
+    issue_content::{html_to_text, prepare_html},
+    routes::PublishError,
+};
+
+use super::multipart_form::MultipartForm;
+
+/// The subset of the publish form that makes up the content of the issue.
+#[derive(Deserialize)]
+pub struct PreviewFormData {
+    #[serde(alias = "html_content")]
+    html: String,
+    #[serde(alias = "text_content")]
+    text: String,
+}
+
+/// Shows the plain text alternative exactly as it would be sent.
+pub async fn preview_newsletter_handler(
+    form: MultipartForm<PreviewFormData>,
+) -> Result<Html<String>, PublishError> {
+    let form = form.fields;
+    let preview_html = match prepare_html(&form.html) {
+        Ok(html) if form.text.trim().is_empty() => {
+            let text = html_to_text(&html.html).context("Failed to render the plain text")?;
+            format!(
+                "<p>Generated from the HTML content:</p>\n<pre>{}</pre>",
+                escape_html(&text)
+            )
+        }
+        Ok(_) => format!(
+            "<p>As written in the plain text content:</p>\n<pre>{}</pre>",
+            escape_html(&form.text)
+        ),
+        Err(problems) => {
+            let mut problems_html = String::new();
+            for problem in problems {
+                writeln!(problems_html, "<li>{}</li>", escape_html(&problem)).unwrap();
+            }
+            format!("<p>The HTML content can not be published:</p>\n<ul>{problems_html}</ul>")
+        }
+    };
+
+    Ok(Html(format!(
+        r#"<!DOCTYPE html>
+        <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8">
+            <title>Plain text preview</title>
+        </head>
+        <body>
+            <h1>Plain text preview</h1>
+            {preview_html}
+        </body>
+        </html>"#
+    )))
+}
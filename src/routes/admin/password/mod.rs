@@ -19,7 +19,6 @@ impl IntoResponse for PasswordError {
                 )
                     .into_response()
             }
-            PasswordError::Unauthenticated(_) => Redirect::to("/login").into_response(),
         }
     }
 }
@@ -31,7 +30,4 @@ pub enum PasswordError {
 
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
-
-    #[error("{0}")]
-    Unauthenticated(String),
 }
@@ -1,13 +1,17 @@
+use anyhow::Context;
 use axum::{Form, extract::State, response::Redirect};
+use axum_login::AuthnBackend;
 use axum_messages::Messages;
 use secrecy::{ExposeSecret, SecretString};
 use serde::Deserialize;
 
-use crate::authentication::change_password;
+use crate::authentication::{change_password, validate_password_length};
 use crate::{
-    authentication::{AuthError, Credentials, validate_credentials},
-    routes::{PasswordError, get_username, session_state::TypedSession},
+    authentication::{AuthError, AuthSession, Credentials, validate_credentials},
+    authorization::CurrentUser,
+    routes::PasswordError,
     startup::AppState,
+    users::revoke_other_sessions,
 };
 
 #[derive(Deserialize)]
@@ -17,58 +21,67 @@ pub struct FormData {
     new_password_check: SecretString,
 }
 
-#[tracing::instrument(name = "Change password", skip(state, form, messages, session))]
+#[tracing::instrument(
+    name = "Change password",
+    skip(state, form, messages, user, auth_session)
+)]
 pub async fn post_change_password(
     messages: Messages,
-    session: TypedSession,
+    user: CurrentUser,
+    mut auth_session: AuthSession,
     State(state): State<AppState>,
     Form(form): Form<FormData>,
 ) -> Result<Redirect, PasswordError> {
-    let user_id = session
-        .get_user_id()
-        .await
-        .map_err(|e| PasswordError::UnexpectedError(e.into()))?;
-    if let Some(userid) = user_id {
-        let username = get_username(userid, &state.pg_pool).await?;
-        let credentials = Credentials {
-            username,
-            password: form.current_password,
-        };
+    let credentials = Credentials {
+        username: user.username,
+        password: form.current_password,
+    };
 
-        if form.new_password.expose_secret() != form.new_password_check.expose_secret() {
-            messages
-                .error("You entered two different new passwords - the field values must match.");
-            return Err(PasswordError::ValidationError(
-                "passwords don't match".to_string(),
-            ));
-        }
+    if form.new_password.expose_secret() != form.new_password_check.expose_secret() {
+        messages.error("You entered two different new passwords - the field values must match.");
+        return Err(PasswordError::ValidationError(
+            "passwords don't match".to_string(),
+        ));
+    }
 
-        if form.new_password.expose_secret().len() <= 12
-            || form.new_password.expose_secret().len() >= 128
-        {
-            messages.error("Password length must be > 12  and < 128");
-            return Err(PasswordError::ValidationError(
-                "password length invalid".to_string(),
-            ));
-        }
-        if let Err(e) = validate_credentials(credentials, &state.pg_pool).await {
-            return match e {
-                AuthError::InvalidCredentials(_) => {
-                    messages.error("The current password is incorrect.");
+    if let Err(e) = validate_password_length(&form.new_password) {
+        messages.error(e);
+        return Err(PasswordError::ValidationError(
+            "password length invalid".to_string(),
+        ));
+    }
+    if let Err(e) = validate_credentials(credentials, &state.pg_pool).await {
+        return match e {
+            AuthError::InvalidCredentials(_) => {
+                messages.error("The current password is incorrect.");
 
-                    Err(PasswordError::ValidationError(
-                        "credentials invalid".to_string(),
-                    ))
-                }
-                AuthError::UnexpectedError(e) => Err(PasswordError::UnexpectedError(e)),
-            };
-        }
-        messages.error("Your password has been changed.");
-        change_password(userid, form.new_password, &state.pg_pool)
+                Err(PasswordError::ValidationError(
+                    "credentials invalid".to_string(),
+                ))
+            }
+            AuthError::UnexpectedError(e) => Err(PasswordError::UnexpectedError(e)),
+        };
+    }
+    messages.error("Your password has been changed.");
+    change_password(user.user_id, form.new_password, &state.pg_pool)
+        .await
+        .map_err(PasswordError::UnexpectedError)?;
+    // Sessions are tied to the password hash, so this one has to be
+    // updated to stay logged in.
+    if let Some(updated_user) = auth_session
+        .backend
+        .get_user(&user.user_id)
+        .await
+        .context("Failed to load the user")?
+    {
+        auth_session
+            .login(&updated_user)
             .await
-            .map_err(PasswordError::UnexpectedError)?;
-        Ok(Redirect::to("/admin/password"))
-    } else {
-        Err(PasswordError::Unauthenticated("".into()))
+            .context("Failed to update the session")?;
     }
+    // Whoever knew the old password should not stay logged in with it.
+    revoke_other_sessions(&state.pg_pool, user.user_id, Some(user.session_id))
+        .await
+        .context("Failed to log out the other sessions")?;
+    Ok(Redirect::to("/admin/password"))
 }
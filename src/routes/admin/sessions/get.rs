@@ -0,0 +1,77 @@
+use anyhow::Context;
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse},
+};
+use axum_messages::Messages;
+use std::fmt::Write;
+
+use crate::{
+    authorization::CurrentUser, csrf::CsrfToken, html::escape_html, routes::SessionError,
+    startup::AppState, users::list_user_sessions,
+};
+
+pub async fn sessions_page(
+    messages: Messages,
+    csrf_token: CsrfToken,
+    user: CurrentUser,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, SessionError> {
+    let csrf_field = csrf_token.form_field();
+    let mut msg_html = String::new();
+    for m in messages.into_iter() {
+        writeln!(msg_html, "<p><i>{m}</i></p>").unwrap();
+    }
+
+    let sessions = list_user_sessions(&state.pg_pool, user.user_id)
+        .await
+        .context("Failed to load the sessions")?;
+    let mut rows_html = String::new();
+    for session in sessions {
+        let action = if session.session_id == user.session_id {
+            "This session".to_string()
+        } else {
+            format!(
+                r#"<form action="/admin/sessions/{}/revoke" method="post">
+                    {csrf_field}
+                    <button type="submit">Revoke</button>
+                </form>"#,
+                session.session_id
+            )
+        };
+        write!(
+            rows_html,
+            r#"<tr>
+                <td>{}</td>
+                <td>{}</td>
+                <td>{}</td>
+                <td>{}</td>
+                <td>{action}</td>
+            </tr>"#,
+            session.created_at.format("%Y-%m-%d %H:%M UTC"),
+            session.last_seen_at.format("%Y-%m-%d %H:%M UTC"),
+            escape_html(session.ip_address.as_deref().unwrap_or("Unknown")),
+            escape_html(session.user_agent.as_deref().unwrap_or("Unknown")),
+        )
+        .unwrap();
+    }
+
+    Ok(Html(format!(
+        r#"<!DOCTYPE html>
+        <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8">
+            <title>Active sessions</title>
+        </head>
+        <body>
+            {msg_html}
+            <h1>Active sessions</h1>
+            <table>
+                <tr><th>Logged in</th><th>Last seen</th><th>IP address</th><th>Browser</th><th></th></tr>
+                {rows_html}
+            </table>
+            <p><a href="/admin/dashboard">&lt;- Back</a></p>
+        </body>
+        </html>"#
+    )))
+}
@@ -0,0 +1,29 @@
+mod get;
+mod post;
+
+use axum::response::{IntoResponse, Response};
+pub use get::sessions_page;
+pub use post::revoke_session_handler;
+use reqwest::StatusCode;
+
+#[derive(thiserror::Error, Debug)]
+pub enum SessionError {
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl IntoResponse for SessionError {
+    fn into_response(self) -> Response {
+        match self {
+            SessionError::UnexpectedError(err) => {
+                tracing::error!("{:?}", err);
+
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Something went wrong".to_owned(),
+                )
+                    .into_response()
+            }
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use anyhow::Context;
+use axum::{
+    extract::{Path, State},
+    response::Redirect,
+};
+use axum_messages::Messages;
+use uuid::Uuid;
+
+use crate::{
+    authorization::CurrentUser, routes::SessionError, startup::AppState, users::revoke_user_session,
+};
+
+#[tracing::instrument(name = "Revoke a session", skip(messages, state, user), fields(user_id = %user.user_id))]
+pub async fn revoke_session_handler(
+    messages: Messages,
+    user: CurrentUser,
+    State(state): State<AppState>,
+    Path(session_id): Path<Uuid>,
+) -> Result<Redirect, SessionError> {
+    if session_id == user.session_id {
+        messages.error("Log out to end the session you are using.");
+        return Ok(Redirect::to("/admin/sessions"));
+    }
+    let revoked = revoke_user_session(&state.pg_pool, user.user_id, session_id)
+        .await
+        .context("Failed to revoke the session")?;
+    if revoked {
+        messages.info("The session has been revoked.");
+    } else {
+        messages.error("The session has already ended.");
+    }
+    Ok(Redirect::to("/admin/sessions"))
+}
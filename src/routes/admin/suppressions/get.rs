@@ -0,0 +1,127 @@
+use anyhow::Context;
+use axum::{
+    extract::State,
+    http::header,
+    response::{Html, IntoResponse},
+};
+use axum_messages::Messages;
+use std::fmt::Write;
+
+use crate::{
+    csrf::CsrfToken,
+    html::escape_html,
+    routes::SuppressionError,
+    startup::AppState,
+    suppressions::{list_suppressions, to_csv},
+};
+
+pub async fn suppressions_page(
+    messages: Messages,
+    csrf_token: CsrfToken,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, SuppressionError> {
+    let csrf_field = csrf_token.form_field();
+    let mut msg_html = String::new();
+    for m in messages.into_iter() {
+        writeln!(msg_html, "<p><i>{m}</i></p>").unwrap();
+    }
+
+    let suppressions = list_suppressions(&state.pg_pool)
+        .await
+        .context("Failed to load the suppression list")?;
+    let mut rows_html = String::new();
+    for s in suppressions {
+        // Bounce webhooks fill these in, so they are not to be trusted.
+        let target = escape_html(&s.email.or(s.domain).unwrap_or_default());
+        writeln!(
+            rows_html,
+            r#"<tr>
+                <td>{target}</td>
+                <td>{}</td>
+                <td>{}</td>
+                <td>{}</td>
+                <td>
+                    <form action="/admin/suppressions/delete" method="post">
+                        {csrf_field}
+                        <input hidden type="text" name="suppression_id" value="{}">
+                        <button type="submit">Remove</button>
+                    </form>
+                </td>
+            </tr>"#,
+            escape_html(&s.reason),
+            s.source,
+            s.created_at.format("%Y-%m-%d %H:%M"),
+            s.suppression_id,
+        )
+        .unwrap();
+    }
+
+    Ok(Html(format!(
+        r#"<!DOCTYPE html>
+        <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8">
+            <title>Suppression list</title>
+        </head>
+        <body>
+            {msg_html}
+            <form action="/admin/suppressions" method="post">
+                {csrf_field}
+                <label>Email or domain
+                    <input
+                        type="text"
+                        placeholder="someone@example.com or example.com"
+                        name="target"
+                    >
+                </label>
+                <label>Reason
+                    <input
+                        type="text"
+                        placeholder="Why must they not be emailed?"
+                        name="reason"
+                    >
+                </label>
+                <button type="submit">Suppress</button>
+            </form>
+            <form action="/admin/suppressions/import" method="post">
+                {csrf_field}
+                <label>Import CSV (columns: email, domain, reason)<br>
+                    <textarea
+                        placeholder="email,domain,reason"
+                        name="csv"
+                        rows="10"
+                        cols="50"
+                    ></textarea>
+                </label>
+                <br>
+                <button type="submit">Import</button>
+            </form>
+            <p><a href="/admin/suppressions/export">Export as CSV</a></p>
+            <table>
+                <tr><th>Email or domain</th><th>Reason</th><th>Source</th><th>Added</th><th></th></tr>
+                {rows_html}
+            </table>
+            <p><a href="/admin/dashboard">&lt;- Back</a></p>
+        </body>
+        </html>"#
+    )))
+}
+
+pub async fn export_suppressions(
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, SuppressionError> {
+    let suppressions = list_suppressions(&state.pg_pool)
+        .await
+        .context("Failed to load the suppression list")?;
+    let csv = to_csv(&suppressions).context("Failed to serialize the suppression list")?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                r#"attachment; filename="suppressions.csv""#,
+            ),
+        ],
+        csv,
+    ))
+}
@@ -0,0 +1,35 @@
+mod get;
+mod post;
+
+use axum::response::{IntoResponse, Redirect, Response};
+pub use get::{export_suppressions, suppressions_page};
+pub use post::{add_suppression_handler, import_suppressions, remove_suppression_handler};
+use reqwest::StatusCode;
+
+#[derive(thiserror::Error, Debug)]
+pub enum SuppressionError {
+    #[error("{0}")]
+    ValidationError(String),
+
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl IntoResponse for SuppressionError {
+    fn into_response(self) -> Response {
+        match self {
+            SuppressionError::ValidationError(_) => {
+                Redirect::to("/admin/suppressions").into_response()
+            }
+            SuppressionError::UnexpectedError(err) => {
+                tracing::error!("{:?}", err);
+
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Something went wrong".to_owned(),
+                )
+                    .into_response()
+            }
+        }
+    }
+}
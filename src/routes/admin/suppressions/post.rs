@@ -0,0 +1,103 @@
+use anyhow::Context;
+use axum::{Form, extract::State, response::Redirect};
+use axum_messages::Messages;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    domain::SuppressionTarget,
+    routes::SuppressionError,
+    startup::AppState,
+    suppressions::{add_suppression, parse_csv, remove_suppression},
+};
+
+#[derive(Deserialize)]
+pub struct AddFormData {
+    target: String,
+    reason: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RemoveFormData {
+    suppression_id: Uuid,
+}
+
+#[derive(Deserialize)]
+pub struct ImportFormData {
+    csv: String,
+}
+
+#[tracing::instrument(name = "Add a suppression", skip(messages, state, form))]
+pub async fn add_suppression_handler(
+    messages: Messages,
+    State(state): State<AppState>,
+    Form(form): Form<AddFormData>,
+) -> Result<Redirect, SuppressionError> {
+    let target = SuppressionTarget::parse(form.target).map_err(|e| {
+        messages.clone().error(&e);
+        SuppressionError::ValidationError(e)
+    })?;
+    let reason = match form.reason.trim() {
+        "" => "added manually",
+        reason => reason,
+    };
+    if add_suppression(&*state.pg_pool, &target, reason, "admin")
+        .await
+        .context("Failed to store the suppression")?
+    {
+        messages.info(format!("{target} has been suppressed."));
+    } else {
+        messages.info(format!("{target} was already suppressed."));
+    }
+    Ok(Redirect::to("/admin/suppressions"))
+}
+
+#[tracing::instrument(name = "Remove a suppression", skip(messages, state))]
+pub async fn remove_suppression_handler(
+    messages: Messages,
+    State(state): State<AppState>,
+    Form(form): Form<RemoveFormData>,
+) -> Result<Redirect, SuppressionError> {
+    remove_suppression(&state.pg_pool, form.suppression_id)
+        .await
+        .context("Failed to remove the suppression")?;
+    messages.info("The suppression has been removed.");
+    Ok(Redirect::to("/admin/suppressions"))
+}
+
+#[tracing::instrument(name = "Import suppressions", skip_all)]
+pub async fn import_suppressions(
+    messages: Messages,
+    State(state): State<AppState>,
+    Form(form): Form<ImportFormData>,
+) -> Result<Redirect, SuppressionError> {
+    let entries = parse_csv(&form.csv).map_err(|e| {
+        messages.clone().error(&e);
+        SuppressionError::ValidationError(e)
+    })?;
+
+    let mut transaction = state
+        .pg_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let mut n_added = 0;
+    for (target, reason) in &entries {
+        if add_suppression(&mut *transaction, target, reason, "import")
+            .await
+            .context("Failed to store an imported suppression")?
+        {
+            n_added += 1;
+        }
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the imported suppressions")?;
+
+    messages.info(format!(
+        "Imported {n_added} new suppressions ({} already present).",
+        entries.len() - n_added
+    ));
+    Ok(Redirect::to("/admin/suppressions"))
+}
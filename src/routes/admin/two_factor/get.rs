@@ -0,0 +1,93 @@
+use anyhow::Context;
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse},
+};
+use axum_messages::Messages;
+use std::fmt::Write;
+
+use crate::{
+    authorization::CurrentUser,
+    csrf::CsrfToken,
+    routes::TwoFactorError,
+    startup::AppState,
+    two_factor::{get_two_factor_status, qr_code_svg},
+};
+
+pub async fn two_factor_page(
+    messages: Messages,
+    csrf_token: CsrfToken,
+    user: CurrentUser,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, TwoFactorError> {
+    let csrf_field = csrf_token.form_field();
+    let mut msg_html = String::new();
+    for m in messages.into_iter() {
+        writeln!(msg_html, "<p><i>{m}</i></p>").unwrap();
+    }
+
+    let status = get_two_factor_status(&state.pg_pool, user.user_id)
+        .await
+        .context("Failed to load the two-factor status")?;
+    let content = if status.enabled {
+        format!(
+            r#"<p>Two-factor authentication is enabled. {} unused recovery codes are left.</p>
+            <form action="/admin/two-factor/disable" method="post">
+                {csrf_field}
+                <label>Code from your app or a recovery code
+                    <input type="text" name="code" autocomplete="one-time-code">
+                </label>
+                <button type="submit">Disable two-factor authentication</button>
+            </form>"#,
+            status.unused_recovery_codes
+        )
+    } else if let Some(pending_secret) = status.pending_secret {
+        let secret = state
+            .two_factor
+            .decrypt_secret(&pending_secret)
+            .context("Failed to decrypt the pending secret")?;
+        let totp = state.two_factor.totp(secret, &user.username);
+        let qr_code = qr_code_svg(&totp.get_url())?;
+        format!(
+            r#"<p>Scan the code with your authenticator app, or enter the secret by hand.</p>
+            {qr_code}
+            <p>Secret: <code>{}</code></p>
+            <form action="/admin/two-factor/verify" method="post">
+                {csrf_field}
+                <label>Code from your app
+                    <input type="text" name="code" inputmode="numeric" autocomplete="one-time-code">
+                </label>
+                <button type="submit">Enable two-factor authentication</button>
+            </form>
+            <form action="/admin/two-factor/enrol" method="post">
+                {csrf_field}
+                <button type="submit">Start over with a new secret</button>
+            </form>"#,
+            totp.get_secret_base32()
+        )
+    } else {
+        format!(
+            r#"<p>Two-factor authentication is not enabled.</p>
+            <form action="/admin/two-factor/enrol" method="post">
+                {csrf_field}
+                <button type="submit">Set up two-factor authentication</button>
+            </form>"#
+        )
+    };
+
+    Ok(Html(format!(
+        r#"<!DOCTYPE html>
+        <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8">
+            <title>Two-factor authentication</title>
+        </head>
+        <body>
+            {msg_html}
+            <h1>Two-factor authentication</h1>
+            {content}
+            <p><a href="/admin/dashboard">&lt;- Back</a></p>
+        </body>
+        </html>"#
+    )))
+}
@@ -0,0 +1,29 @@
+mod get;
+mod post;
+
+use axum::response::{IntoResponse, Response};
+pub use get::two_factor_page;
+pub use post::{confirm_two_factor_handler, disable_two_factor_handler, enrol_two_factor_handler};
+use reqwest::StatusCode;
+
+#[derive(thiserror::Error, Debug)]
+pub enum TwoFactorError {
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl IntoResponse for TwoFactorError {
+    fn into_response(self) -> Response {
+        match self {
+            TwoFactorError::UnexpectedError(err) => {
+                tracing::error!("{:?}", err);
+
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Something went wrong".to_owned(),
+                )
+                    .into_response()
+            }
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use anyhow::Context;
+use axum::{
+    Form,
+    extract::State,
+    http::header::CACHE_CONTROL,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_messages::Messages;
+use serde::Deserialize;
+use std::fmt::Write;
+
+use crate::{
+    authorization::CurrentUser,
+    routes::TwoFactorError,
+    startup::AppState,
+    telemetry::spawn_blocking_with_tracing,
+    two_factor::{
+        RECOVERY_CODE_COUNT, disable_two_factor, enable_two_factor, generate_recovery_code,
+        generate_totp_secret, get_two_factor_status, hash_recovery_code, matching_step,
+        set_pending_secret, unix_time_now, verify_second_factor,
+    },
+};
+
+const ALREADY_ENABLED: &str =
+    "Two-factor authentication is already enabled, disable it before setting it up again.";
+
+#[derive(Deserialize)]
+pub struct CodeFormData {
+    code: String,
+}
+
+/// Stores a new secret that only takes effect once a code generated from it
+/// has been entered. An enabled second factor has to be disabled first, which
+/// takes a code from it, so a hijacked session cannot swap it out.
+#[tracing::instrument(name = "Start a two-factor enrolment", skip(messages, state, user), fields(user_id = %user.user_id))]
+pub async fn enrol_two_factor_handler(
+    messages: Messages,
+    user: CurrentUser,
+    State(state): State<AppState>,
+) -> Result<Redirect, TwoFactorError> {
+    let status = get_two_factor_status(&state.pg_pool, user.user_id)
+        .await
+        .context("Failed to load the two-factor status")?;
+    if status.enabled {
+        messages.error(ALREADY_ENABLED);
+        return Ok(Redirect::to("/admin/two-factor"));
+    }
+    let encrypted_secret = state.two_factor.encrypt_secret(&generate_totp_secret())?;
+    set_pending_secret(&state.pg_pool, user.user_id, &encrypted_secret)
+        .await
+        .context("Failed to store the pending secret")?;
+    Ok(Redirect::to("/admin/two-factor"))
+}
+
+/// The recovery codes are shown on the response itself, so they are never
+/// stored anywhere in plain text, not even in the session.
+#[tracing::instrument(name = "Confirm a two-factor enrolment", skip(messages, state, user, form), fields(user_id = %user.user_id))]
+pub async fn confirm_two_factor_handler(
+    messages: Messages,
+    user: CurrentUser,
+    State(state): State<AppState>,
+    Form(form): Form<CodeFormData>,
+) -> Result<Response, TwoFactorError> {
+    let status = get_two_factor_status(&state.pg_pool, user.user_id)
+        .await
+        .context("Failed to load the two-factor status")?;
+    if status.enabled {
+        messages.error(ALREADY_ENABLED);
+        return Ok(Redirect::to("/admin/two-factor").into_response());
+    }
+    let Some(pending_secret) = status.pending_secret else {
+        messages.error("Set up two-factor authentication first.");
+        return Ok(Redirect::to("/admin/two-factor").into_response());
+    };
+    let secret = state
+        .two_factor
+        .decrypt_secret(&pending_secret)
+        .context("Failed to decrypt the pending secret")?;
+    let totp = state.two_factor.totp(secret, &user.username);
+    let Some(step) = matching_step(&totp, form.code.trim(), unix_time_now()?) else {
+        messages.error("The code is not valid, please try again.");
+        return Ok(Redirect::to("/admin/two-factor").into_response());
+    };
+
+    let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| generate_recovery_code())
+        .collect();
+    let code_hashes = spawn_blocking_with_tracing({
+        let codes = codes.clone();
+        move || {
+            codes
+                .iter()
+                .map(|code| hash_recovery_code(code))
+                .collect::<Result<Vec<_>, _>>()
+        }
+    })
+    .await
+    .context("Failed to spawn blocking task.")?
+    .context("Failed to hash the recovery codes")?;
+
+    let mut transaction = state
+        .pg_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    enable_two_factor(&mut transaction, user.user_id, step, &code_hashes)
+        .await
+        .context("Failed to enable two-factor authentication")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the two-factor enrolment")?;
+
+    let mut codes_html = String::new();
+    for code in &codes {
+        writeln!(codes_html, "<li><code>{code}</code></li>").unwrap();
+    }
+    let page = Html(format!(
+        r#"<!DOCTYPE html>
+        <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8">
+            <title>Recovery codes</title>
+        </head>
+        <body>
+            <h1>Two-factor authentication is enabled</h1>
+            <p>Keep these recovery codes somewhere safe, each of them can be used once instead of a code. They are not shown again.</p>
+            <ul>
+                {codes_html}
+            </ul>
+            <p><a href="/admin/two-factor">Continue</a></p>
+        </body>
+        </html>"#
+    ));
+    Ok(([(CACHE_CONTROL, "no-store")], page).into_response())
+}
+
+#[tracing::instrument(name = "Disable two-factor authentication", skip(messages, state, user, form), fields(user_id = %user.user_id))]
+pub async fn disable_two_factor_handler(
+    messages: Messages,
+    user: CurrentUser,
+    State(state): State<AppState>,
+    Form(form): Form<CodeFormData>,
+) -> Result<Redirect, TwoFactorError> {
+    if !verify_second_factor(&state.pg_pool, &state.two_factor, user.user_id, &form.code).await? {
+        messages.error("The code is not valid, please try again.");
+        return Ok(Redirect::to("/admin/two-factor"));
+    }
+    disable_two_factor(&state.pg_pool, user.user_id)
+        .await
+        .context("Failed to disable two-factor authentication")?;
+    messages.info("Two-factor authentication is disabled.");
+    Ok(Redirect::to("/admin/two-factor"))
+}
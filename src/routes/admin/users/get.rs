@@ -0,0 +1,125 @@
+use anyhow::Context;
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse},
+};
+use axum_messages::Messages;
+use std::fmt::Write;
+
+use crate::{
+    csrf::CsrfToken, domain::UserRole, html::escape_html, routes::UserError, startup::AppState,
+    users::list_users,
+};
+
+pub async fn users_page(
+    messages: Messages,
+    csrf_token: CsrfToken,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, UserError> {
+    let csrf_field = csrf_token.form_field();
+    let mut msg_html = String::new();
+    for m in messages.into_iter() {
+        writeln!(msg_html, "<p><i>{m}</i></p>").unwrap();
+    }
+
+    let users = list_users(&state.pg_pool)
+        .await
+        .context("Failed to load the users")?;
+    let mut rows_html = String::new();
+    for user in users {
+        let status = match (user.is_active, user.has_password) {
+            (false, _) => "Disabled",
+            (true, false) => "Invited",
+            (true, true) => "Active",
+        };
+        let mut role_options = String::new();
+        for role in UserRole::ALL {
+            let selected = if role.as_str() == user.role {
+                " selected"
+            } else {
+                ""
+            };
+            write!(
+                role_options,
+                r#"<option value="{}"{selected}>{}</option>"#,
+                role.as_str(),
+                role.label()
+            )
+            .unwrap();
+        }
+        let (toggle_value, toggle_label) = if user.is_active {
+            ("false", "Disable")
+        } else {
+            ("true", "Enable")
+        };
+        writeln!(
+            rows_html,
+            r#"<tr>
+                <td>{}</td>
+                <td>{}</td>
+                <td>
+                    <form action="/admin/users/{user_id}/role" method="post">
+                        {csrf_field}
+                        <select name="role">{role_options}</select>
+                        <button type="submit">Change role</button>
+                    </form>
+                </td>
+                <td>{status}</td>
+                <td>{}</td>
+                <td>
+                    <form action="/admin/users/{user_id}/toggle" method="post">
+                        {csrf_field}
+                        <input hidden type="text" name="is_active" value="{toggle_value}">
+                        <button type="submit">{toggle_label}</button>
+                    </form>
+                </td>
+                <td>
+                    <form action="/admin/users/{user_id}/delete" method="post">
+                        {csrf_field}
+                        <button type="submit">Delete</button>
+                    </form>
+                </td>
+            </tr>"#,
+            escape_html(&user.username),
+            escape_html(user.email.as_deref().unwrap_or("")),
+            user.created_at.format("%Y-%m-%d %H:%M"),
+            user_id = user.user_id,
+        )
+        .unwrap();
+    }
+
+    Ok(Html(format!(
+        r#"<!DOCTYPE html>
+        <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8">
+            <title>Users</title>
+        </head>
+        <body>
+            {msg_html}
+            <form action="/admin/users" method="post">
+                {csrf_field}
+                <label>Username
+                    <input type="text" name="username">
+                </label>
+                <label>Email
+                    <input type="email" name="email">
+                </label>
+                <label>Role
+                    <select name="role">
+                        <option value="owner">Owner</option>
+                        <option value="editor" selected>Editor</option>
+                        <option value="viewer">Viewer</option>
+                    </select>
+                </label>
+                <button type="submit">Send an invitation</button>
+            </form>
+            <table>
+                <tr><th>Username</th><th>Email</th><th>Role</th><th>Status</th><th>Created</th><th></th><th></th></tr>
+                {rows_html}
+            </table>
+            <p><a href="/admin/dashboard">&lt;- Back</a></p>
+        </body>
+        </html>"#
+    )))
+}
@@ -0,0 +1,35 @@
+mod get;
+mod post;
+
+use axum::response::{IntoResponse, Response};
+pub use get::users_page;
+pub use post::{
+    change_role_handler, delete_user_handler, invite_user_handler, toggle_user_handler,
+};
+use reqwest::StatusCode;
+
+#[derive(thiserror::Error, Debug)]
+pub enum UserError {
+    #[error("The user does not exist.")]
+    NotFound,
+
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl IntoResponse for UserError {
+    fn into_response(self) -> Response {
+        match self {
+            UserError::NotFound => (StatusCode::NOT_FOUND, self.to_string()).into_response(),
+            UserError::UnexpectedError(err) => {
+                tracing::error!("{:?}", err);
+
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Something went wrong".to_owned(),
+                )
+                    .into_response()
+            }
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use anyhow::Context;
+use axum::{
+    Form,
+    extract::{Path, State},
+    response::Redirect,
+};
+use axum_messages::Messages;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    domain::{SubscriberEmail, UserRole},
+    html::escape_html,
+    routes::UserError,
+    startup::AppState,
+    suppressions::{SendOutcome, send_unless_suppressed},
+    users::{
+        UserChange, delete_user, generate_token, hash_token, insert_invitation,
+        insert_invited_user, invitation_url, set_user_active, set_user_role, validate_username,
+    },
+};
+
+#[derive(Deserialize)]
+pub struct InviteFormData {
+    username: String,
+    email: String,
+    role: String,
+}
+
+fn is_unique_violation(e: &sqlx::Error) -> bool {
+    matches!(e, sqlx::Error::Database(e) if e.is_unique_violation())
+}
+
+#[tracing::instrument(name = "Invite a user", skip_all, fields(username = %form.username))]
+pub async fn invite_user_handler(
+    messages: Messages,
+    State(state): State<AppState>,
+    Form(form): Form<InviteFormData>,
+) -> Result<Redirect, UserError> {
+    let (username, email, role) = match (
+        validate_username(&form.username),
+        SubscriberEmail::parse(form.email.trim().to_string()),
+        UserRole::parse(&form.role),
+    ) {
+        (Ok(username), Ok(email), Ok(role)) => (username, email, role),
+        (username, email, role) => {
+            [username.err(), email.err(), role.err()]
+                .into_iter()
+                .flatten()
+                .fold(messages, Messages::error);
+            return Ok(Redirect::to("/admin/users"));
+        }
+    };
+
+    let mut transaction = state
+        .pg_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let user_id = match insert_invited_user(&mut transaction, username, email.as_ref(), role).await
+    {
+        Ok(user_id) => user_id,
+        Err(e) if is_unique_violation(&e) => {
+            messages.error("A user with this username or email already exists.");
+            return Ok(Redirect::to("/admin/users"));
+        }
+        Err(e) => {
+            return Err(anyhow::Error::new(e)
+                .context("Failed to store the user")
+                .into());
+        }
+    };
+    let token = generate_token();
+    insert_invitation(&mut transaction, user_id, &hash_token(&token))
+        .await
+        .context("Failed to store the invitation")?;
+
+    // Sent before committing, a user nobody can log in as is of no use.
+    let url = invitation_url(&state.base_url.0, &token);
+    let outcome = send_unless_suppressed(
+        &state.pg_pool,
+        &state.email_client,
+        &email,
+        "You have been invited to manage the newsletter",
+        &format!(
+            r#"<p>Hi {},</p><p>You have been given access to the newsletter admin. <a href="{url}">Choose your password</a> to get started.</p>"#,
+            escape_html(username)
+        ),
+        &format!(
+            "Hi {username},\n\nYou have been given access to the newsletter admin. Visit {url} to choose your password."
+        ),
+        &[],
+    )
+    .await
+    .context("Failed to send the invitation")?;
+    if let SendOutcome::Suppressed = outcome {
+        messages.error("The email address is on the suppression list, no invitation was sent.");
+        return Ok(Redirect::to("/admin/users"));
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the new user")?;
+
+    messages.info(format!(
+        "An invitation has been sent to {}.",
+        email.as_ref()
+    ));
+    Ok(Redirect::to("/admin/users"))
+}
+
+#[derive(Deserialize)]
+pub struct ToggleUserFormData {
+    is_active: bool,
+}
+
+#[tracing::instrument(name = "Enable or disable a user", skip(messages, state, form))]
+pub async fn toggle_user_handler(
+    messages: Messages,
+    State(state): State<AppState>,
+    Path(user_id): Path<Uuid>,
+    Form(form): Form<ToggleUserFormData>,
+) -> Result<Redirect, UserError> {
+    let change = set_user_active(&state.pg_pool, user_id, form.is_active)
+        .await
+        .context("Failed to update the user")?;
+    match change {
+        UserChange::Done if form.is_active => messages.info("The user has been enabled."),
+        UserChange::Done => messages.info("The user has been disabled."),
+        UserChange::NotFound => return Err(UserError::NotFound),
+        UserChange::LastActiveOwner => messages.error("The last active owner cannot be disabled."),
+    };
+    Ok(Redirect::to("/admin/users"))
+}
+
+#[derive(Deserialize)]
+pub struct ChangeRoleFormData {
+    role: String,
+}
+
+#[tracing::instrument(name = "Change the role of a user", skip(messages, state, form))]
+pub async fn change_role_handler(
+    messages: Messages,
+    State(state): State<AppState>,
+    Path(user_id): Path<Uuid>,
+    Form(form): Form<ChangeRoleFormData>,
+) -> Result<Redirect, UserError> {
+    let role = match UserRole::parse(&form.role) {
+        Ok(role) => role,
+        Err(e) => {
+            messages.error(e);
+            return Ok(Redirect::to("/admin/users"));
+        }
+    };
+    let change = set_user_role(&state.pg_pool, user_id, role)
+        .await
+        .context("Failed to update the role")?;
+    match change {
+        UserChange::Done => messages.info(format!("The user is now a {}.", role.as_str())),
+        UserChange::NotFound => return Err(UserError::NotFound),
+        UserChange::LastActiveOwner => {
+            messages.error("The last active owner has to remain an owner.")
+        }
+    };
+    Ok(Redirect::to("/admin/users"))
+}
+
+#[tracing::instrument(name = "Delete a user", skip(messages, state))]
+pub async fn delete_user_handler(
+    messages: Messages,
+    State(state): State<AppState>,
+    Path(user_id): Path<Uuid>,
+) -> Result<Redirect, UserError> {
+    let change = delete_user(&state.pg_pool, user_id)
+        .await
+        .context("Failed to delete the user")?;
+    match change {
+        UserChange::Done => messages.info("The user has been deleted."),
+        UserChange::NotFound => return Err(UserError::NotFound),
+        UserChange::LastActiveOwner => messages.error("The last active owner cannot be deleted."),
+    };
+    Ok(Redirect::to("/admin/users"))
+}
@@ -0,0 +1,188 @@
+use anyhow::Context;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::{html::escape_html, startup::AppState};
+
+const ISSUES_PER_PAGE: i64 = 10;
+/// Far beyond anything we will publish, it keeps the offset arithmetic in range.
+const MAX_PAGE: i64 = 100_000;
+
+#[derive(Deserialize, Debug)]
+pub struct ArchiveParameters {
+    page: Option<i64>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ArchiveError {
+    #[error("The issue does not exist.")]
+    NotFound,
+    #[error("The page does not exist.")]
+    PageNotFound,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl IntoResponse for ArchiveError {
+    fn into_response(self) -> Response {
+        match self {
+            ArchiveError::NotFound | ArchiveError::PageNotFound => {
+                (StatusCode::NOT_FOUND, self.to_string()).into_response()
+            }
+            ArchiveError::UnexpectedError(err) => {
+                tracing::error!("{:?}", err);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Something went wrong".to_owned(),
+                )
+                    .into_response()
+            }
+        }
+    }
+}
+
+struct ArchiveEntry {
+    title: String,
+    slug: String,
+    published_at: DateTime<Utc>,
+}
+
+struct ArchivedIssue {
+    title: String,
+    html_content: String,
+}
+
+#[tracing::instrument(name = "Show the issue archive", skip(state))]
+pub async fn archive_page(
+    State(state): State<AppState>,
+    Query(parameters): Query<ArchiveParameters>,
+) -> Result<Html<String>, ArchiveError> {
+    let page = parameters.page.unwrap_or(1).max(1);
+    if page > MAX_PAGE {
+        return Err(ArchiveError::PageNotFound);
+    }
+    let mut issues = get_public_issues(&state.pg_pool, page)
+        .await
+        .context("Failed to load the archive")?;
+    let has_next_page = issues.len() as i64 > ISSUES_PER_PAGE;
+    issues.truncate(ISSUES_PER_PAGE as usize);
+
+    let mut issues_html = String::new();
+    for issue in &issues {
+        writeln!(
+            issues_html,
+            r#"<li><a href="/archive/{}">{}</a> <small>{}</small></li>"#,
+            issue.slug,
+            escape_html(&issue.title),
+            issue.published_at.format("%B %-d, %Y")
+        )
+        .unwrap();
+    }
+    if issues.is_empty() {
+        issues_html.push_str("<li>Nothing has been published yet.</li>");
+    }
+    let mut pagination_html = String::new();
+    if page > 1 {
+        write!(
+            pagination_html,
+            r#"<a href="/archive?page={}">&lt;- Newer issues</a> "#,
+            page - 1
+        )
+        .unwrap();
+    }
+    if has_next_page {
+        write!(
+            pagination_html,
+            r#"<a href="/archive?page={}">Older issues -&gt;</a>"#,
+            page + 1
+        )
+        .unwrap();
+    }
+
+    Ok(Html(format!(
+        r#"<!DOCTYPE html>
+        <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8">
+            <title>Newsletter archive</title>
+        </head>
+        <body>
+            <h1>Newsletter archive</h1>
+            <ul>
+                {issues_html}
+            </ul>
+            <p>{pagination_html}</p>
+        </body>
+        </html>"#
+    )))
+}
+
+#[tracing::instrument(name = "Show an archived issue", skip(state))]
+pub async fn archive_issue_page(
+    State(state): State<AppState>,
+    Path(slug): Path<String>,
+) -> Result<Html<String>, ArchiveError> {
+    let issue = get_public_issue(&state.pg_pool, &slug)
+        .await
+        .context("Failed to load the issue")?
+        .ok_or(ArchiveError::NotFound)?;
+    // Issues written as html fragments still need a document around them.
+    if issue.html_content.to_ascii_lowercase().contains("<html") {
+        return Ok(Html(issue.html_content));
+    }
+    Ok(Html(format!(
+        r#"<!DOCTYPE html>
+        <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8">
+            <title>{}</title>
+        </head>
+        <body>
+            {}
+        </body>
+        </html>"#,
+        escape_html(&issue.title),
+        issue.html_content
+    )))
+}
+
+/// Fetches one issue more than fits on the page to tell whether there is a next page.
+#[tracing::instrument(name = "Get public issues", skip(pool))]
+async fn get_public_issues(pool: &PgPool, page: i64) -> Result<Vec<ArchiveEntry>, sqlx::Error> {
+    sqlx::query_as!(
+        ArchiveEntry,
+        r#"
+        SELECT title, slug, published_at
+        FROM newsletter_issues
+        WHERE is_public
+        ORDER BY published_at DESC
+        LIMIT $1 OFFSET $2
+        "#,
+        ISSUES_PER_PAGE + 1,
+        (page - 1) * ISSUES_PER_PAGE
+    )
+    .fetch_all(pool)
+    .await
+}
+
+#[tracing::instrument(name = "Get public issue", skip(pool))]
+async fn get_public_issue(pool: &PgPool, slug: &str) -> Result<Option<ArchivedIssue>, sqlx::Error> {
+    sqlx::query_as!(
+        ArchivedIssue,
+        r#"
+        SELECT title, html_content
+        FROM newsletter_issues
+        WHERE slug = $1 AND is_public
+        "#,
+        slug
+    )
+    .fetch_optional(pool)
+    .await
+}
@@ -0,0 +1,165 @@
+use anyhow::Context;
+use atom_syndication::{ContentBuilder, EntryBuilder, FeedBuilder, LinkBuilder};
+use axum::{
+    extract::State,
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use rss::{ChannelBuilder, GuidBuilder, ItemBuilder};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{archive::archive_url, startup::AppState};
+
+const FEED_TITLE: &str = "Newsletter";
+const FEED_DESCRIPTION: &str = "Every public issue of the newsletter.";
+const FEED_LENGTH: i64 = 20;
+
+#[derive(thiserror::Error, Debug)]
+pub enum FeedError {
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl IntoResponse for FeedError {
+    fn into_response(self) -> Response {
+        let FeedError::UnexpectedError(err) = self;
+        tracing::error!("{:?}", err);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Something went wrong".to_owned(),
+        )
+            .into_response()
+    }
+}
+
+struct FeedIssue {
+    newsletter_issue_id: Uuid,
+    title: String,
+    slug: String,
+    html_content: String,
+    published_at: DateTime<Utc>,
+}
+
+/// The issue id never changes, unlike the title the archive url is derived from.
+fn guid(issue: &FeedIssue) -> String {
+    format!("urn:uuid:{}", issue.newsletter_issue_id)
+}
+
+#[tracing::instrument(name = "Render the RSS feed", skip(state))]
+pub async fn rss_feed(State(state): State<AppState>) -> Result<Response, FeedError> {
+    let issues = get_feed_issues(&state.pg_pool)
+        .await
+        .context("Failed to load the issues for the feed")?;
+    let base_url = &state.base_url.0;
+
+    let items: Vec<_> = issues
+        .iter()
+        .map(|issue| {
+            ItemBuilder::default()
+                .title(issue.title.clone())
+                .link(archive_url(base_url, &issue.slug))
+                .guid(
+                    GuidBuilder::default()
+                        .value(guid(issue))
+                        .permalink(false)
+                        .build(),
+                )
+                .pub_date(issue.published_at.to_rfc2822())
+                .description(issue.html_content.clone())
+                .build()
+        })
+        .collect();
+    let channel = ChannelBuilder::default()
+        .title(FEED_TITLE)
+        .link(format!("http://{base_url}/archive"))
+        .description(FEED_DESCRIPTION)
+        .last_build_date(issues.first().map(|i| i.published_at.to_rfc2822()))
+        .items(items)
+        .build();
+
+    Ok((
+        [(header::CONTENT_TYPE, "application/rss+xml; charset=utf-8")],
+        channel.to_string(),
+    )
+        .into_response())
+}
+
+#[tracing::instrument(name = "Render the Atom feed", skip(state))]
+pub async fn atom_feed(State(state): State<AppState>) -> Result<Response, FeedError> {
+    let issues = get_feed_issues(&state.pg_pool)
+        .await
+        .context("Failed to load the issues for the feed")?;
+    let base_url = &state.base_url.0;
+
+    let entries: Vec<_> = issues
+        .iter()
+        .map(|issue| {
+            EntryBuilder::default()
+                .id(guid(issue))
+                .title(issue.title.as_str())
+                .updated(issue.published_at)
+                .published(Some(issue.published_at.into()))
+                .link(
+                    LinkBuilder::default()
+                        .href(archive_url(base_url, &issue.slug))
+                        .rel("alternate")
+                        .build(),
+                )
+                .content(
+                    ContentBuilder::default()
+                        .content_type(Some("html".to_string()))
+                        .value(Some(issue.html_content.clone()))
+                        .build(),
+                )
+                .build()
+        })
+        .collect();
+    let updated = issues
+        .first()
+        .map(|i| i.published_at)
+        .unwrap_or(DateTime::UNIX_EPOCH);
+    let feed = FeedBuilder::default()
+        .id(format!("http://{base_url}/feed.atom"))
+        .title(FEED_TITLE)
+        .subtitle(Some(FEED_DESCRIPTION.into()))
+        .updated(updated)
+        .link(
+            LinkBuilder::default()
+                .href(format!("http://{base_url}/feed.atom"))
+                .rel("self")
+                .build(),
+        )
+        .link(
+            LinkBuilder::default()
+                .href(format!("http://{base_url}/archive"))
+                .rel("alternate")
+                .build(),
+        )
+        .entries(entries)
+        .build();
+
+    Ok((
+        [(header::CONTENT_TYPE, "application/atom+xml; charset=utf-8")],
+        feed.to_string(),
+    )
+        .into_response())
+}
+
+#[tracing::instrument(name = "Get feed issues", skip(pool))]
+async fn get_feed_issues(pool: &PgPool) -> Result<Vec<FeedIssue>, sqlx::Error> {
+    sqlx::query_as!(
+        FeedIssue,
+        r#"
+        SELECT newsletter_issue_id, title, slug, html_content, published_at
+        FROM newsletter_issues
+        WHERE is_public
+        ORDER BY published_at DESC
+        LIMIT $1
+        "#,
+        FEED_LENGTH
+    )
+    .fetch_all(pool)
+    .await
+}
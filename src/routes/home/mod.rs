@@ -1,6 +1,9 @@
 use axum::response::{Html, IntoResponse};
 use reqwest::StatusCode;
 
-pub async fn home() -> impl IntoResponse {
-    (StatusCode::OK, Html(include_str!("home.html")))
+use crate::csrf::CsrfToken;
+
+pub async fn home(csrf_token: CsrfToken) -> impl IntoResponse {
+    let page = include_str!("home.html").replace("{csrf_field}", &csrf_token.form_field());
+    (StatusCode::OK, Html(page))
 }
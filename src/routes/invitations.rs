@@ -0,0 +1,147 @@
+use anyhow::Context;
+use axum::{
+    Form,
+    extract::{Query, State},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_messages::Messages;
+use reqwest::StatusCode;
+use secrecy::{ExposeSecret, SecretString};
+use serde::Deserialize;
+use std::fmt::Write;
+
+use crate::{
+    authentication::{compute_password_hash, validate_password_length},
+    csrf::CsrfToken,
+    html::escape_html,
+    routes::error_chain_fmt,
+    startup::AppState,
+    telemetry::spawn_blocking_with_tracing,
+    users::{accept_invitation, get_invited_username, hash_token},
+};
+
+#[derive(thiserror::Error)]
+pub enum InvitationError {
+    #[error("This invitation is invalid or has expired.")]
+    InvalidToken,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for InvitationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for InvitationError {
+    fn into_response(self) -> Response {
+        match self {
+            InvitationError::InvalidToken => {
+                (StatusCode::UNAUTHORIZED, self.to_string()).into_response()
+            }
+            InvitationError::UnexpectedError(err) => {
+                tracing::error!("{:?}", err);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Something went wrong".to_owned(),
+                )
+                    .into_response()
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct InvitationParameters {
+    token: String,
+}
+
+#[tracing::instrument(name = "Show an invitation", skip_all)]
+pub async fn accept_invitation_form(
+    messages: Messages,
+    csrf_token: CsrfToken,
+    State(state): State<AppState>,
+    Query(parameters): Query<InvitationParameters>,
+) -> Result<Html<String>, InvitationError> {
+    let csrf_field = csrf_token.form_field();
+    let username = get_invited_username(&state.pg_pool, &hash_token(&parameters.token))
+        .await
+        .context("Failed to look up the invitation")?
+        .ok_or(InvitationError::InvalidToken)?;
+    let mut msg_html = String::new();
+    for m in messages.into_iter() {
+        writeln!(msg_html, "<p><i>{m}</i></p>").unwrap();
+    }
+
+    Ok(Html(format!(
+        r#"<!DOCTYPE html>
+        <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8">
+            <title>Choose your password</title>
+        </head>
+        <body>
+            {msg_html}
+            <p>Welcome {}! Choose a password to finish setting up your account.</p>
+            <form action="/invitations/accept" method="post">
+                {csrf_field}
+                <input hidden type="text" name="token" value="{}">
+                <label>Password
+                    <input type="password" name="password">
+                </label>
+                <br>
+                <label>Confirm the password
+                    <input type="password" name="password_check">
+                </label>
+                <br>
+                <button type="submit">Set password</button>
+            </form>
+        </body>
+        </html>"#,
+        escape_html(&username),
+        escape_html(&parameters.token)
+    )))
+}
+
+#[derive(Deserialize)]
+pub struct AcceptInvitationFormData {
+    token: String,
+    password: SecretString,
+    password_check: SecretString,
+}
+
+#[tracing::instrument(name = "Accept an invitation", skip_all)]
+pub async fn accept_invitation_handler(
+    messages: Messages,
+    State(state): State<AppState>,
+    Form(form): Form<AcceptInvitationFormData>,
+) -> Result<Redirect, InvitationError> {
+    let retry = format!(
+        "/invitations/accept?{}",
+        serde_urlencoded::to_string([("token", &form.token)])
+            .context("Failed to encode the token")?
+    );
+    if form.password.expose_secret() != form.password_check.expose_secret() {
+        messages.error("You entered two different passwords - the field values must match.");
+        return Ok(Redirect::to(&retry));
+    }
+    if let Err(e) = validate_password_length(&form.password) {
+        messages.error(e);
+        return Ok(Redirect::to(&retry));
+    }
+
+    let password = form.password;
+    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
+        .await
+        .context("Failed to spawn blocking task.")?
+        .context("Failed to hash the password")?;
+    let accepted = accept_invitation(&state.pg_pool, &hash_token(&form.token), password_hash)
+        .await
+        .context("Failed to accept the invitation")?;
+    if !accepted {
+        return Err(InvitationError::InvalidToken);
+    }
+    messages.info("Your password has been set, you can now log in.");
+    Ok(Redirect::to("/login"))
+}
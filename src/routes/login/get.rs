@@ -2,8 +2,11 @@ use axum::response::{Html, IntoResponse};
 use axum_messages::Messages;
 use std::fmt::Write;
 
+use crate::csrf::CsrfToken;
+
 #[axum::debug_handler]
-pub async fn login_form(messages: Messages) -> impl IntoResponse {
+pub async fn login_form(csrf_token: CsrfToken, messages: Messages) -> impl IntoResponse {
+    let csrf_field = csrf_token.form_field();
     let mut error_html = String::new();
     for m in messages.into_iter() {
         writeln!(error_html, "<p><i>{m}</i></p>").unwrap();
@@ -19,6 +22,7 @@ pub async fn login_form(messages: Messages) -> impl IntoResponse {
             <body>
                 {error_html}
                 <form action="/login" method="post">
+                    {csrf_field}
                     <label>Username
                         <input
                             type="text"
@@ -33,8 +37,13 @@ pub async fn login_form(messages: Messages) -> impl IntoResponse {
                             name="password"
                         >
                     </label>
+                    <label>
+                        <input type="checkbox" name="remember_me">
+                        Remember me
+                    </label>
                     <button type="submit">Login</button>
                 </form>
+                <p><a href="/password-reset">Forgot your password?</a></p>
             </body>
             </html>"#
     ))
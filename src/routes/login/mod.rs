@@ -1,5 +1,56 @@
 mod get;
 mod post;
+mod two_factor;
 
+use anyhow::Context;
+use axum::http::{HeaderMap, header::USER_AGENT};
+use axum_login::tower_sessions::{Expiry, Session};
 pub use get::login_form;
 pub use post::login;
+use std::net::IpAddr;
+use time::Duration;
+pub use two_factor::{login_two_factor_form, login_two_factor_handler};
+
+use crate::{
+    authentication::{AuthSession, SessionUser},
+    routes::session_state::insert_session_id,
+    startup::AppState,
+    users::insert_user_session,
+};
+
+/// Set instead of the logged in user while a user with two-factor authentication
+/// still has to enter their code.
+const PENDING_USER_ID_KEY: &str = "two_factor_user_id";
+/// Whether "remember me" was ticked, kept until the second factor is checked.
+const PENDING_REMEMBER_ME_KEY: &str = "two_factor_remember_me";
+
+/// Logs the session in once every factor has been checked, and records it
+/// so it shows up on the sessions page.
+async fn start_session(
+    state: &AppState,
+    auth_session: &mut AuthSession,
+    session: &Session,
+    user: &SessionUser,
+    address: IpAddr,
+    headers: &HeaderMap,
+    remember_me: bool,
+) -> Result<(), anyhow::Error> {
+    let user_agent = headers
+        .get(USER_AGENT)
+        .and_then(|value| value.to_str().ok());
+    let lifetime = state.session_settings.lifetime(remember_me);
+    let session_id =
+        insert_user_session(&state.pg_pool, user.user_id, address, user_agent, lifetime)
+            .await
+            .context("Failed to record the session")?;
+    // Cycles the session id as well.
+    auth_session.login(user).await.context("Failed to log in")?;
+    if remember_me {
+        session.set_expiry(Some(Expiry::OnInactivity(Duration::seconds(
+            lifetime.inactivity_timeout_seconds,
+        ))));
+    }
+    insert_session_id(session, session_id)
+        .await
+        .context("Failed to update the session")
+}
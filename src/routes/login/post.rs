@@ -1,59 +1,120 @@
+use anyhow::Context;
 use axum::{
     Form,
-    extract::State,
-    http::status::StatusCode,
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, status::StatusCode},
     response::{IntoResponse, Redirect},
 };
 use axum_login::tower_sessions::Session;
 use axum_messages::Messages;
 use secrecy::SecretString;
 use serde::Deserialize;
+use std::net::SocketAddr;
 
+use super::{PENDING_REMEMBER_ME_KEY, PENDING_USER_ID_KEY, start_session};
 use crate::{
-    authentication::{AuthError, Credentials, validate_credentials},
+    authentication::{AuthSession, Credentials},
+    login_throttle::{Block, wait_description},
     startup::AppState,
+    two_factor::is_two_factor_enabled,
 };
 
 #[derive(Deserialize)]
 pub struct FormData {
     username: String,
     password: SecretString,
+    /// Sent as "on" when the checkbox is ticked, left out otherwise.
+    remember_me: Option<String>,
 }
 
-#[tracing::instrument(skip(state, formdata, messages))]
+#[tracing::instrument(skip(state, formdata, messages, auth_session, session, headers))]
 pub async fn login(
     state: State<AppState>,
     messages: Messages,
+    mut auth_session: AuthSession,
     session: Session,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     formdata: Form<FormData>,
 ) -> Result<impl IntoResponse, LoginError> {
+    let throttle = &state.login_throttle;
+    // Blocked attempts are refused before the password is even looked at.
+    if let Some(seconds) = throttle
+        .retry_after(&formdata.username, address.ip())
+        .await?
+    {
+        messages.error(format!(
+            "Too many failed login attempts. Please try again in {}.",
+            wait_description(seconds)
+        ));
+        return Ok(Redirect::to("/login"));
+    }
+
+    let remember_me = formdata.remember_me.is_some();
     let credentials = Credentials {
         username: formdata.username.clone(),
         password: formdata.password.clone(),
     };
 
     tracing::Span::current().record("username", tracing::field::display(&credentials.username));
-    match validate_credentials(credentials, &state.pg_pool).await {
-        Ok(user_id) => {
-            tracing::Span::current().record("user_id", tracing::field::display(&user_id));
-            session
-                .cycle_id()
+    let user = auth_session
+        .authenticate(credentials)
+        .await
+        .context("Failed to check the credentials")?;
+    match user {
+        Some(user) => {
+            tracing::Span::current().record("user_id", tracing::field::display(&user.user_id));
+            let has_two_factor = is_two_factor_enabled(&state.pg_pool, user.user_id)
                 .await
                 .map_err(|e| LoginError::UnexpectedError(e.into()))?;
-            session.insert("user_id", user_id).await.map_err(|e| {
-                messages.error(e.to_string());
-                LoginError::UnexpectedError(e.into())
-            })?;
+            if has_two_factor {
+                // The session only counts as logged in once the code has been checked.
+                session
+                    .cycle_id()
+                    .await
+                    .map_err(|e| LoginError::UnexpectedError(e.into()))?;
+                session
+                    .insert(PENDING_USER_ID_KEY, user.user_id)
+                    .await
+                    .map_err(|e| LoginError::UnexpectedError(e.into()))?;
+                session
+                    .insert(PENDING_REMEMBER_ME_KEY, remember_me)
+                    .await
+                    .map_err(|e| LoginError::UnexpectedError(e.into()))?;
+                // The failures are only cleared once the second factor is right too.
+                return Ok(Redirect::to("/login/two-factor"));
+            }
+            throttle.record_success(&formdata.username).await?;
+            start_session(
+                &state,
+                &mut auth_session,
+                &session,
+                &user,
+                address.ip(),
+                &headers,
+                remember_me,
+            )
+            .await?;
 
             Ok(Redirect::to("/admin/dashboard"))
         }
 
-        Err(e) => {
-            let e = match e {
-                AuthError::InvalidCredentials(_) => LoginError::AuthError(e.into()),
-                AuthError::UnexpectedError(_) => LoginError::UnexpectedError(e.into()),
+        None => {
+            let messages = messages.error("Authentication failed");
+            match throttle
+                .record_failure(&formdata.username, address.ip())
+                .await?
+            {
+                Some(Block::Lockout(seconds)) => messages.error(format!(
+                    "Logging in has been locked because of too many failed attempts. Please try again in {}.",
+                    wait_description(seconds)
+                )),
+                Some(Block::Delay(seconds)) => messages.error(format!(
+                    "Please wait {} before trying again.",
+                    wait_description(seconds)
+                )),
+                None => messages,
             };
-            messages.error(e.to_string());
             Ok(Redirect::to("/login"))
         }
     }
@@ -61,26 +122,19 @@ pub async fn login(
 
 #[derive(thiserror::Error, Debug)]
 pub enum LoginError {
-    #[error("Authentication failed")]
-    AuthError(#[source] anyhow::Error),
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
 
 impl IntoResponse for LoginError {
     fn into_response(self) -> axum::response::Response {
-        let (status, message) = match self {
-            LoginError::AuthError(rejection) => (StatusCode::UNAUTHORIZED, rejection.to_string()),
-            LoginError::UnexpectedError(err) => {
-                tracing::error!("{:?}", err);
-
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "Something went wrong".to_owned(),
-                )
-            }
-        };
+        let LoginError::UnexpectedError(err) = self;
+        tracing::error!("{:?}", err);
 
-        (status, message).into_response()
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Something went wrong".to_owned(),
+        )
+            .into_response()
     }
 }
@@ -0,0 +1,196 @@
+use anyhow::Context;
+use axum::{
+    Form,
+    extract::{ConnectInfo, State},
+    http::HeaderMap,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_login::AuthnBackend;
+use axum_login::tower_sessions::Session;
+use axum_messages::Messages;
+use serde::Deserialize;
+use std::{fmt::Write, net::SocketAddr};
+use uuid::Uuid;
+
+use super::{PENDING_REMEMBER_ME_KEY, PENDING_USER_ID_KEY, post::LoginError, start_session};
+use crate::{
+    authentication::AuthSession,
+    csrf::CsrfToken,
+    login_throttle::{Block, wait_description},
+    startup::AppState,
+    two_factor::verify_second_factor,
+};
+
+const FAILED_ATTEMPTS_KEY: &str = "two_factor_failed_attempts";
+/// After this many wrong codes the password has to be entered again.
+const MAX_FAILED_ATTEMPTS: u32 = 5;
+
+pub async fn login_two_factor_form(
+    messages: Messages,
+    csrf_token: CsrfToken,
+    session: Session,
+) -> Result<Response, LoginError> {
+    let csrf_field = csrf_token.form_field();
+    let pending_user_id: Option<Uuid> = session
+        .get(PENDING_USER_ID_KEY)
+        .await
+        .context("Failed to read the session")?;
+    if pending_user_id.is_none() {
+        return Ok(Redirect::to("/login").into_response());
+    }
+
+    let mut error_html = String::new();
+    for m in messages.into_iter() {
+        writeln!(error_html, "<p><i>{m}</i></p>").unwrap();
+    }
+
+    Ok(Html(format!(
+        r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Login</title>
+            </head>
+            <body>
+                {error_html}
+                <form action="/login/two-factor" method="post">
+                    {csrf_field}
+                    <label>Code from your authenticator app or a recovery code
+                        <input
+                            type="text"
+                            name="code"
+                            autocomplete="one-time-code"
+                        >
+                    </label>
+                    <button type="submit">Verify</button>
+                </form>
+            </body>
+            </html>"#
+    ))
+    .into_response())
+}
+
+#[derive(Deserialize)]
+pub struct TwoFactorFormData {
+    code: String,
+}
+
+/// Wrong codes count as failed logins of the username and the address, so
+/// logging in again with a stolen password does not buy more guesses.
+#[tracing::instrument(
+    skip(state, messages, auth_session, session, headers, form),
+    fields(user_id)
+)]
+pub async fn login_two_factor_handler(
+    State(state): State<AppState>,
+    messages: Messages,
+    mut auth_session: AuthSession,
+    session: Session,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Form(form): Form<TwoFactorFormData>,
+) -> Result<Redirect, LoginError> {
+    let Some(user_id) = session
+        .get::<Uuid>(PENDING_USER_ID_KEY)
+        .await
+        .context("Failed to read the session")?
+    else {
+        return Ok(Redirect::to("/login"));
+    };
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+    // The user may have been disabled while they were looking for the code.
+    let Some(user) = auth_session
+        .backend
+        .get_user(&user_id)
+        .await
+        .context("Failed to load the user")?
+    else {
+        forget_pending_login(&session).await?;
+        return Ok(Redirect::to("/login"));
+    };
+
+    let throttle = &state.login_throttle;
+    if let Some(seconds) = throttle.retry_after(&user.username, address.ip()).await? {
+        messages.error(format!(
+            "Too many failed login attempts. Please try again in {}.",
+            wait_description(seconds)
+        ));
+        return Ok(Redirect::to("/login/two-factor"));
+    }
+
+    if verify_second_factor(&state.pg_pool, &state.two_factor, user_id, &form.code).await? {
+        let remember_me = session
+            .get::<bool>(PENDING_REMEMBER_ME_KEY)
+            .await
+            .context("Failed to read the session")?
+            .unwrap_or(false);
+        forget_pending_login(&session).await?;
+        throttle.record_success(&user.username).await?;
+        start_session(
+            &state,
+            &mut auth_session,
+            &session,
+            &user,
+            address.ip(),
+            &headers,
+            remember_me,
+        )
+        .await?;
+        return Ok(Redirect::to("/admin/dashboard"));
+    }
+
+    let messages = messages.error("The code is not valid, please try again.");
+    let messages = match throttle
+        .record_failure(&user.username, address.ip())
+        .await?
+    {
+        Some(Block::Lockout(seconds)) => {
+            forget_pending_login(&session).await?;
+            messages.error(format!(
+                "Logging in has been locked because of too many failed attempts. Please try again in {}.",
+                wait_description(seconds)
+            ));
+            return Ok(Redirect::to("/login"));
+        }
+        Some(Block::Delay(seconds)) => messages.error(format!(
+            "Please wait {} before trying again.",
+            wait_description(seconds)
+        )),
+        None => messages,
+    };
+
+    let failed_attempts = session
+        .get::<u32>(FAILED_ATTEMPTS_KEY)
+        .await
+        .context("Failed to read the session")?
+        .unwrap_or(0)
+        + 1;
+    if failed_attempts >= MAX_FAILED_ATTEMPTS {
+        tracing::warn!("Too many invalid two-factor codes, starting the login over.");
+        forget_pending_login(&session).await?;
+        messages.error("Too many invalid codes, please log in again.");
+        return Ok(Redirect::to("/login"));
+    }
+    session
+        .insert(FAILED_ATTEMPTS_KEY, failed_attempts)
+        .await
+        .context("Failed to update the session")?;
+    Ok(Redirect::to("/login/two-factor"))
+}
+
+/// Drops everything the password step left in the session.
+async fn forget_pending_login(session: &Session) -> Result<(), anyhow::Error> {
+    session
+        .remove::<Uuid>(PENDING_USER_ID_KEY)
+        .await
+        .context("Failed to update the session")?;
+    session
+        .remove::<bool>(PENDING_REMEMBER_ME_KEY)
+        .await
+        .context("Failed to update the session")?;
+    session
+        .remove::<u32>(FAILED_ATTEMPTS_KEY)
+        .await
+        .context("Failed to update the session")?;
+    Ok(())
+}
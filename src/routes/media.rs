@@ -0,0 +1,79 @@
+use anyhow::Context;
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use uuid::Uuid;
+
+use crate::{media::get_media, startup::AppState};
+
+/// Stored files never change, so they can be cached for as long as clients like.
+const CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+#[derive(thiserror::Error, Debug)]
+pub enum MediaError {
+    #[error("The file does not exist.")]
+    NotFound,
+
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl IntoResponse for MediaError {
+    fn into_response(self) -> Response {
+        match self {
+            MediaError::NotFound => (StatusCode::NOT_FOUND, self.to_string()).into_response(),
+            MediaError::UnexpectedError(err) => {
+                tracing::error!("{:?}", err);
+
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Something went wrong".to_owned(),
+                )
+                    .into_response()
+            }
+        }
+    }
+}
+
+#[tracing::instrument(name = "Serve an uploaded file", skip(state, headers))]
+pub async fn media_handler(
+    State(state): State<AppState>,
+    Path(media_id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Result<Response, MediaError> {
+    let item = get_media(&state.pg_pool, media_id)
+        .await
+        .context("Failed to load the media details")?
+        .ok_or(MediaError::NotFound)?;
+    let etag = format!("\"{}\"", item.media_id);
+    if headers
+        .get(header::IF_NONE_MATCH)
+        .is_some_and(|v| v.as_bytes() == etag.as_bytes())
+    {
+        return Ok((
+            StatusCode::NOT_MODIFIED,
+            [
+                (header::ETAG, etag),
+                (header::CACHE_CONTROL, CACHE_CONTROL.into()),
+            ],
+        )
+            .into_response());
+    }
+
+    let Some(content) = state.media.get(&item.media_id.to_string()).await? else {
+        tracing::warn!("The file is missing from the media storage.");
+        return Err(MediaError::NotFound);
+    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, item.content_type),
+            (header::CACHE_CONTROL, CACHE_CONTROL.into()),
+            (header::ETAG, etag),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff".into()),
+        ],
+        content,
+    )
+        .into_response())
+}
@@ -1,14 +1,32 @@
 mod admin;
+mod archive;
+mod feeds;
 mod health_check;
 mod home;
+mod invitations;
 mod login;
-mod session_state;
+mod media;
+mod password_reset;
+mod preferences;
+pub(crate) mod session_state;
 mod subscriptions;
 mod subscriptions_confirm;
+mod tracking;
+mod unsubscribe;
+mod webhooks;
 
 pub use admin::*;
+pub use archive::*;
+pub use feeds::*;
 pub use health_check::*;
 pub use home::*;
+pub use invitations::*;
 pub use login::*;
+pub use media::*;
+pub use password_reset::*;
+pub use preferences::*;
 pub use subscriptions::*;
 pub use subscriptions_confirm::*;
+pub use tracking::*;
+pub use unsubscribe::*;
+pub use webhooks::*;
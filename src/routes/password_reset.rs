@@ -0,0 +1,257 @@
+use anyhow::Context;
+use axum::{
+    Form,
+    extract::{ConnectInfo, Query, State},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_messages::Messages;
+use reqwest::StatusCode;
+use secrecy::{ExposeSecret, SecretString};
+use serde::Deserialize;
+use std::{fmt::Write, net::SocketAddr};
+use tracing::Instrument;
+
+use crate::{
+    authentication::{compute_password_hash, validate_password_length},
+    csrf::CsrfToken,
+    domain::SubscriberEmail,
+    html::escape_html,
+    login_throttle::wait_description,
+    routes::error_chain_fmt,
+    startup::AppState,
+    suppressions::send_unless_suppressed,
+    telemetry::spawn_blocking_with_tracing,
+    users::{
+        generate_token, get_reset_recipient, hash_token, insert_password_reset,
+        is_valid_password_reset, password_reset_url, reset_password, revoke_other_sessions,
+    },
+};
+
+#[derive(thiserror::Error)]
+pub enum PasswordResetError {
+    #[error("This password reset link is invalid or has expired.")]
+    InvalidToken,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for PasswordResetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for PasswordResetError {
+    fn into_response(self) -> Response {
+        match self {
+            PasswordResetError::InvalidToken => {
+                (StatusCode::UNAUTHORIZED, self.to_string()).into_response()
+            }
+            PasswordResetError::UnexpectedError(err) => {
+                tracing::error!("{:?}", err);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Something went wrong".to_owned(),
+                )
+                    .into_response()
+            }
+        }
+    }
+}
+
+pub async fn forgot_password_form(csrf_token: CsrfToken, messages: Messages) -> Html<String> {
+    let csrf_field = csrf_token.form_field();
+    let mut msg_html = String::new();
+    for m in messages.into_iter() {
+        writeln!(msg_html, "<p><i>{m}</i></p>").unwrap();
+    }
+
+    Html(format!(
+        r#"<!DOCTYPE html>
+        <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8">
+            <title>Forgot your password?</title>
+        </head>
+        <body>
+            {msg_html}
+            <p>Enter your username and we will email you a link to choose a new password.</p>
+            <form action="/password-reset" method="post">
+                {csrf_field}
+                <label>Username
+                    <input type="text" name="username">
+                </label>
+                <button type="submit">Send reset link</button>
+            </form>
+            <p><a href="/login">&lt;- Back to the login</a></p>
+        </body>
+        </html>"#
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct ForgotPasswordFormData {
+    username: String,
+}
+
+/// Answers the same way whether or not the username exists. The email is sent
+/// in the background, so the response time does not give it away either.
+/// Requests are throttled per username and per address like failed logins,
+/// so nobody can flood an inbox with reset links.
+#[tracing::instrument(name = "Request a password reset", skip_all)]
+pub async fn request_password_reset_handler(
+    messages: Messages,
+    State(state): State<AppState>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    Form(form): Form<ForgotPasswordFormData>,
+) -> Result<Redirect, PasswordResetError> {
+    let username = form.username.trim().to_string();
+    let throttle = &state.password_reset_throttle;
+    if let Some(seconds) = throttle.retry_after(&username, address.ip()).await? {
+        messages.error(format!(
+            "Too many password reset requests. Please try again in {}.",
+            wait_description(seconds)
+        ));
+        return Ok(Redirect::to("/password-reset"));
+    }
+    throttle.record_failure(&username, address.ip()).await?;
+    tokio::spawn(
+        async move {
+            if let Err(e) = send_password_reset(&state, &username).await {
+                tracing::error!(error.cause_chain = ?e, "Failed to send a password reset.");
+            }
+        }
+        .in_current_span(),
+    );
+    messages.info(
+        "If the username belongs to an account with an email address, a link to reset the password has been sent to it.",
+    );
+    Ok(Redirect::to("/login"))
+}
+
+async fn send_password_reset(state: &AppState, username: &str) -> Result<(), anyhow::Error> {
+    let Some(recipient) = get_reset_recipient(&state.pg_pool, username)
+        .await
+        .context("Failed to look up the user")?
+    else {
+        tracing::info!("No password reset sent, there is no matching active user with an email.");
+        return Ok(());
+    };
+    let email = SubscriberEmail::parse(recipient.email).map_err(anyhow::Error::msg)?;
+    let token = generate_token();
+    insert_password_reset(&state.pg_pool, recipient.user_id, &hash_token(&token))
+        .await
+        .context("Failed to store the password reset")?;
+
+    let url = password_reset_url(&state.base_url.0, &token);
+    let shown_username = escape_html(username);
+    send_unless_suppressed(
+        &state.pg_pool,
+        &state.email_client,
+        &email,
+        "Reset your newsletter admin password",
+        &format!(
+            r#"<p>Hi {shown_username},</p><p>Somebody asked to reset your password. <a href="{url}">Choose a new password</a> within the next hour, or ignore this email to keep your current one.</p>"#
+        ),
+        &format!(
+            "Hi {username},\n\nSomebody asked to reset your password. Visit {url} within the next hour to choose a new one, or ignore this email to keep your current one."
+        ),
+        &[],
+    )
+    .await
+    .context("Failed to send the password reset")?;
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordParameters {
+    token: String,
+}
+
+#[tracing::instrument(name = "Show a password reset", skip_all)]
+pub async fn reset_password_form(
+    messages: Messages,
+    csrf_token: CsrfToken,
+    State(state): State<AppState>,
+    Query(parameters): Query<ResetPasswordParameters>,
+) -> Result<Html<String>, PasswordResetError> {
+    let csrf_field = csrf_token.form_field();
+    let valid = is_valid_password_reset(&state.pg_pool, &hash_token(&parameters.token))
+        .await
+        .context("Failed to look up the password reset")?;
+    if !valid {
+        return Err(PasswordResetError::InvalidToken);
+    }
+    let mut msg_html = String::new();
+    for m in messages.into_iter() {
+        writeln!(msg_html, "<p><i>{m}</i></p>").unwrap();
+    }
+
+    Ok(Html(format!(
+        r#"<!DOCTYPE html>
+        <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8">
+            <title>Choose a new password</title>
+        </head>
+        <body>
+            {msg_html}
+            <form action="/password-reset/confirm" method="post">
+                {csrf_field}
+                <input hidden type="text" name="token" value="{}">
+                <label>New password
+                    <input type="password" name="new_password">
+                </label>
+                <br>
+                <label>Confirm new password
+                    <input type="password" name="new_password_check">
+                </label>
+                <br>
+                <button type="submit">Change password</button>
+            </form>
+        </body>
+        </html>"#,
+        escape_html(&parameters.token)
+    )))
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordFormData {
+    token: String,
+    new_password: SecretString,
+    new_password_check: SecretString,
+}
+
+#[tracing::instrument(name = "Reset a password", skip_all)]
+pub async fn reset_password_handler(
+    messages: Messages,
+    State(state): State<AppState>,
+    Form(form): Form<ResetPasswordFormData>,
+) -> Result<Redirect, PasswordResetError> {
+    let query = serde_urlencoded::to_string([("token", &form.token)])
+        .context("Failed to encode the token")?;
+    let retry = format!("/password-reset/confirm?{query}");
+    if form.new_password.expose_secret() != form.new_password_check.expose_secret() {
+        messages.error("You entered two different new passwords - the field values must match.");
+        return Ok(Redirect::to(&retry));
+    }
+    if let Err(e) = validate_password_length(&form.new_password) {
+        messages.error(e);
+        return Ok(Redirect::to(&retry));
+    }
+
+    let password = form.new_password;
+    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
+        .await
+        .context("Failed to spawn blocking task.")?
+        .context("Failed to hash the password")?;
+    let user_id = reset_password(&state.pg_pool, &hash_token(&form.token), password_hash)
+        .await
+        .context("Failed to use the password reset")?
+        .ok_or(PasswordResetError::InvalidToken)?;
+    revoke_other_sessions(&state.pg_pool, user_id, None)
+        .await
+        .context("Failed to log out the existing sessions")?;
+    messages.info("Your password has been changed, you can now log in.");
+    Ok(Redirect::to("/login"))
+}
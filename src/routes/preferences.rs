@@ -0,0 +1,192 @@
+use axum::{
+    Form,
+    extract::{Query, State},
+    response::{Html, IntoResponse, Response},
+};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    csrf::CsrfToken,
+    domain::DeliveryFrequency,
+    html::escape_html,
+    startup::AppState,
+    timezones::{DEFAULT_TIMEZONE, is_known_timezone, list_timezones},
+    unsubscribe::get_subscriber_id_from_unsubscribe_token,
+};
+
+/// The preference links carry the unsubscribe token.
+#[derive(Deserialize, Debug)]
+pub struct PreferencesParameters {
+    token: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PreferencesFormData {
+    token: String,
+    delivery_frequency: String,
+    /// Missing leaves the time zone as it is, an empty value clears it.
+    timezone: Option<String>,
+}
+
+struct Preferences {
+    delivery_frequency: String,
+    timezone: Option<String>,
+}
+
+#[tracing::instrument(name = "Show the email preferences", skip(state, csrf_token))]
+pub async fn preferences_form(
+    csrf_token: CsrfToken,
+    State(state): State<AppState>,
+    Query(parameters): Query<PreferencesParameters>,
+) -> Response {
+    let csrf_field = csrf_token.form_field();
+    let id = match get_subscriber_id_from_unsubscribe_token(&state.pg_pool, &parameters.token).await
+    {
+        Ok(id) => id,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let Some(subscriber_id) = id else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    let (preferences, timezones) = match tokio::try_join!(
+        get_preferences(&state.pg_pool, subscriber_id),
+        list_timezones(&state.pg_pool)
+    ) {
+        Ok(results) => results,
+        Err(e) => {
+            tracing::error!("{:?}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let mut options = String::new();
+    for frequency in DeliveryFrequency::ALL {
+        let checked = if frequency.as_str() == preferences.delivery_frequency {
+            " checked"
+        } else {
+            ""
+        };
+        options.push_str(&format!(
+            r#"<label><input type="radio" name="delivery_frequency" value="{}"{checked}> {}</label><br>"#,
+            frequency.as_str(),
+            frequency.description()
+        ));
+    }
+    let mut timezone_options = format!(r#"<option value="">Not set ({DEFAULT_TIMEZONE})</option>"#);
+    for timezone in timezones {
+        let selected = if preferences.timezone.as_deref() == Some(timezone.as_str()) {
+            " selected"
+        } else {
+            ""
+        };
+        timezone_options.push_str(&format!(
+            r#"<option value="{0}"{selected}>{0}</option>"#,
+            escape_html(&timezone)
+        ));
+    }
+    Html(format!(
+        r#"<!DOCTYPE html>
+        <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8">
+            <title>Email preferences</title>
+        </head>
+        <body>
+            <form action="/subscriptions/preferences" method="post">
+                {csrf_field}
+                <input hidden type="text" name="token" value="{}">
+                <p>How often would you like to get our emails?</p>
+                {options}
+                <p>
+                    <label>Your time zone, some issues are delivered at a set local time:
+                        <select name="timezone">{timezone_options}</select>
+                    </label>
+                </p>
+                <button type="submit">Save preferences</button>
+            </form>
+        </body>
+        </html>"#,
+        escape_html(&parameters.token)
+    ))
+    .into_response()
+}
+
+#[tracing::instrument(name = "Update the email preferences", skip(state, form))]
+pub async fn preferences_handler(
+    State(state): State<AppState>,
+    Form(form): Form<PreferencesFormData>,
+) -> Response {
+    let Ok(frequency) = DeliveryFrequency::parse(&form.delivery_frequency) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    let id = match get_subscriber_id_from_unsubscribe_token(&state.pg_pool, &form.token).await {
+        Ok(id) => id,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let Some(subscriber_id) = id else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    let timezone = form.timezone.as_deref().map(str::trim);
+    if let Some(timezone) = timezone.filter(|t| !t.is_empty()) {
+        match is_known_timezone(&state.pg_pool, timezone).await {
+            Ok(true) => {}
+            Ok(false) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    format!("{timezone} is not a known time zone."),
+                )
+                    .into_response();
+            }
+            Err(e) => {
+                tracing::error!("{:?}", e);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        }
+    }
+    if let Err(e) = set_preferences(&state.pg_pool, subscriber_id, frequency, timezone).await {
+        tracing::error!("{:?}", e);
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    Html(format!(
+        "<p>Your preferences have been saved: {}.</p>",
+        frequency.description().to_lowercase()
+    ))
+    .into_response()
+}
+
+async fn get_preferences(pool: &PgPool, subscriber_id: Uuid) -> Result<Preferences, sqlx::Error> {
+    sqlx::query_as!(
+        Preferences,
+        r#"SELECT delivery_frequency, timezone FROM subscriptions WHERE id = $1"#,
+        subscriber_id
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// A `None` time zone is left untouched, an empty one is cleared.
+async fn set_preferences(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    frequency: DeliveryFrequency,
+    timezone: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET
+            delivery_frequency = $1,
+            timezone = CASE WHEN $3::text IS NULL THEN timezone ELSE NULLIF($3, '') END
+        WHERE id = $2
+        "#,
+        frequency.as_str(),
+        subscriber_id,
+        timezone
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
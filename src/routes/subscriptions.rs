@@ -8,19 +8,23 @@ use chrono::Utc;
 use rand::{Rng, distr::Alphanumeric};
 use reqwest::StatusCode;
 use serde::Deserialize;
-use sqlx::{Executor, Postgres, Transaction};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::{
     domain::{NewSubscriber, SubscriberEmail, SubscriberName},
     email_client::EmailClient,
     startup::AppState,
+    suppressions::send_unless_suppressed,
+    timezones::is_known_timezone,
 };
 
 #[derive(Deserialize, Debug)]
 pub struct FormData {
     email: String,
     name: String,
+    /// Usually filled in by the browser, so a bad value does not fail the signup.
+    timezone: Option<String>,
 }
 impl TryFrom<FormData> for NewSubscriber {
     type Error = String;
@@ -43,8 +47,23 @@ impl TryFrom<FormData> for NewSubscriber {
 
 pub async fn subscribe_handler(
     State(state): State<AppState>,
-    Form(form): Form<FormData>,
+    Form(mut form): Form<FormData>,
 ) -> Result<(), SubscribeError> {
+    let timezone = match form.timezone.take().filter(|t| !t.trim().is_empty()) {
+        Some(timezone) => {
+            let timezone = timezone.trim().to_string();
+            if is_known_timezone(&state.pg_pool, &timezone)
+                .await
+                .context("Failed to check the time zone")?
+            {
+                Some(timezone)
+            } else {
+                tracing::warn!(timezone, "Ignoring an unknown time zone.");
+                None
+            }
+        }
+        None => None,
+    };
     let new_subscriber = form.try_into().map_err(SubscribeError::ValidationError)?;
 
     let mut transaction = state
@@ -53,7 +72,7 @@ pub async fn subscribe_handler(
         .await
         .context("Failed to acquire a Postgres connection from the pool")?;
 
-    let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber)
+    let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber, timezone.as_deref())
         .await
         .context("Failed to insert subscriber in the database")?;
     let subscription_token = generate_subscription_token();
@@ -67,6 +86,7 @@ pub async fn subscribe_handler(
         .await
         .context("Failed to commit SQL transaction to store a new subscriber")?;
     send_confirmation_email(
+        &state.pg_pool,
         &state.email_client,
         new_subscriber,
         state.base_url.0.as_str(),
@@ -85,18 +105,23 @@ pub async fn subscribe_handler(
 pub async fn insert_subscriber(
     transaction: &mut Transaction<'_, Postgres>,
     new_subscriber: &NewSubscriber,
+    timezone: Option<&str>,
 ) -> Result<Uuid, sqlx::Error> {
     let subscriber_id = Uuid::new_v4();
     let query = sqlx::query!(
         r#"
-        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
-        VALUES ($1, $2, $3, $4, $5)
+        INSERT INTO subscriptions (
+            id, email, name, subscribed_at, status, timezone, unsubscribe_token
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
         "#,
         subscriber_id,
         new_subscriber.email.as_ref(),
         new_subscriber.name.as_ref(),
         Utc::now(),
-        "pending_confirmation"
+        "pending_confirmation",
+        timezone,
+        generate_subscription_token()
     );
     transaction.execute(query).await?;
     Ok(subscriber_id)
@@ -104,14 +129,15 @@ pub async fn insert_subscriber(
 
 #[tracing::instrument(
     name = "Send a confirmation email to a new subscriber",
-    skip(email_client, new_subscriber, base_url, subscription_token)
+    skip(pool, email_client, new_subscriber, base_url, subscription_token)
 )]
 pub async fn send_confirmation_email(
+    pool: &PgPool,
     email_client: &EmailClient,
     new_subscriber: NewSubscriber,
     base_url: &str,
     subscription_token: &str,
-) -> Result<(), reqwest::Error> {
+) -> Result<(), anyhow::Error> {
     let confirmation_link =
         format!("http://{base_url}/subscriptions/confirm?subscription_token={subscription_token}");
 
@@ -125,9 +151,17 @@ pub async fn send_confirmation_email(
                 Click <a href=\"{confirmation_link}\" here </a> to confirm your subscription."
     );
 
-    email_client
-        .send_email(&new_subscriber.email, "Welcome!", html_body, plain_body)
-        .await
+    send_unless_suppressed(
+        pool,
+        email_client,
+        &new_subscriber.email,
+        "Welcome!",
+        html_body,
+        plain_body,
+        &[],
+    )
+    .await?;
+    Ok(())
 }
 
 fn generate_subscription_token() -> String {
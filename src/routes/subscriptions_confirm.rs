@@ -7,7 +7,7 @@ use serde::Deserialize;
 use sqlx::PgPool;
 use uuid::Uuid;
 
-use crate::startup::AppState;
+use crate::{automations::schedule_automation_steps, startup::AppState};
 
 #[derive(Deserialize, Debug)]
 pub struct Parameters {
@@ -39,18 +39,30 @@ pub async fn subscriptions_confirm_handler(
     }
 }
 
+/// Only pending subscribers are confirmed and enrolled in the active automations.
+/// Following the confirmation link again changes nothing, so it can not undo an
+/// unsubscribe or a bounce either.
 #[tracing::instrument(name = "Mark subscriber as confirmed", skip(pool, subscriber_id))]
 pub async fn confirm_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
-    sqlx::query!(
-        r#"UPDATE subscriptions SET status = 'confirmed' WHERE id = $1"#,
+    let mut transaction = pool.begin().await?;
+    let email = sqlx::query_scalar!(
+        r#"
+        UPDATE subscriptions SET status = 'confirmed'
+        WHERE id = $1 AND status = 'pending_confirmation'
+        RETURNING email
+        "#,
         subscriber_id
     )
-    .execute(pool)
+    .fetch_optional(&mut *transaction)
     .await
     .map_err(|e| {
         tracing::error!("Failed to execute query: {:?}", e);
         e
     })?;
+    if let Some(email) = email {
+        schedule_automation_steps(&mut transaction, &email).await?;
+    }
+    transaction.commit().await?;
     Ok(())
 }
 
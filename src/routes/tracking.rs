@@ -0,0 +1,57 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use uuid::Uuid;
+
+use crate::{
+    startup::AppState,
+    tracking::{TRACKING_PIXEL, record_click, record_open, verify_link},
+};
+
+/// Always answers with the pixel, failing to record an open must not show a
+/// broken image to the reader.
+#[tracing::instrument(name = "Record an issue open", skip(state, headers))]
+pub async fn track_open_handler(
+    State(state): State<AppState>,
+    Path(recipient_id): Path<Uuid>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if state.tracking.allow_open_tracking {
+        let user_agent = headers
+            .get(header::USER_AGENT)
+            .and_then(|h| h.to_str().ok());
+        if let Err(e) = record_open(&state.pg_pool, recipient_id, user_agent).await {
+            tracing::error!(error.cause_chain = ?e, error.message = %e, "Failed to record an open.");
+        }
+    }
+    (
+        [
+            (header::CONTENT_TYPE, "image/gif"),
+            (
+                header::CACHE_CONTROL,
+                "no-store, no-cache, must-revalidate, private",
+            ),
+        ],
+        TRACKING_PIXEL,
+    )
+}
+
+/// Only tokens signed by the worker are followed, so this can not be abused as an
+/// open redirect.
+#[tracing::instrument(name = "Record a link click", skip(state, token))]
+pub async fn track_click_handler(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Response {
+    let Some((recipient_id, url)) = verify_link(&state.tracking.link_signing_key, &token) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if state.tracking.allow_click_tracking
+        && let Err(e) = record_click(&state.pg_pool, recipient_id, &url).await
+    {
+        tracing::error!(error.cause_chain = ?e, error.message = %e, "Failed to record a click.");
+    }
+    (StatusCode::FOUND, [(header::LOCATION, url)]).into_response()
+}
@@ -0,0 +1,68 @@
+use axum::{
+    Form,
+    extract::{Query, State},
+    response::{Html, IntoResponse, Response},
+};
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use crate::{
+    csrf::CsrfToken,
+    html::escape_html,
+    startup::AppState,
+    unsubscribe::{get_subscriber_id_from_unsubscribe_token, unsubscribe_subscriber},
+};
+
+#[derive(Deserialize, Debug)]
+pub struct UnsubscribeParameters {
+    token: String,
+}
+
+/// Asks for confirmation first, so that mail scanners following the link do not
+/// unsubscribe anybody.
+pub async fn unsubscribe_form(
+    csrf_token: CsrfToken,
+    Query(parameters): Query<UnsubscribeParameters>,
+) -> Html<String> {
+    let csrf_field = csrf_token.form_field();
+    Html(format!(
+        r#"<!DOCTYPE html>
+        <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8">
+            <title>Unsubscribe</title>
+        </head>
+        <body>
+            <form action="/subscriptions/unsubscribe" method="post">
+                {csrf_field}
+                <input hidden type="text" name="token" value="{}">
+                <button type="submit">Unsubscribe from the newsletter</button>
+            </form>
+        </body>
+        </html>"#,
+        escape_html(&parameters.token)
+    ))
+}
+
+#[tracing::instrument(name = "Unsubscribe", skip(state, form))]
+pub async fn unsubscribe_handler(
+    State(state): State<AppState>,
+    Form(form): Form<UnsubscribeParameters>,
+) -> Response {
+    let id = match get_subscriber_id_from_unsubscribe_token(&state.pg_pool, &form.token).await {
+        Ok(id) => id,
+        Err(e) => {
+            tracing::error!(error.cause_chain = ?e, error.message = %e, "Failed to look up the unsubscribe token.");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let Some(subscriber_id) = id else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    if let Err(e) = unsubscribe_subscriber(&state.pg_pool, subscriber_id).await {
+        tracing::error!(error.cause_chain = ?e, error.message = %e, "Failed to unsubscribe the subscriber.");
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    Html("<p>You have been unsubscribed, you will not get any more emails from us.</p>")
+        .into_response()
+}
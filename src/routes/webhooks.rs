@@ -0,0 +1,175 @@
+use anyhow::Context;
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use secrecy::ExposeSecret;
+use serde::Deserialize;
+use sqlx::{Executor, Postgres, Transaction};
+use subtle::ConstantTimeEq;
+
+use crate::{
+    configuration::WebhookSettings, domain::SuppressionTarget, routes::error_chain_fmt,
+    startup::AppState, suppressions::add_suppression,
+};
+
+/// The subset of a Postmark bounce or spam complaint webhook payload we act on.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct PostmarkWebhook {
+    record_type: String,
+    #[serde(rename = "Type", default)]
+    bounce_type: Option<String>,
+    email: String,
+}
+
+/// Bounce types after which Postmark will not deliver to the address again.
+const PERMANENT_BOUNCE_TYPES: [&str; 3] = ["HardBounce", "BadEmailAddress", "ManuallyDeactivated"];
+
+#[derive(thiserror::Error)]
+pub enum WebhookError {
+    #[error("Invalid webhook credentials.")]
+    Unauthorized,
+    #[error("The webhook payload could not be parsed.")]
+    InvalidPayload(#[source] serde_json::Error),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for WebhookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for WebhookError {
+    fn into_response(self) -> Response {
+        match self {
+            WebhookError::Unauthorized => (
+                StatusCode::UNAUTHORIZED,
+                [("WWW-Authenticate", r#"Basic realm="webhooks""#)],
+                self.to_string(),
+            )
+                .into_response(),
+            WebhookError::InvalidPayload(_) => {
+                (StatusCode::BAD_REQUEST, self.to_string()).into_response()
+            }
+            WebhookError::UnexpectedError(err) => {
+                tracing::error!("{:?}", err);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Something went wrong".to_owned(),
+                )
+                    .into_response()
+            }
+        }
+    }
+}
+
+/// The body is only parsed once the credentials have been checked.
+#[tracing::instrument(
+    name = "Processing a Postmark webhook",
+    skip(state, headers, body),
+    fields(record_type, subscriber_email)
+)]
+pub async fn postmark_webhook_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<StatusCode, WebhookError> {
+    if !is_authorized(&headers, &state.webhooks) {
+        return Err(WebhookError::Unauthorized);
+    }
+    let payload: PostmarkWebhook =
+        serde_json::from_slice(&body).map_err(WebhookError::InvalidPayload)?;
+    let span = tracing::Span::current();
+    span.record("record_type", tracing::field::display(&payload.record_type));
+    span.record("subscriber_email", tracing::field::display(&payload.email));
+
+    let status = match payload.record_type.as_str() {
+        "SpamComplaint" => "complained",
+        "Bounce"
+            if payload
+                .bounce_type
+                .as_deref()
+                .is_some_and(|t| PERMANENT_BOUNCE_TYPES.contains(&t)) =>
+        {
+            "bounced"
+        }
+        _ => {
+            tracing::info!("Ignoring a webhook event that does not require any action.");
+            return Ok(StatusCode::OK);
+        }
+    };
+
+    // Either both the status and the suppression are stored, or neither is.
+    let mut transaction = state
+        .pg_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    mark_subscriber(&mut transaction, &payload.email, status)
+        .await
+        .context("Failed to update the subscriber status")?;
+    match SuppressionTarget::parse(payload.email.clone()) {
+        Ok(target) => {
+            add_suppression(&mut *transaction, &target, status, "postmark")
+                .await
+                .context("Failed to add the address to the suppression list")?;
+        }
+        Err(e) => tracing::warn!("Not suppressing an invalid address: {e}"),
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the webhook changes")?;
+    Ok(StatusCode::OK)
+}
+
+/// Postmark can either authenticate with basic auth credentials embedded in the
+/// webhook url or send the shared secret as a custom header.
+fn is_authorized(headers: &HeaderMap, settings: &WebhookSettings) -> bool {
+    let secret = settings.shared_secret.expose_secret().as_bytes();
+    if let Some(token) = headers.get("X-Webhook-Secret") {
+        return bool::from(token.as_bytes().ct_eq(secret));
+    }
+
+    let Some(credentials) = headers
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Basic "))
+        .and_then(|encoded| STANDARD.decode(encoded).ok())
+        .and_then(|decoded| String::from_utf8(decoded).ok())
+    else {
+        return false;
+    };
+    match credentials.split_once(':') {
+        Some((username, password)) => {
+            username == settings.username && bool::from(password.as_bytes().ct_eq(secret))
+        }
+        None => false,
+    }
+}
+
+#[tracing::instrument(name = "Mark subscriber as undeliverable", skip(transaction, email))]
+pub async fn mark_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &str,
+    status: &str,
+) -> Result<(), sqlx::Error> {
+    // A complaint is the stronger signal, a later bounce must not downgrade it.
+    let query = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = $1
+        WHERE email = $2 AND status <> 'complained'
+        "#,
+        status,
+        email
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
@@ -1,39 +1,89 @@
 use crate::{
-    configuration::{DatabaseSettings, Settings},
+    attachments::MAX_TOTAL_ATTACHMENT_SIZE,
+    authentication::Backend,
+    authorization::{authorize, is_logged_in},
+    configuration::{
+        DatabaseSettings, MediaSettings, SessionSettings, Settings, TrackingSettings,
+        WebhookSettings,
+    },
+    csrf::{CsrfBodyLimits, verify_csrf_token},
     email_client::EmailClient,
+    login_throttle::LoginThrottle,
+    media::MediaStorage,
     routes::{
-        admin_dashboard, change_password_form, health_check_handler, home, log_out, login,
-        login_form, post_change_password, publish_newsletters_form, publish_newsletters_handler,
-        subscribe_handler, subscriptions_confirm_handler,
+        accept_invitation_form, accept_invitation_handler, add_step_handler,
+        add_suppression_handler, admin_dashboard, archive_issue_page, archive_page, atom_feed,
+        automation_page, automations_page, change_password_form, change_role_handler,
+        confirm_two_factor_handler, create_automation_handler, create_layout_handler,
+        delete_layout_handler, delete_media_handler, delete_step_handler, delete_user_handler,
+        disable_two_factor_handler, edit_layout_page, enrol_two_factor_handler,
+        export_suppressions, forgot_password_form, health_check_handler, home, import_suppressions,
+        invite_user_handler, issue_stats_page, issues_page, layouts_page, log_out, login,
+        login_form, login_two_factor_form, login_two_factor_handler, media_handler, media_page,
+        post_change_password, postmark_webhook_handler, preferences_form, preferences_handler,
+        preview_newsletter_handler, publish_newsletters_form, publish_newsletters_handler,
+        remove_suppression_handler, request_password_reset_handler, reset_password_form,
+        reset_password_handler, revoke_session_handler, rss_feed, sessions_page,
+        set_default_layout_handler, subscribe_handler, subscriptions_confirm_handler,
+        suppressions_page, toggle_automation_handler, toggle_user_handler, track_click_handler,
+        track_open_handler, two_factor_page, unsubscribe_form, unsubscribe_handler,
+        update_layout_handler, upload_media_handler, users_page,
     },
+    two_factor::TwoFactor,
 };
 use axum::{
     Router,
-    extract::{MatchedPath, Request},
+    extract::{
+        ConnectInfo, DefaultBodyLimit, MatchedPath, Request,
+        connect_info::IntoMakeServiceWithConnectInfo,
+    },
+    middleware::{self, AddExtension},
+    response::Redirect,
     routing::{get, post},
     serve::Serve,
 };
-use axum_login::tower_sessions::{Expiry, SessionManagerLayer};
+use axum_login::{
+    AuthManagerLayerBuilder, predicate_required,
+    tower_sessions::{Expiry, SessionManagerLayer},
+};
 use axum_messages::MessagesManagerLayer;
-use secrecy::{ExposeSecret, SecretString};
+use secrecy::ExposeSecret;
 use sqlx::{PgPool, postgres::PgPoolOptions};
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 use time::Duration;
 use tokio::net::TcpListener;
 use tower_http::trace::TraceLayer;
 use tower_sessions_redis_store::{RedisStore, fred::prelude::*};
 use tracing::{info, info_span};
 
+/// What axum accepts on routes that do not set a limit of their own.
+const DEFAULT_BODY_LIMIT_BYTES: usize = 2 * 1024 * 1024;
+
 #[derive(Clone, Debug)]
 pub struct AppState {
     pub pg_pool: Arc<PgPool>,
     pub email_client: Arc<EmailClient>,
     pub base_url: Arc<ApplicationBaseUrl>,
+    pub webhooks: Arc<WebhookSettings>,
+    pub tracking: Arc<TrackingSettings>,
+    pub media: Arc<dyn MediaStorage>,
+    pub media_settings: Arc<MediaSettings>,
+    pub two_factor: Arc<TwoFactor>,
+    pub login_throttle: Arc<LoginThrottle>,
+    pub password_reset_throttle: Arc<LoginThrottle>,
+    pub session_settings: Arc<SessionSettings>,
 }
 
+/// Served with the client address, the login throttle counts failures per address.
+type Server = Serve<
+    TcpListener,
+    IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+    AddExtension<Router, ConnectInfo<SocketAddr>>,
+>;
+
 pub struct Application {
     port: u16,
-    server: Serve<TcpListener, Router, Router>,
+    server: Server,
 }
 #[derive(Clone, Debug)]
 pub struct ApplicationBaseUrl(pub String);
@@ -42,32 +92,81 @@ pub async fn run(
     listener: TcpListener,
     connection: PgPool,
     email_client: EmailClient,
-    base_url: String,
-    redis_uri: SecretString,
-) -> Result<Serve<TcpListener, Router, Router>, anyhow::Error> {
-    let state = AppState {
-        pg_pool: Arc::new(connection),
-        email_client: Arc::new(email_client),
-        base_url: Arc::new(ApplicationBaseUrl(base_url)),
-    };
-
+    configuration: Settings,
+) -> Result<Server, anyhow::Error> {
     //Redis
-    let conf = Config::from_url(redis_uri.expose_secret())?;
+    let conf = Config::from_url(configuration.redis_uri.expose_secret())?;
     let pool = Pool::new(conf, None, None, None, 6)?;
     let _redis_conn = pool.connect();
     pool.wait_for_connect().await?;
 
+    let state = AppState {
+        pg_pool: Arc::new(connection),
+        email_client: Arc::new(email_client),
+        base_url: Arc::new(ApplicationBaseUrl(configuration.application.base_url)),
+        webhooks: Arc::new(configuration.webhooks),
+        tracking: Arc::new(configuration.tracking),
+        media: Arc::new(configuration.media.storage()),
+        media_settings: Arc::new(configuration.media),
+        two_factor: Arc::new(TwoFactor::new(&configuration.two_factor)?),
+        login_throttle: Arc::new(LoginThrottle::new(
+            pool.clone(),
+            configuration.login_throttle,
+        )),
+        password_reset_throttle: Arc::new(LoginThrottle::new(
+            pool.clone(),
+            configuration.password_reset_throttle,
+        )),
+        session_settings: Arc::new(configuration.session),
+    };
+
     let session_store = RedisStore::new(pool);
-    let session_layer = SessionManagerLayer::new(session_store)
-        .with_secure(false)
-        .with_expiry(Expiry::OnInactivity(Duration::seconds(10)));
+    let backend = Backend::new(state.pg_pool.as_ref().clone());
+    let session_settings = &state.session_settings;
+    let mut session_layer = SessionManagerLayer::new(session_store)
+        .with_name(session_settings.cookie_name.clone())
+        .with_secure(session_settings.secure)
+        .with_same_site(session_settings.same_site.into())
+        .with_http_only(true)
+        .with_expiry(Expiry::OnInactivity(Duration::seconds(
+            session_settings.inactivity_timeout_seconds,
+        )));
+    if let Some(domain) = &session_settings.domain {
+        session_layer = session_layer.with_domain(domain.clone());
+    }
 
+    // The publish form carries its attachments next to the content of the issue.
+    let max_publish_bytes = MAX_TOTAL_ATTACHMENT_SIZE + 2 * 1024 * 1024;
+    let max_upload_bytes = state.media_settings.max_upload_bytes + 64 * 1024;
+    let publish_body_limit = DefaultBodyLimit::max(max_publish_bytes);
+    let upload_body_limit = DefaultBodyLimit::max(max_upload_bytes);
+    // The token is looked for before the route applies its own limit.
+    let csrf_body_limits = CsrfBodyLimits::new(DEFAULT_BODY_LIMIT_BYTES)
+        .route("/admin/newsletters", max_publish_bytes)
+        .route("/admin/newsletters/preview", max_publish_bytes)
+        .route("/admin/media", max_upload_bytes);
     let app = Router::new()
         .route("/", get(home))
         .nest(
             "/admin",
             Router::new()
+                .route(
+                    "/automations",
+                    get(automations_page).post(create_automation_handler),
+                )
+                .route("/automations/{automation_id}", get(automation_page))
+                .route(
+                    "/automations/{automation_id}/toggle",
+                    post(toggle_automation_handler),
+                )
+                .route("/automations/{automation_id}/steps", post(add_step_handler))
+                .route(
+                    "/automations/{automation_id}/steps/{step_id}/delete",
+                    post(delete_step_handler),
+                )
                 .route("/dashboard", get(admin_dashboard))
+                .route("/issues", get(issues_page))
+                .route("/issues/{issue_id}", get(issue_stats_page))
                 .route(
                     "/password",
                     get(change_password_form).post(post_change_password),
@@ -75,13 +174,95 @@ pub async fn run(
                 .route("/logout", post(log_out))
                 .route(
                     "/newsletters",
-                    get(publish_newsletters_form).post(publish_newsletters_handler),
-                ),
+                    get(publish_newsletters_form)
+                        .post(publish_newsletters_handler)
+                        .layer(publish_body_limit),
+                )
+                .route(
+                    "/newsletters/preview",
+                    post(preview_newsletter_handler).layer(publish_body_limit),
+                )
+                .route("/layouts", get(layouts_page).post(create_layout_handler))
+                .route(
+                    "/layouts/{layout_id}",
+                    get(edit_layout_page).post(update_layout_handler),
+                )
+                .route(
+                    "/layouts/{layout_id}/default",
+                    post(set_default_layout_handler),
+                )
+                .route("/layouts/{layout_id}/delete", post(delete_layout_handler))
+                .route(
+                    "/media",
+                    get(media_page)
+                        .post(upload_media_handler)
+                        .layer(upload_body_limit),
+                )
+                .route("/media/{media_id}/delete", post(delete_media_handler))
+                .route("/sessions", get(sessions_page))
+                .route(
+                    "/sessions/{session_id}/revoke",
+                    post(revoke_session_handler),
+                )
+                .route(
+                    "/suppressions",
+                    get(suppressions_page).post(add_suppression_handler),
+                )
+                .route("/suppressions/delete", post(remove_suppression_handler))
+                .route("/suppressions/import", post(import_suppressions))
+                .route("/suppressions/export", get(export_suppressions))
+                .route("/two-factor", get(two_factor_page))
+                .route("/two-factor/enrol", post(enrol_two_factor_handler))
+                .route("/two-factor/verify", post(confirm_two_factor_handler))
+                .route("/two-factor/disable", post(disable_two_factor_handler))
+                .route("/users", get(users_page).post(invite_user_handler))
+                .route("/users/{user_id}/toggle", post(toggle_user_handler))
+                .route("/users/{user_id}/role", post(change_role_handler))
+                .route("/users/{user_id}/delete", post(delete_user_handler))
+                .route_layer(middleware::from_fn_with_state(state.clone(), authorize))
+                .route_layer(predicate_required!(is_logged_in, Redirect::to("/login"))),
         )
+        .route("/archive", get(archive_page))
+        .route("/archive/{slug}", get(archive_issue_page))
+        .route("/feed.atom", get(atom_feed))
+        .route("/feed.rss", get(rss_feed))
         .route("/health_check", get(health_check_handler))
+        .route(
+            "/invitations/accept",
+            get(accept_invitation_form).post(accept_invitation_handler),
+        )
         .route("/login", get(login_form).post(login))
+        .route(
+            "/login/two-factor",
+            get(login_two_factor_form).post(login_two_factor_handler),
+        )
+        .route("/media/{media_id}", get(media_handler))
+        .route(
+            "/password-reset",
+            get(forgot_password_form).post(request_password_reset_handler),
+        )
+        .route(
+            "/password-reset/confirm",
+            get(reset_password_form).post(reset_password_handler),
+        )
+        .route("/o/{recipient_id}", get(track_open_handler))
+        .route("/r/{token}", get(track_click_handler))
         .route("/subscriptions", post(subscribe_handler))
         .route("/subscriptions/confirm", get(subscriptions_confirm_handler))
+        .route(
+            "/subscriptions/preferences",
+            get(preferences_form).post(preferences_handler),
+        )
+        .route(
+            "/subscriptions/unsubscribe",
+            get(unsubscribe_form).post(unsubscribe_handler),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            csrf_body_limits,
+            verify_csrf_token,
+        ))
+        // Sent by Postmark, which has no session to take a token from.
+        .route("/webhooks/postmark", post(postmark_webhook_handler))
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(|request: &Request<_>| {
@@ -101,16 +282,19 @@ pub async fn run(
                 .on_failure(()),
         )
         .layer(MessagesManagerLayer)
-        .layer(session_layer)
+        .layer(AuthManagerLayerBuilder::new(backend, session_layer).build())
         .with_state(state);
 
-    Ok(axum::serve(listener, app))
+    Ok(axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    ))
 }
 
 impl Application {
     pub async fn build(configuration: Settings) -> Result<Self, anyhow::Error> {
         let connection_pool = get_connection_pool(&configuration.database);
-        let email_client = configuration.email_client.client();
+        let email_client = configuration.email_client.clone().client();
         let listener = TcpListener::bind(format!(
             "{}:{}",
             configuration.application.host, configuration.application.port
@@ -123,14 +307,7 @@ impl Application {
             configuration.application.host, configuration.application.port
         );
         //Start the application
-        let server = run(
-            listener,
-            connection_pool,
-            email_client,
-            configuration.application.base_url.clone(),
-            configuration.redis_uri.clone(),
-        )
-        .await?;
+        let server = run(listener, connection_pool, email_client, configuration).await?;
         Ok(Self { port, server })
     }
 
@@ -0,0 +1,272 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use serde::Deserialize;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use tracing::Span;
+use tracing::field::display;
+use uuid::Uuid;
+
+use crate::configuration::Settings;
+use crate::issue_delivery_worker::ExecutionOutcome;
+use crate::startup::get_connection_pool;
+
+/// The engagement signal used to pick the winning subject line of a test.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WinningMetric {
+    #[default]
+    Opens,
+    Clicks,
+}
+
+impl WinningMetric {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "opens" => Ok(Self::Opens),
+            "clicks" => Ok(Self::Clicks),
+            other => Err(format!("{other} is not a valid winning metric.")),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WinningMetric::Opens => "opens",
+            WinningMetric::Clicks => "clicks",
+        }
+    }
+}
+
+pub struct VariantResult {
+    pub variant_id: Uuid,
+    pub subject: String,
+    pub recipients: i64,
+    pub opens: i64,
+    pub clickers: i64,
+    pub is_winner: bool,
+}
+
+impl VariantResult {
+    fn rate(&self, metric: WinningMetric) -> f64 {
+        if self.recipients == 0 {
+            return 0.0;
+        }
+        let hits = match metric {
+            WinningMetric::Opens => self.opens,
+            WinningMetric::Clicks => self.clickers,
+        };
+        hits as f64 / self.recipients as f64
+    }
+}
+
+/// Compares rates rather than counts since the random split is rarely exact.
+/// The earliest variant wins ties, so an inconclusive test falls back to the title.
+pub fn pick_winner(results: &[VariantResult], metric: WinningMetric) -> Option<&VariantResult> {
+    results.iter().fold(None, |best, candidate| match best {
+        Some(best) if best.rate(metric) >= candidate.rate(metric) => Some(best),
+        _ => Some(candidate),
+    })
+}
+
+/// Stores the subject lines in order and schedules the decision.
+#[tracing::instrument(name = "Create subject test", skip(transaction, subjects))]
+pub async fn create_subject_test(
+    transaction: &mut Transaction<'static, Postgres>,
+    newsletter_issue_id: Uuid,
+    subjects: &[String],
+    metric: WinningMetric,
+    window: Duration,
+) -> Result<Vec<Uuid>, anyhow::Error> {
+    let mut variant_ids = Vec::with_capacity(subjects.len());
+    for (position, subject) in subjects.iter().enumerate() {
+        let variant_id = Uuid::new_v4();
+        let query = sqlx::query!(
+            r#"
+            INSERT INTO issue_subject_variants (variant_id, newsletter_issue_id, position, subject)
+            VALUES ($1, $2, $3, $4)
+            "#,
+            variant_id,
+            newsletter_issue_id,
+            i16::try_from(position).context("Too many subject variants")?,
+            subject
+        );
+        transaction.execute(query).await?;
+        variant_ids.push(variant_id);
+    }
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO subject_tests (newsletter_issue_id, metric, decide_after)
+        VALUES ($1, $2, now() + make_interval(secs => $3))
+        "#,
+        newsletter_issue_id,
+        metric.as_str(),
+        window.as_secs_f64()
+    );
+    transaction.execute(query).await?;
+    Ok(variant_ids)
+}
+
+#[tracing::instrument(name = "Get subject variant results", skip(executor))]
+pub async fn get_variant_results<'e, E>(
+    executor: E,
+    newsletter_issue_id: Uuid,
+) -> Result<Vec<VariantResult>, sqlx::Error>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    sqlx::query_as!(
+        VariantResult,
+        r#"
+        SELECT
+            v.variant_id,
+            v.subject,
+            count(r.recipient_id) AS "recipients!",
+            count(r.first_opened_at) AS "opens!",
+            count(c.recipient_id) AS "clickers!",
+            COALESCE(t.winning_variant_id = v.variant_id, false) AS "is_winner!"
+        FROM issue_subject_variants v
+        JOIN subject_tests t USING (newsletter_issue_id)
+        LEFT JOIN issue_recipients r ON r.subject_variant_id = v.variant_id
+        LEFT JOIN (SELECT DISTINCT recipient_id FROM issue_clicks) c
+            ON c.recipient_id = r.recipient_id
+        WHERE v.newsletter_issue_id = $1
+        GROUP BY v.variant_id, t.winning_variant_id
+        ORDER BY v.position
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_all(executor)
+    .await
+}
+
+pub async fn run_finalizer_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database);
+    loop {
+        match try_finalize_subject_test(&connection_pool).await {
+            Ok(ExecutionOutcome::EmptyQueue) => {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+            }
+            Err(_) => {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+        }
+    }
+}
+
+/// Picks the winner of a test whose window has passed and releases the held
+/// part of the audience with the winning subject line.
+#[tracing::instrument(skip_all, fields(newsletter_issue_id=tracing::field::Empty), err)]
+pub async fn try_finalize_subject_test(pool: &PgPool) -> Result<ExecutionOutcome, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let test = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id, metric
+        FROM subject_tests
+        WHERE decided_at IS NULL AND decide_after <= now()
+        FOR UPDATE
+        SKIP LOCKED
+        LIMIT 1
+        "#
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+    let Some(test) = test else {
+        transaction.rollback().await?;
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    Span::current().record("newsletter_issue_id", display(test.newsletter_issue_id));
+
+    let metric = WinningMetric::parse(&test.metric)
+        .map_err(anyhow::Error::msg)
+        .context("The subject test has an unknown winning metric")?;
+    let results = get_variant_results(&mut *transaction, test.newsletter_issue_id).await?;
+    let winner = pick_winner(&results, metric).context("The subject test has no variants")?;
+    tracing::info!(subject = %winner.subject, "Picked the winning subject line.");
+
+    let query = sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue
+        SET held = false, subject_variant_id = $2
+        WHERE newsletter_issue_id = $1 AND held
+        "#,
+        test.newsletter_issue_id,
+        winner.variant_id
+    );
+    transaction.execute(query).await?;
+    let query = sqlx::query!(
+        r#"
+        UPDATE subject_tests
+        SET winning_variant_id = $2, decided_at = now()
+        WHERE newsletter_issue_id = $1
+        "#,
+        test.newsletter_issue_id,
+        winner.variant_id
+    );
+    transaction.execute(query).await?;
+    transaction.commit().await?;
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{VariantResult, WinningMetric, pick_winner};
+    use claim::{assert_err, assert_none, assert_ok_eq, assert_some_eq};
+    use uuid::Uuid;
+
+    fn variant(subject: &str, recipients: i64, opens: i64, clickers: i64) -> VariantResult {
+        VariantResult {
+            variant_id: Uuid::new_v4(),
+            subject: subject.into(),
+            recipients,
+            opens,
+            clickers,
+            is_winner: false,
+        }
+    }
+
+    fn winner(results: &[VariantResult], metric: WinningMetric) -> Option<&str> {
+        pick_winner(results, metric).map(|v| v.subject.as_str())
+    }
+
+    #[test]
+    fn the_best_open_rate_wins() {
+        let results = [variant("A", 10, 2, 5), variant("B", 10, 6, 0)];
+        assert_some_eq!(winner(&results, WinningMetric::Opens), "B");
+    }
+
+    #[test]
+    fn the_best_click_rate_wins() {
+        let results = [variant("A", 10, 2, 5), variant("B", 10, 6, 0)];
+        assert_some_eq!(winner(&results, WinningMetric::Clicks), "A");
+    }
+
+    #[test]
+    fn rates_are_compared_instead_of_counts() {
+        let results = [variant("A", 20, 6, 0), variant("B", 10, 5, 0)];
+        assert_some_eq!(winner(&results, WinningMetric::Opens), "B");
+    }
+
+    #[test]
+    fn the_first_variant_wins_a_tie() {
+        let results = [
+            variant("A", 0, 0, 0),
+            variant("B", 0, 0, 0),
+            variant("C", 0, 0, 0),
+        ];
+        assert_some_eq!(winner(&results, WinningMetric::Opens), "A");
+    }
+
+    #[test]
+    fn there_is_no_winner_without_variants() {
+        assert_none!(winner(&[], WinningMetric::Opens));
+    }
+
+    #[test]
+    fn metrics_are_read_by_their_stored_name() {
+        for metric in [WinningMetric::Opens, WinningMetric::Clicks] {
+            assert_ok_eq!(WinningMetric::parse(metric.as_str()), metric);
+        }
+        assert_err!(WinningMetric::parse("bounces"));
+    }
+}
@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{domain::SuppressionTarget, suppressions::Suppression};
+
+#[derive(Serialize)]
+struct ExportRecord<'a> {
+    email: Option<&'a str>,
+    domain: Option<&'a str>,
+    reason: &'a str,
+    source: &'a str,
+    created_at: String,
+}
+
+#[derive(Deserialize)]
+struct ImportRecord {
+    #[serde(default)]
+    email: Option<String>,
+    #[serde(default)]
+    domain: Option<String>,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+pub fn to_csv(suppressions: &[Suppression]) -> Result<String, anyhow::Error> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for s in suppressions {
+        writer.serialize(ExportRecord {
+            email: s.email.as_deref(),
+            domain: s.domain.as_deref(),
+            reason: &s.reason,
+            source: &s.source,
+            created_at: s.created_at.to_rfc3339(),
+        })?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+/// Parses a CSV file with an `email` and/or `domain` column and an optional
+/// `reason` column, as produced by [`to_csv`].
+pub fn parse_csv(input: &str) -> Result<Vec<(SuppressionTarget, String)>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(input.as_bytes());
+    let mut entries = Vec::new();
+    for (i, record) in reader.deserialize::<ImportRecord>().enumerate() {
+        // The header is line 1.
+        let line = i + 2;
+        let record = record.map_err(|e| format!("Line {line}: {e}"))?;
+        let value = match (record.email, record.domain) {
+            (Some(email), _) if !email.is_empty() => email,
+            (_, Some(domain)) if !domain.is_empty() => domain,
+            _ => return Err(format!("Line {line}: either email or domain must be set.")),
+        };
+        let target = SuppressionTarget::parse(value).map_err(|e| format!("Line {line}: {e}"))?;
+        let reason = record
+            .reason
+            .filter(|r| !r.is_empty())
+            .unwrap_or_else(|| "imported".to_string());
+        entries.push((target, reason));
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_csv, to_csv};
+    use crate::suppressions::Suppression;
+    use claim::assert_err;
+
+    #[test]
+    fn an_export_can_be_imported_again() {
+        let suppressions = vec![
+            Suppression {
+                suppression_id: uuid::Uuid::new_v4(),
+                email: Some("ursula@domain.com".into()),
+                domain: None,
+                reason: "asked, politely, to be left alone".into(),
+                source: "admin".into(),
+                created_at: chrono::Utc::now(),
+            },
+            Suppression {
+                suppression_id: uuid::Uuid::new_v4(),
+                email: None,
+                domain: Some("example.com".into()),
+                reason: "spam trap".into(),
+                source: "import".into(),
+                created_at: chrono::Utc::now(),
+            },
+        ];
+
+        let entries = parse_csv(&to_csv(&suppressions).unwrap()).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].0.email(), Some("ursula@domain.com"));
+        assert_eq!(entries[0].1, "asked, politely, to be left alone");
+        assert_eq!(entries[1].0.domain(), Some("example.com"));
+    }
+
+    #[test]
+    fn a_missing_reason_defaults_to_imported() {
+        let entries = parse_csv("email\nursula@domain.com\n").unwrap();
+        assert_eq!(entries[0].1, "imported");
+    }
+
+    #[test]
+    fn invalid_rows_are_rejected_with_their_line_number() {
+        let error = parse_csv("email,reason\nursula@domain.com,\nnot-an-email@,x\n").unwrap_err();
+        assert!(error.starts_with("Line 3"));
+    }
+
+    #[test]
+    fn rows_without_email_or_domain_are_rejected() {
+        assert_err!(parse_csv("email,domain,reason\n,,no target\n"));
+    }
+}
@@ -0,0 +1,42 @@
+mod csv_file;
+mod persistence;
+
+pub use csv_file::{parse_csv, to_csv};
+pub use persistence::{
+    Suppression, add_suppression, is_suppressed, list_suppressions, remove_suppression,
+};
+
+use sqlx::PgPool;
+
+use crate::{attachments::EncodedAttachment, domain::SubscriberEmail, email_client::EmailClient};
+
+pub enum SendOutcome {
+    Sent,
+    Suppressed,
+}
+
+/// Every email leaves through here so that suppressed recipients are skipped
+/// no matter which feature triggered the send.
+#[tracing::instrument(
+    name = "Send an email unless the recipient is suppressed",
+    skip_all,
+    fields(recipient = %recipient)
+)]
+pub async fn send_unless_suppressed(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    recipient: &SubscriberEmail,
+    subject: &str,
+    html_content: &str,
+    text_content: &str,
+    attachments: &[EncodedAttachment],
+) -> Result<SendOutcome, anyhow::Error> {
+    if is_suppressed(pool, recipient).await? {
+        tracing::info!("Skipping a recipient on the suppression list.");
+        return Ok(SendOutcome::Suppressed);
+    }
+    email_client
+        .send_email_with_attachments(recipient, subject, html_content, text_content, attachments)
+        .await?;
+    Ok(SendOutcome::Sent)
+}
@@ -0,0 +1,88 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, PgPool, Postgres};
+use uuid::Uuid;
+
+use crate::domain::{SubscriberEmail, SuppressionTarget};
+
+#[derive(Debug)]
+pub struct Suppression {
+    pub suppression_id: Uuid,
+    pub email: Option<String>,
+    pub domain: Option<String>,
+    pub reason: String,
+    pub source: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "Check the suppression list", skip(pool))]
+pub async fn is_suppressed(pool: &PgPool, email: &SubscriberEmail) -> Result<bool, sqlx::Error> {
+    let suppressed = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1
+            FROM suppressions
+            WHERE
+                lower(email) = lower($1) OR
+                lower(domain) = lower(split_part($1, '@', 2))
+        ) AS "suppressed!"
+        "#,
+        email.as_ref()
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(suppressed)
+}
+
+/// Returns `false` if the target was already suppressed.
+#[tracing::instrument(name = "Add a suppression", skip(executor))]
+pub async fn add_suppression<'e, E>(
+    executor: E,
+    target: &SuppressionTarget,
+    reason: &str,
+    source: &str,
+) -> Result<bool, sqlx::Error>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    let n_inserted_rows = sqlx::query!(
+        r#"
+        INSERT INTO suppressions (suppression_id, email, domain, reason, source, created_at)
+        VALUES ($1, $2, $3, $4, $5, now())
+        ON CONFLICT DO NOTHING
+        "#,
+        Uuid::new_v4(),
+        target.email(),
+        target.domain(),
+        reason,
+        source
+    )
+    .execute(executor)
+    .await?
+    .rows_affected();
+    Ok(n_inserted_rows > 0)
+}
+
+#[tracing::instrument(name = "Remove a suppression", skip(pool))]
+pub async fn remove_suppression(pool: &PgPool, suppression_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"DELETE FROM suppressions WHERE suppression_id = $1"#,
+        suppression_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "List suppressions", skip(pool))]
+pub async fn list_suppressions(pool: &PgPool) -> Result<Vec<Suppression>, sqlx::Error> {
+    sqlx::query_as!(
+        Suppression,
+        r#"
+        SELECT suppression_id, email, domain, reason, source, created_at
+        FROM suppressions
+        ORDER BY created_at DESC
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
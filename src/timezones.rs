@@ -0,0 +1,105 @@
+use chrono::{NaiveDate, NaiveTime};
+use sqlx::PgPool;
+
+/// Subscribers who never told us their time zone get issues at this local time.
+pub const DEFAULT_TIMEZONE: &str = "UTC";
+
+/// A delivery at the same wall clock time in every subscriber's time zone.
+/// Without a date, the next occurrence of that time is used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalSendTime {
+    pub date: Option<NaiveDate>,
+    pub time: NaiveTime,
+}
+
+impl LocalSendTime {
+    /// Parses the date and time inputs of the publish form. Both being blank
+    /// means the issue goes out right away.
+    pub fn parse(date: &str, time: &str) -> Result<Option<Self>, String> {
+        let (date, time) = (date.trim(), time.trim());
+        if time.is_empty() {
+            if date.is_empty() {
+                return Ok(None);
+            }
+            return Err("Pick the local time the issue should be delivered at.".into());
+        }
+        let time = NaiveTime::parse_from_str(time, "%H:%M")
+            .map_err(|_| format!("{time} is not a valid time of day."))?;
+        let date = if date.is_empty() {
+            None
+        } else {
+            Some(
+                NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                    .map_err(|_| format!("{date} is not a valid date."))?,
+            )
+        };
+        Ok(Some(Self { date, time }))
+    }
+}
+
+/// Postgres does the time zone arithmetic, so its list is the one that matters.
+#[tracing::instrument(name = "Check a time zone name", skip(pool))]
+pub async fn is_known_timezone(pool: &PgPool, name: &str) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM pg_timezone_names WHERE name = $1) AS "exists!""#,
+        name
+    )
+    .fetch_one(pool)
+    .await
+}
+
+#[tracing::instrument(name = "List time zones", skip(pool))]
+pub async fn list_timezones(pool: &PgPool) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT name AS "name!"
+        FROM pg_timezone_names
+        WHERE name NOT LIKE 'posix/%' AND name <> 'localtime'
+        ORDER BY name
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LocalSendTime;
+    use chrono::{NaiveDate, NaiveTime};
+    use claim::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn blank_inputs_mean_right_away() {
+        assert_ok_eq!(LocalSendTime::parse("", " "), None);
+    }
+
+    #[test]
+    fn the_date_is_optional() {
+        assert_ok_eq!(
+            LocalSendTime::parse("", "09:00"),
+            Some(LocalSendTime {
+                date: None,
+                time: NaiveTime::from_hms_opt(9, 0, 0).unwrap()
+            })
+        );
+        assert_ok_eq!(
+            LocalSendTime::parse("2025-07-21", "17:30"),
+            Some(LocalSendTime {
+                date: NaiveDate::from_ymd_opt(2025, 7, 21),
+                time: NaiveTime::from_hms_opt(17, 30, 0).unwrap()
+            })
+        );
+    }
+
+    #[test]
+    fn a_date_without_a_time_is_rejected() {
+        assert_err!(LocalSendTime::parse("2025-07-21", ""));
+    }
+
+    #[test]
+    fn malformed_inputs_are_rejected() {
+        assert_err!(LocalSendTime::parse("", "25:00"));
+        assert_err!(LocalSendTime::parse("", "9am"));
+        assert_err!(LocalSendTime::parse("21/07/2025", "09:00"));
+    }
+}
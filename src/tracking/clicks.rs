@@ -0,0 +1,215 @@
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use hmac::{Hmac, Mac};
+use lol_html::{RewriteStrSettings, element, rewrite_str};
+use secrecy::{ExposeSecret, SecretString};
+use sha2::Sha256;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn mac(key: &SecretString) -> HmacSha256 {
+    HmacSha256::new_from_slice(key.expose_secret().as_bytes())
+        .expect("HMAC accepts keys of any size")
+}
+
+/// Encodes the recipient and the target of a link into a token for `/r/{token}`.
+/// The signature makes sure the redirect endpoint can only send readers to links
+/// that were part of an issue.
+pub fn sign_link(key: &SecretString, recipient_id: Uuid, url: &str) -> String {
+    let payload = format!("{recipient_id}|{url}");
+    let mut mac = mac(key);
+    mac.update(payload.as_bytes());
+    let signature = mac.finalize().into_bytes();
+    format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(payload),
+        URL_SAFE_NO_PAD.encode(signature)
+    )
+}
+
+/// Returns the recipient and the target of a link if the token was signed by us.
+pub fn verify_link(key: &SecretString, token: &str) -> Option<(Uuid, String)> {
+    let (payload, signature) = token.split_once('.')?;
+    let payload = URL_SAFE_NO_PAD.decode(payload).ok()?;
+    let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+    let mut mac = mac(key);
+    mac.update(&payload);
+    mac.verify_slice(&signature).ok()?;
+
+    let payload = String::from_utf8(payload).ok()?;
+    let (recipient_id, url) = payload.split_once('|')?;
+    Some((recipient_id.parse().ok()?, url.to_owned()))
+}
+
+/// The url a click on `href` is recorded under, if it is a link we track.
+fn link_target(href: &str) -> Option<String> {
+    let lowercase = href.trim_start().to_ascii_lowercase();
+    (lowercase.starts_with("http://") || lowercase.starts_with("https://"))
+        .then(|| href.trim().replace("&amp;", "&"))
+}
+
+/// Every distinct link of the issue `rewrite_links` would point at the redirect.
+pub fn trackable_links(html: &str) -> Result<Vec<String>, anyhow::Error> {
+    let mut links = Vec::new();
+    rewrite_str(
+        html,
+        RewriteStrSettings {
+            element_content_handlers: vec![element!("a[href]", |el| {
+                if let Some(url) = link_target(&el.get_attribute("href").unwrap_or_default())
+                    && !links.contains(&url)
+                {
+                    links.push(url);
+                }
+                Ok(())
+            })],
+            ..RewriteStrSettings::new()
+        },
+    )?;
+    Ok(links)
+}
+
+/// Points every http(s) link of the issue at the click tracking redirect.
+/// `mailto:`, anchors and relative links are left untouched.
+pub fn rewrite_links(
+    html: &str,
+    base_url: &str,
+    key: &SecretString,
+    recipient_id: Uuid,
+) -> Result<String, anyhow::Error> {
+    let rewritten = rewrite_str(
+        html,
+        RewriteStrSettings {
+            element_content_handlers: vec![element!("a[href]", |el| {
+                if let Some(url) = link_target(&el.get_attribute("href").unwrap_or_default()) {
+                    let token = sign_link(key, recipient_id, &url);
+                    el.set_attribute("href", &format!("http://{base_url}/r/{token}"))?;
+                }
+                Ok(())
+            })],
+            ..RewriteStrSettings::new()
+        },
+    )?;
+    Ok(rewritten)
+}
+
+/// Stores the links of a freshly published issue, so the stats list them
+/// before anyone clicks.
+#[tracing::instrument(skip(transaction, links))]
+pub async fn insert_issue_links(
+    transaction: &mut Transaction<'static, Postgres>,
+    newsletter_issue_id: Uuid,
+    links: &[String],
+) -> Result<(), sqlx::Error> {
+    for url in links {
+        let query = sqlx::query!(
+            r#"
+            INSERT INTO issue_links (link_id, newsletter_issue_id, url)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (newsletter_issue_id, url) DO NOTHING
+            "#,
+            Uuid::new_v4(),
+            newsletter_issue_id,
+            url
+        );
+        transaction.execute(query).await?;
+    }
+    Ok(())
+}
+
+/// Links of the layout are only rewritten at send time, so they get their
+/// row on the first click.
+#[tracing::instrument(skip(pool))]
+pub async fn record_click(pool: &PgPool, recipient_id: Uuid, url: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        WITH link AS (
+            INSERT INTO issue_links (link_id, newsletter_issue_id, url)
+            SELECT $1, newsletter_issue_id, $3
+            FROM issue_recipients
+            WHERE recipient_id = $2
+            ON CONFLICT (newsletter_issue_id, url) DO UPDATE SET url = EXCLUDED.url
+            RETURNING link_id
+        )
+        INSERT INTO issue_clicks (recipient_id, link_id, first_clicked_at, last_clicked_at, click_count)
+        SELECT $2, link_id, now(), now(), 1
+        FROM link
+        ON CONFLICT (recipient_id, link_id) DO UPDATE
+        SET
+            last_clicked_at = now(),
+            click_count = issue_clicks.click_count + 1
+        "#,
+        Uuid::new_v4(),
+        recipient_id,
+        url
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rewrite_links, sign_link, trackable_links, verify_link};
+    use secrecy::SecretString;
+    use uuid::Uuid;
+
+    fn key() -> SecretString {
+        SecretString::new("a-signing-key".into())
+    }
+
+    #[test]
+    fn a_signed_link_can_be_verified() {
+        let recipient_id = Uuid::new_v4();
+        let token = sign_link(&key(), recipient_id, "https://example.com/?a=1&b=2");
+
+        let (id, url) = verify_link(&key(), &token).unwrap();
+
+        assert_eq!(id, recipient_id);
+        assert_eq!(url, "https://example.com/?a=1&b=2");
+    }
+
+    #[test]
+    fn a_link_signed_with_another_key_is_rejected() {
+        let token = sign_link(
+            &SecretString::new("another-key".into()),
+            Uuid::new_v4(),
+            "https://a.b",
+        );
+        assert_eq!(verify_link(&key(), &token), None);
+    }
+
+    #[test]
+    fn a_tampered_target_is_rejected() {
+        let token = sign_link(&key(), Uuid::new_v4(), "https://example.com");
+        let (_, signature) = token.split_once('.').unwrap();
+        let forged = sign_link(&key(), Uuid::new_v4(), "https://evil.com");
+        let (payload, _) = forged.split_once('.').unwrap();
+        assert_eq!(verify_link(&key(), &format!("{payload}.{signature}")), None);
+    }
+
+    #[test]
+    fn trackable_links_are_listed_once() {
+        let html = r#"<a href="https://a.b/?x=1&amp;y=2">1</a><a href="mailto:a@b.c">2</a><a href="https://a.b/?x=1&amp;y=2">3</a>"#;
+
+        assert_eq!(
+            trackable_links(html).unwrap(),
+            vec!["https://a.b/?x=1&y=2".to_string()]
+        );
+    }
+
+    #[test]
+    fn only_http_links_are_rewritten() {
+        let html = r#"<a href="https://example.com/?a=1&amp;b=2">x</a><a href="mailto:a@b.c">y</a><a href="/local">z</a>"#;
+
+        let rewritten = rewrite_links(html, "127.0.0.1", &key(), Uuid::new_v4()).unwrap();
+
+        assert!(rewritten.contains(r#"<a href="http://127.0.0.1/r/"#));
+        assert!(rewritten.contains(r#"<a href="mailto:a@b.c">y</a>"#));
+        assert!(rewritten.contains(r#"<a href="/local">z</a>"#));
+        let start = rewritten.find("/r/").unwrap() + 3;
+        let end = start + rewritten[start..].find('"').unwrap();
+        let (_, url) = verify_link(&key(), &rewritten[start..end]).unwrap();
+        assert_eq!(url, "https://example.com/?a=1&b=2");
+    }
+}
@@ -0,0 +1,42 @@
+mod clicks;
+mod opens;
+
+pub use clicks::{
+    insert_issue_links, record_click, rewrite_links, sign_link, trackable_links, verify_link,
+};
+pub use opens::{TRACKING_PIXEL, inject_tracking_pixel, record_open};
+
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Returns the id identifying this recipient of the issue in tracking urls.
+/// Retried deliveries keep the id of the first attempt.
+#[tracing::instrument(skip(pool, subscriber_email))]
+pub async fn get_or_create_recipient(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    subscriber_email: &str,
+    subject_variant_id: Option<Uuid>,
+) -> Result<Uuid, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        INSERT INTO issue_recipients (
+            recipient_id,
+            newsletter_issue_id,
+            subscriber_email,
+            sent_at,
+            subject_variant_id
+        )
+        VALUES ($1, $2, $3, now(), $4)
+        ON CONFLICT (newsletter_issue_id, subscriber_email)
+        DO UPDATE SET sent_at = EXCLUDED.sent_at
+        RETURNING recipient_id
+        "#,
+        Uuid::new_v4(),
+        newsletter_issue_id,
+        subscriber_email,
+        subject_variant_id
+    )
+    .fetch_one(pool)
+    .await
+}
@@ -0,0 +1,63 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// A transparent 1x1 GIF.
+pub const TRACKING_PIXEL: [u8; 43] = [
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xff, 0xff, 0xff, 0x21, 0xf9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x02, 0x44, 0x01, 0x00, 0x3b,
+];
+
+/// Inserts an invisible image right before `</body>`, or at the end of
+/// fragments that have no body.
+pub fn inject_tracking_pixel(html: &str, pixel_url: &str) -> String {
+    let img =
+        format!(r#"<img src="{pixel_url}" width="1" height="1" alt="" style="display:none">"#);
+    match html.to_ascii_lowercase().rfind("</body>") {
+        Some(i) => format!("{}{img}{}", &html[..i], &html[i..]),
+        None => format!("{html}{img}"),
+    }
+}
+
+#[tracing::instrument(skip(pool, user_agent))]
+pub async fn record_open(
+    pool: &PgPool,
+    recipient_id: Uuid,
+    user_agent: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE issue_recipients
+        SET
+            open_count = open_count + 1,
+            first_opened_at = COALESCE(first_opened_at, now()),
+            last_opened_at = now(),
+            user_agent = COALESCE($2, user_agent)
+        WHERE recipient_id = $1
+        "#,
+        recipient_id,
+        user_agent
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::inject_tracking_pixel;
+
+    #[test]
+    fn the_pixel_is_injected_before_the_closing_body_tag() {
+        let html = "<html><body><p>Hi</p></BODY></html>";
+        let injected = inject_tracking_pixel(html, "http://127.0.0.1/o/1");
+        assert!(injected.starts_with("<html><body><p>Hi</p><img src=\"http://127.0.0.1/o/1\""));
+        assert!(injected.ends_with("></BODY></html>"));
+    }
+
+    #[test]
+    fn the_pixel_is_appended_to_fragments() {
+        let injected = inject_tracking_pixel("<p>Hi</p>", "http://127.0.0.1/o/1");
+        assert!(injected.starts_with("<p>Hi</p><img "));
+    }
+}
@@ -0,0 +1,303 @@
+mod persistence;
+
+pub use persistence::{
+    TwoFactorStatus, disable_two_factor, enable_two_factor, get_two_factor_status,
+    is_two_factor_enabled, set_pending_secret,
+};
+
+use aes_gcm::{
+    Aes256Gcm, KeyInit, Nonce,
+    aead::{Aead, AeadCore, OsRng},
+};
+use anyhow::Context;
+use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use qrcodegen::{QrCode, QrCodeEcc};
+use rand::{Rng, RngCore};
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::PgPool;
+use std::{
+    fmt::Write,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use subtle::ConstantTimeEq;
+use totp_rs::{Algorithm, TOTP};
+use uuid::Uuid;
+
+use crate::{
+    authentication::compute_password_hash, configuration::TwoFactorSettings,
+    telemetry::spawn_blocking_with_tracing,
+};
+
+pub const TOTP_STEP_SECONDS: u64 = 30;
+pub const RECOVERY_CODE_COUNT: usize = 8;
+const RECOVERY_CODE_LENGTH: usize = 10;
+/// No characters that are easily mistaken for one another, like `l` and `1`.
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+const NONCE_LENGTH: usize = 12;
+
+/// Everything needed to enrol users and check their codes.
+pub struct TwoFactor {
+    cipher: Aes256Gcm,
+    issuer: String,
+}
+
+impl std::fmt::Debug for TwoFactor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TwoFactor")
+            .field("issuer", &self.issuer)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TwoFactor {
+    pub fn new(settings: &TwoFactorSettings) -> Result<Self, anyhow::Error> {
+        let key = STANDARD
+            .decode(settings.encryption_key.expose_secret())
+            .context("The two-factor encryption key is not valid base64.")?;
+        let cipher = Aes256Gcm::new_from_slice(&key)
+            .map_err(|_| anyhow::anyhow!("The two-factor encryption key must be 32 bytes long."))?;
+        Ok(Self {
+            cipher,
+            issuer: settings.issuer.clone(),
+        })
+    }
+
+    /// The stored value is the random nonce followed by the ciphertext.
+    pub fn encrypt_secret(&self, secret: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, secret)
+            .map_err(|_| anyhow::anyhow!("Failed to encrypt a TOTP secret."))?;
+        let mut stored = nonce.to_vec();
+        stored.extend(ciphertext);
+        Ok(stored)
+    }
+
+    pub fn decrypt_secret(&self, stored: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
+        if stored.len() <= NONCE_LENGTH {
+            anyhow::bail!("The stored TOTP secret is too short.");
+        }
+        let (nonce, ciphertext) = stored.split_at(NONCE_LENGTH);
+        self.cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow::anyhow!("Failed to decrypt a TOTP secret."))
+    }
+
+    pub fn totp(&self, secret: Vec<u8>, account_name: &str) -> TOTP {
+        // Six digits every thirty seconds is what every authenticator app expects.
+        TOTP::new_unchecked(
+            Algorithm::SHA1,
+            6,
+            1,
+            TOTP_STEP_SECONDS,
+            secret,
+            Some(self.issuer.clone()),
+            account_name.to_string(),
+        )
+    }
+}
+
+pub fn generate_totp_secret() -> Vec<u8> {
+    let mut secret = vec![0; 20];
+    rand::rng().fill_bytes(&mut secret);
+    secret
+}
+
+/// Returns the time step `code` was generated for. The steps next to the
+/// current one are accepted as well, for phones whose clock is slightly off.
+pub fn matching_step(totp: &TOTP, code: &str, unix_time: u64) -> Option<u64> {
+    let step = unix_time / TOTP_STEP_SECONDS;
+    [step, step.saturating_sub(1), step + 1]
+        .into_iter()
+        .find(|candidate| {
+            totp.generate(candidate * TOTP_STEP_SECONDS)
+                .as_bytes()
+                .ct_eq(code.as_bytes())
+                .into()
+        })
+}
+
+pub fn is_totp_code(code: &str) -> bool {
+    code.len() == 6 && code.chars().all(|c| c.is_ascii_digit())
+}
+
+pub fn generate_recovery_code() -> String {
+    let mut rng = rand::rng();
+    let code: String = (0..RECOVERY_CODE_LENGTH)
+        .map(|_| RECOVERY_CODE_ALPHABET[rng.random_range(0..RECOVERY_CODE_ALPHABET.len())] as char)
+        .collect();
+    format!("{}-{}", &code[..5], &code[5..])
+}
+
+/// Recovery codes are typed in by hand, dashes, spaces and case do not matter.
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+pub fn hash_recovery_code(code: &str) -> Result<String, anyhow::Error> {
+    let normalized = SecretString::from(normalize_recovery_code(code));
+    Ok(compute_password_hash(normalized)?
+        .expose_secret()
+        .to_string())
+}
+
+fn verify_recovery_code(code_hash: &str, candidate: &str) -> bool {
+    PasswordHash::new(code_hash).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(candidate.as_bytes(), &hash)
+            .is_ok()
+    })
+}
+
+pub fn unix_time_now() -> Result<u64, anyhow::Error> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
+/// Checks a code from an authenticator app or one of the recovery codes.
+/// Either of them is only accepted once.
+#[tracing::instrument(name = "Verify a second factor", skip(pool, two_factor, code))]
+pub async fn verify_second_factor(
+    pool: &PgPool,
+    two_factor: &TwoFactor,
+    user_id: Uuid,
+    code: &str,
+) -> Result<bool, anyhow::Error> {
+    let code = code.trim();
+    if is_totp_code(code) {
+        let Some(stored) = persistence::get_totp_secret(pool, user_id).await? else {
+            return Ok(false);
+        };
+        let totp = two_factor.totp(two_factor.decrypt_secret(&stored)?, "");
+        let Some(step) = matching_step(&totp, code, unix_time_now()?) else {
+            return Ok(false);
+        };
+        return Ok(persistence::record_totp_step(pool, user_id, step).await?);
+    }
+
+    let candidate = normalize_recovery_code(code);
+    if candidate.len() != RECOVERY_CODE_LENGTH {
+        return Ok(false);
+    }
+    let stored = persistence::list_unused_recovery_codes(pool, user_id).await?;
+    let matching = spawn_blocking_with_tracing(move || {
+        stored
+            .into_iter()
+            .find(|(_, code_hash)| verify_recovery_code(code_hash, &candidate))
+            .map(|(recovery_code_id, _)| recovery_code_id)
+    })
+    .await
+    .context("Failed to spawn blocking task.")?;
+    match matching {
+        Some(recovery_code_id) => Ok(persistence::use_recovery_code(pool, recovery_code_id).await?),
+        None => Ok(false),
+    }
+}
+
+/// Renders `text` as an inline SVG, for scanning the enrolment link.
+pub fn qr_code_svg(text: &str) -> Result<String, anyhow::Error> {
+    let qr = QrCode::encode_text(text, QrCodeEcc::Medium)
+        .map_err(|e| anyhow::anyhow!("Failed to encode a QR code: {e:?}"))?;
+    let border = 4;
+    let dimension = qr.size() + 2 * border;
+    let mut path = String::new();
+    for y in 0..qr.size() {
+        for x in 0..qr.size() {
+            if qr.get_module(x, y) {
+                write!(path, "M{},{}h1v1h-1z", x + border, y + border).unwrap();
+            }
+        }
+    }
+    Ok(format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {dimension} {dimension}" width="200" height="200"><rect width="100%" height="100%" fill="#ffffff"/><path d="{path}" fill="#000000"/></svg>"##
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        TOTP_STEP_SECONDS, TwoFactor, generate_recovery_code, generate_totp_secret,
+        hash_recovery_code, is_totp_code, matching_step, normalize_recovery_code, qr_code_svg,
+        verify_recovery_code,
+    };
+    use crate::configuration::TwoFactorSettings;
+    use claim::{assert_err, assert_none, assert_ok, assert_some_eq};
+
+    fn two_factor() -> TwoFactor {
+        TwoFactor::new(&TwoFactorSettings {
+            issuer: "zero2prod".into(),
+            encryption_key: "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=".into(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn keys_must_be_32_bytes_long() {
+        assert_err!(TwoFactor::new(&TwoFactorSettings {
+            issuer: "zero2prod".into(),
+            encryption_key: "c2hvcnQ=".into(),
+        }));
+    }
+
+    #[test]
+    fn secrets_survive_a_round_trip() {
+        let two_factor = two_factor();
+        let secret = generate_totp_secret();
+        let stored = two_factor.encrypt_secret(&secret).unwrap();
+        assert!(!stored.windows(secret.len()).any(|w| w == secret));
+        assert_eq!(two_factor.decrypt_secret(&stored).unwrap(), secret);
+    }
+
+    #[test]
+    fn tampered_secrets_are_rejected() {
+        let two_factor = two_factor();
+        let mut stored = two_factor.encrypt_secret(&generate_totp_secret()).unwrap();
+        *stored.last_mut().unwrap() ^= 1;
+        assert_err!(two_factor.decrypt_secret(&stored));
+        assert_err!(two_factor.decrypt_secret(&[0; 12]));
+    }
+
+    #[test]
+    fn codes_of_the_neighbouring_steps_are_accepted() {
+        let totp = two_factor().totp(generate_totp_secret(), "ursula");
+        let now = 1_700_000_000;
+        let step = now / TOTP_STEP_SECONDS;
+        assert_some_eq!(matching_step(&totp, &totp.generate(now), now), step);
+        let previous = totp.generate(now - TOTP_STEP_SECONDS);
+        assert_some_eq!(matching_step(&totp, &previous, now), step - 1);
+        let stale = totp.generate(now - 3 * TOTP_STEP_SECONDS);
+        assert_none!(matching_step(&totp, &stale, now));
+    }
+
+    #[test]
+    fn totp_codes_are_six_digits() {
+        assert!(is_totp_code("012345"));
+        assert!(!is_totp_code("01234"));
+        assert!(!is_totp_code("abcde-fghjk"));
+    }
+
+    #[test]
+    fn recovery_codes_are_verified_regardless_of_formatting() {
+        let code = generate_recovery_code();
+        assert_eq!(code.len(), 11);
+        assert_ne!(code, generate_recovery_code());
+        let hash = hash_recovery_code(&code).unwrap();
+        let typed = code.to_uppercase().replace('-', " ");
+        assert!(verify_recovery_code(
+            &hash,
+            &normalize_recovery_code(&typed)
+        ));
+        assert!(!verify_recovery_code(&hash, "abcdefghjk"));
+    }
+
+    #[test]
+    fn qr_codes_are_rendered_as_svg() {
+        let svg = assert_ok!(qr_code_svg("otpauth://totp/zero2prod:ursula"));
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("<path d=\"M"));
+    }
+}
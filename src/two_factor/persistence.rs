@@ -0,0 +1,188 @@
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+pub struct TwoFactorStatus {
+    pub enabled: bool,
+    /// The encrypted secret of an enrolment that has not been verified yet.
+    pub pending_secret: Option<Vec<u8>>,
+    pub unused_recovery_codes: i64,
+}
+
+#[tracing::instrument(name = "Get the two-factor status", skip(pool))]
+pub async fn get_two_factor_status(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<TwoFactorStatus, sqlx::Error> {
+    sqlx::query_as!(
+        TwoFactorStatus,
+        r#"
+        SELECT
+            totp_secret IS NOT NULL AS "enabled!",
+            totp_pending_secret AS pending_secret,
+            (
+                SELECT count(*) FROM recovery_codes
+                WHERE recovery_codes.user_id = users.user_id AND used_at IS NULL
+            ) AS "unused_recovery_codes!"
+        FROM users
+        WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+}
+
+#[tracing::instrument(
+    name = "Check whether two-factor authentication is enabled",
+    skip(pool)
+)]
+pub async fn is_two_factor_enabled(pool: &PgPool, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    let enabled = sqlx::query_scalar!(
+        r#"SELECT totp_secret IS NOT NULL AS "enabled!" FROM users WHERE user_id = $1"#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(enabled.unwrap_or(false))
+}
+
+#[tracing::instrument(name = "Store a pending TOTP secret", skip(pool, encrypted_secret))]
+pub async fn set_pending_secret(
+    pool: &PgPool,
+    user_id: Uuid,
+    encrypted_secret: &[u8],
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE users SET totp_pending_secret = $1 WHERE user_id = $2",
+        encrypted_secret,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Promotes the pending secret once a code generated from it has been checked,
+/// and replaces any previous recovery codes.
+#[tracing::instrument(
+    name = "Enable two-factor authentication",
+    skip(transaction, code_hashes)
+)]
+pub async fn enable_two_factor(
+    transaction: &mut Transaction<'static, Postgres>,
+    user_id: Uuid,
+    verified_step: u64,
+    code_hashes: &[String],
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET totp_secret = totp_pending_secret, totp_pending_secret = NULL, totp_last_step = $1
+        WHERE user_id = $2 AND totp_pending_secret IS NOT NULL
+        "#,
+        verified_step as i64,
+        user_id
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!("DELETE FROM recovery_codes WHERE user_id = $1", user_id)
+        .execute(&mut **transaction)
+        .await?;
+    let ids: Vec<Uuid> = code_hashes.iter().map(|_| Uuid::new_v4()).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO recovery_codes (recovery_code_id, user_id, code_hash)
+        SELECT id, $2, code_hash FROM UNNEST($1::uuid[], $3::text[]) AS t(id, code_hash)
+        "#,
+        &ids,
+        user_id,
+        code_hashes
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Disable two-factor authentication", skip(pool))]
+pub async fn disable_two_factor(pool: &PgPool, user_id: Uuid) -> Result<(), sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET totp_secret = NULL, totp_pending_secret = NULL, totp_last_step = NULL
+        WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!("DELETE FROM recovery_codes WHERE user_id = $1", user_id)
+        .execute(&mut *transaction)
+        .await?;
+    transaction.commit().await
+}
+
+pub(super) async fn get_totp_secret(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Option<Vec<u8>>, sqlx::Error> {
+    let secret = sqlx::query_scalar!("SELECT totp_secret FROM users WHERE user_id = $1", user_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(secret.flatten())
+}
+
+/// Returns false if a code of this or a later step has been used already.
+pub(super) async fn record_totp_step(
+    pool: &PgPool,
+    user_id: Uuid,
+    step: u64,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE users SET totp_last_step = $1
+        WHERE user_id = $2 AND (totp_last_step IS NULL OR totp_last_step < $1)
+        "#,
+        step as i64,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+pub(super) async fn list_unused_recovery_codes(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Vec<(Uuid, String)>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT recovery_code_id, code_hash FROM recovery_codes
+        WHERE user_id = $1 AND used_at IS NULL
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.recovery_code_id, row.code_hash))
+        .collect())
+}
+
+/// Returns false if the code has been used in the meantime.
+pub(super) async fn use_recovery_code(
+    pool: &PgPool,
+    recovery_code_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE recovery_codes SET used_at = now()
+        WHERE recovery_code_id = $1 AND used_at IS NULL
+        "#,
+        recovery_code_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
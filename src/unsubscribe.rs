@@ -0,0 +1,100 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::automations::cancel_pending_steps;
+
+/// The unsubscribe token is only known to the subscriber. It is kept apart from
+/// the confirmation token, so a forwarded issue can not be used to confirm.
+pub fn unsubscribe_url(base_url: &str, unsubscribe_token: &str) -> String {
+    format!("http://{base_url}/subscriptions/unsubscribe?token={unsubscribe_token}")
+}
+
+pub fn preferences_url(base_url: &str, unsubscribe_token: &str) -> String {
+    format!("http://{base_url}/subscriptions/preferences?token={unsubscribe_token}")
+}
+
+/// Inserts the links right before `</body>`, or at the end of fragments that
+/// have no body.
+pub fn append_unsubscribe_link(html: &str, url: &str, preferences_url: &str) -> String {
+    let link = format!(
+        r#"<p><a href="{url}">Unsubscribe</a> | <a href="{preferences_url}">Email preferences</a></p>"#
+    );
+    match html.to_ascii_lowercase().rfind("</body>") {
+        Some(i) => format!("{}{link}{}", &html[..i], &html[i..]),
+        None => format!("{html}{link}"),
+    }
+}
+
+pub fn append_unsubscribe_text(text: &str, url: &str, preferences_url: &str) -> String {
+    format!("{text}\n\nUnsubscribe: {url}\nEmail preferences: {preferences_url}")
+}
+
+#[tracing::instrument(name = "Get subscriber_id from unsubscribe token", skip(pool, token))]
+pub async fn get_subscriber_id_from_unsubscribe_token(
+    pool: &PgPool,
+    token: &str,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT id FROM subscriptions WHERE unsubscribe_token = $1"#,
+        token
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Marks the subscriber as unsubscribed and cancels the automation steps that
+/// were still scheduled for them. Bounced and complained subscribers keep their
+/// status.
+#[tracing::instrument(name = "Unsubscribe a subscriber", skip(pool))]
+pub async fn unsubscribe_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let email = sqlx::query_scalar!(
+        r#"
+        UPDATE subscriptions SET status = 'unsubscribed'
+        WHERE id = $1 AND status IN ('confirmed', 'pending_confirmation')
+        RETURNING email
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+    let Some(email) = email else {
+        tracing::info!("The subscriber is no longer subscribed, nothing to do.");
+        return Ok(());
+    };
+    let cancelled = cancel_pending_steps(&mut *transaction, &email).await?;
+    tracing::info!(cancelled, "Cancelled pending automation steps.");
+    transaction.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::append_unsubscribe_link;
+
+    #[test]
+    fn the_link_is_the_last_element_of_the_body() {
+        let html = append_unsubscribe_link(
+            "<html><body><p>Hi</p></body></html>",
+            "http://localhost/unsubscribe",
+            "http://localhost/preferences",
+        );
+        assert_eq!(
+            html,
+            r#"<html><body><p>Hi</p><p><a href="http://localhost/unsubscribe">Unsubscribe</a> | <a href="http://localhost/preferences">Email preferences</a></p></body></html>"#
+        );
+    }
+
+    #[test]
+    fn fragments_get_the_link_appended() {
+        let html = append_unsubscribe_link(
+            "<p>Hi</p>",
+            "http://localhost/unsubscribe",
+            "http://localhost/preferences",
+        );
+        assert!(html.starts_with("<p>Hi</p>"));
+        assert!(
+            html.ends_with(r#"<a href="http://localhost/preferences">Email preferences</a></p>"#)
+        );
+    }
+}
@@ -0,0 +1,90 @@
+mod persistence;
+mod sessions;
+
+pub use persistence::{
+    AdminUser, ResetRecipient, UserChange, accept_invitation, delete_user, get_invited_username,
+    get_reset_recipient, insert_invitation, insert_invited_user, insert_password_reset,
+    is_valid_password_reset, list_users, reset_password, set_user_active, set_user_role,
+};
+pub use sessions::{
+    UserSession, insert_user_session, list_user_sessions, revoke_other_sessions,
+    revoke_user_session, touch_user_session,
+};
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use rand::{Rng, distr::Alphanumeric};
+use sha2::{Digest, Sha256};
+
+/// How long the link in an invitation email can be used.
+pub const INVITATION_VALIDITY_HOURS: i32 = 72;
+/// Reset links are short lived, they replace the password after all.
+pub const PASSWORD_RESET_VALIDITY_MINUTES: i32 = 60;
+
+pub fn generate_token() -> String {
+    let mut rng = rand::rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(32)
+        .collect()
+}
+
+/// Tokens are random enough that a fast hash is sufficient, what matters is
+/// that a database leak does not hand out working links.
+pub fn hash_token(token: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(token.as_bytes()))
+}
+
+pub fn invitation_url(base_url: &str, token: &str) -> String {
+    format!("http://{base_url}/invitations/accept?token={token}")
+}
+
+pub fn password_reset_url(base_url: &str, token: &str) -> String {
+    format!("http://{base_url}/password-reset/confirm?token={token}")
+}
+
+pub fn validate_username(username: &str) -> Result<&str, String> {
+    let username = username.trim();
+    if username.is_empty() {
+        return Err("The username cannot be empty.".into());
+    }
+    if username.chars().count() > 64 {
+        return Err("The username must be at most 64 characters long.".into());
+    }
+    if username.chars().any(char::is_whitespace) {
+        return Err("The username cannot contain spaces.".into());
+    }
+    Ok(username)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_token, hash_token, validate_username};
+    use claim::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn tokens_are_unique() {
+        let token = generate_token();
+        assert_eq!(token.len(), 32);
+        assert_ne!(token, generate_token());
+    }
+
+    #[test]
+    fn the_hash_does_not_contain_the_token() {
+        let token = generate_token();
+        let hash = hash_token(&token);
+        assert_eq!(hash, hash_token(&token));
+        assert!(!hash.contains(&token));
+    }
+
+    #[test]
+    fn usernames_are_trimmed() {
+        assert_ok_eq!(validate_username("  ursula "), "ursula");
+    }
+
+    #[test]
+    fn invalid_usernames_are_rejected() {
+        assert_err!(validate_username(" "));
+        assert_err!(validate_username("ursula le guin"));
+        assert_err!(validate_username(&"a".repeat(65)));
+    }
+}
@@ -0,0 +1,326 @@
+use chrono::{DateTime, Utc};
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use super::{INVITATION_VALIDITY_HOURS, PASSWORD_RESET_VALIDITY_MINUTES};
+use crate::domain::UserRole;
+
+pub struct AdminUser {
+    pub user_id: Uuid,
+    pub username: String,
+    pub email: Option<String>,
+    pub role: String,
+    pub is_active: bool,
+    /// False until the invitation has been accepted.
+    pub has_password: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The outcome of a change that could lock everybody out.
+#[derive(Debug, PartialEq, Eq)]
+pub enum UserChange {
+    Done,
+    NotFound,
+    LastActiveOwner,
+}
+
+#[tracing::instrument(name = "List users", skip(pool))]
+pub async fn list_users(pool: &PgPool) -> Result<Vec<AdminUser>, sqlx::Error> {
+    sqlx::query_as!(
+        AdminUser,
+        r#"
+        SELECT
+            user_id,
+            username,
+            email,
+            role,
+            is_active,
+            password_hash IS NOT NULL AS "has_password!",
+            created_at
+        FROM users
+        ORDER BY username
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+#[tracing::instrument(name = "Insert an invited user", skip(transaction))]
+pub async fn insert_invited_user(
+    transaction: &mut Transaction<'static, Postgres>,
+    username: &str,
+    email: &str,
+    role: UserRole,
+) -> Result<Uuid, sqlx::Error> {
+    let user_id = Uuid::new_v4();
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO users (user_id, username, email, password_hash, role, is_active, created_at)
+        VALUES ($1, $2, $3, NULL, $4, true, now())
+        "#,
+        user_id,
+        username,
+        email,
+        role.as_str()
+    );
+    transaction.execute(query).await?;
+    Ok(user_id)
+}
+
+#[tracing::instrument(name = "Insert an invitation", skip_all)]
+pub async fn insert_invitation(
+    transaction: &mut Transaction<'static, Postgres>,
+    user_id: Uuid,
+    token_hash: &str,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO user_invitations (token_hash, user_id, created_at, expires_at)
+        VALUES ($1, $2, now(), now() + make_interval(hours => $3))
+        "#,
+        token_hash,
+        user_id,
+        INVITATION_VALIDITY_HOURS
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Get the user of an invitation", skip_all)]
+pub async fn get_invited_username(
+    pool: &PgPool,
+    token_hash: &str,
+) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT u.username
+        FROM user_invitations i
+        JOIN users u USING (user_id)
+        WHERE i.token_hash = $1 AND i.expires_at > now()
+        "#,
+        token_hash
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Uses up the invitation and sets the first password of the user. Returns
+/// `false` when the invitation is unknown or has expired.
+#[tracing::instrument(name = "Accept an invitation", skip_all)]
+pub async fn accept_invitation(
+    pool: &PgPool,
+    token_hash: &str,
+    password_hash: SecretString,
+) -> Result<bool, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let user_id = sqlx::query_scalar!(
+        r#"
+        DELETE FROM user_invitations
+        WHERE token_hash = $1 AND expires_at > now()
+        RETURNING user_id
+        "#,
+        token_hash
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+    let Some(user_id) = user_id else {
+        return Ok(false);
+    };
+    let query = sqlx::query!(
+        r#"UPDATE users SET password_hash = $1 WHERE user_id = $2"#,
+        password_hash.expose_secret(),
+        user_id
+    );
+    transaction.execute(query).await?;
+    transaction.commit().await?;
+    Ok(true)
+}
+
+/// Locks every owner who can log in, so that two concurrent changes cannot
+/// each leave the other as the last one able to manage users.
+async fn lock_active_owners(
+    transaction: &mut Transaction<'static, Postgres>,
+) -> Result<Vec<Uuid>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT user_id
+        FROM users
+        WHERE is_active AND password_hash IS NOT NULL AND role = 'owner'
+        FOR UPDATE
+        "#
+    )
+    .fetch_all(&mut **transaction)
+    .await
+}
+
+fn is_last_active_owner(active_owners: &[Uuid], user_id: Uuid) -> bool {
+    active_owners == [user_id]
+}
+
+#[tracing::instrument(name = "Enable or disable a user", skip(pool))]
+pub async fn set_user_active(
+    pool: &PgPool,
+    user_id: Uuid,
+    is_active: bool,
+) -> Result<UserChange, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let active_owners = lock_active_owners(&mut transaction).await?;
+    if !is_active && is_last_active_owner(&active_owners, user_id) {
+        return Ok(UserChange::LastActiveOwner);
+    }
+    let query = sqlx::query!(
+        r#"UPDATE users SET is_active = $1 WHERE user_id = $2"#,
+        is_active,
+        user_id
+    );
+    if transaction.execute(query).await?.rows_affected() == 0 {
+        return Ok(UserChange::NotFound);
+    }
+    transaction.commit().await?;
+    Ok(UserChange::Done)
+}
+
+#[tracing::instrument(name = "Delete a user", skip(pool))]
+pub async fn delete_user(pool: &PgPool, user_id: Uuid) -> Result<UserChange, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let active_owners = lock_active_owners(&mut transaction).await?;
+    if is_last_active_owner(&active_owners, user_id) {
+        return Ok(UserChange::LastActiveOwner);
+    }
+    let query = sqlx::query!(r#"DELETE FROM users WHERE user_id = $1"#, user_id);
+    if transaction.execute(query).await?.rows_affected() == 0 {
+        return Ok(UserChange::NotFound);
+    }
+    transaction.commit().await?;
+    Ok(UserChange::Done)
+}
+
+#[tracing::instrument(name = "Change the role of a user", skip(pool))]
+pub async fn set_user_role(
+    pool: &PgPool,
+    user_id: Uuid,
+    role: UserRole,
+) -> Result<UserChange, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let active_owners = lock_active_owners(&mut transaction).await?;
+    if role != UserRole::Owner && is_last_active_owner(&active_owners, user_id) {
+        return Ok(UserChange::LastActiveOwner);
+    }
+    let query = sqlx::query!(
+        r#"UPDATE users SET role = $1 WHERE user_id = $2"#,
+        role.as_str(),
+        user_id
+    );
+    if transaction.execute(query).await?.rows_affected() == 0 {
+        return Ok(UserChange::NotFound);
+    }
+    transaction.commit().await?;
+    Ok(UserChange::Done)
+}
+
+/// A user a password reset link can be sent to.
+pub struct ResetRecipient {
+    pub user_id: Uuid,
+    pub email: String,
+}
+
+#[tracing::instrument(name = "Find the recipient of a password reset", skip(pool))]
+pub async fn get_reset_recipient(
+    pool: &PgPool,
+    username: &str,
+) -> Result<Option<ResetRecipient>, sqlx::Error> {
+    sqlx::query_as!(
+        ResetRecipient,
+        r#"
+        SELECT user_id, email AS "email!"
+        FROM users
+        WHERE username = $1
+            AND is_active
+            AND password_hash IS NOT NULL
+            AND email IS NOT NULL
+        "#,
+        username
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Stores a new reset link, clearing out the links that have expired since.
+#[tracing::instrument(name = "Insert a password reset", skip(pool, token_hash))]
+pub async fn insert_password_reset(
+    pool: &PgPool,
+    user_id: Uuid,
+    token_hash: &str,
+) -> Result<(), sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let query = sqlx::query!(r#"DELETE FROM password_resets WHERE expires_at <= now()"#);
+    transaction.execute(query).await?;
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO password_resets (token_hash, user_id, created_at, expires_at)
+        VALUES ($1, $2, now(), now() + make_interval(mins => $3))
+        "#,
+        token_hash,
+        user_id,
+        PASSWORD_RESET_VALIDITY_MINUTES
+    );
+    transaction.execute(query).await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Check a password reset", skip(pool, token_hash))]
+pub async fn is_valid_password_reset(pool: &PgPool, token_hash: &str) -> Result<bool, sqlx::Error> {
+    let valid = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM password_resets
+            WHERE token_hash = $1 AND expires_at > now()
+        ) AS "valid!"
+        "#,
+        token_hash
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(valid)
+}
+
+/// Uses up the token, along with every other link that was sent to the same
+/// user, and sets the new password in the same transaction. Returns who the
+/// password was changed for.
+#[tracing::instrument(name = "Reset a password", skip(pool, token_hash, password_hash))]
+pub async fn reset_password(
+    pool: &PgPool,
+    token_hash: &str,
+    password_hash: SecretString,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let user_id = sqlx::query_scalar!(
+        r#"
+        WITH reset AS (
+            SELECT user_id FROM password_resets
+            WHERE token_hash = $1 AND expires_at > now()
+        )
+        DELETE FROM password_resets
+        WHERE user_id IN (SELECT user_id FROM reset)
+        RETURNING user_id
+        "#,
+        token_hash
+    )
+    .fetch_all(&mut *transaction)
+    .await?
+    .into_iter()
+    .next();
+    let Some(user_id) = user_id else {
+        return Ok(None);
+    };
+    let query = sqlx::query!(
+        r#"UPDATE users SET password_hash = $1 WHERE user_id = $2"#,
+        password_hash.expose_secret(),
+        user_id
+    );
+    transaction.execute(query).await?;
+    transaction.commit().await?;
+    Ok(Some(user_id))
+}
@@ -0,0 +1,136 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::net::IpAddr;
+use uuid::Uuid;
+
+use crate::configuration::SessionLifetime;
+
+pub struct UserSession {
+    pub session_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+#[tracing::instrument(name = "Insert a user session", skip(pool, user_agent))]
+pub async fn insert_user_session(
+    pool: &PgPool,
+    user_id: Uuid,
+    ip_address: IpAddr,
+    user_agent: Option<&str>,
+    lifetime: SessionLifetime,
+) -> Result<Uuid, sqlx::Error> {
+    let session_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO user_sessions (
+            session_id, user_id, created_at, last_seen_at, ip_address, user_agent,
+            expires_at, inactivity_timeout_seconds
+        )
+        VALUES ($1, $2, now(), now(), $3, $4, now() + make_interval(secs => $5), $6)
+        "#,
+        session_id,
+        user_id,
+        ip_address.to_string(),
+        user_agent,
+        lifetime.absolute_lifetime_seconds as f64,
+        lifetime.inactivity_timeout_seconds as i32
+    )
+    .execute(pool)
+    .await?;
+    Ok(session_id)
+}
+
+/// Returns false if the session has been revoked or has run out.
+#[tracing::instrument(name = "Touch a user session", skip(pool))]
+pub async fn touch_user_session(
+    pool: &PgPool,
+    user_id: Uuid,
+    session_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE user_sessions SET last_seen_at = now()
+        WHERE session_id = $1
+            AND user_id = $2
+            AND expires_at > now()
+            AND last_seen_at > now() - make_interval(secs => inactivity_timeout_seconds)
+        "#,
+        session_id,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Lists the sessions that are still alive, after forgetting those that
+/// have run out.
+#[tracing::instrument(name = "List user sessions", skip(pool))]
+pub async fn list_user_sessions(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Vec<UserSession>, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        DELETE FROM user_sessions
+        WHERE user_id = $1
+            AND (
+                expires_at <= now()
+                OR last_seen_at <= now() - make_interval(secs => inactivity_timeout_seconds)
+            )
+        "#,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query_as!(
+        UserSession,
+        r#"
+        SELECT session_id, created_at, last_seen_at, ip_address, user_agent
+        FROM user_sessions
+        WHERE user_id = $1
+        ORDER BY last_seen_at DESC
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+#[tracing::instrument(name = "Revoke a user session", skip(pool))]
+pub async fn revoke_user_session(
+    pool: &PgPool,
+    user_id: Uuid,
+    session_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "DELETE FROM user_sessions WHERE session_id = $1 AND user_id = $2",
+        session_id,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Logs the user out everywhere, except in `keep` if given.
+#[tracing::instrument(name = "Revoke other user sessions", skip(pool))]
+pub async fn revoke_other_sessions(
+    pool: &PgPool,
+    user_id: Uuid,
+    keep: Option<Uuid>,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM user_sessions
+        WHERE user_id = $1 AND session_id IS DISTINCT FROM $2
+        "#,
+        user_id,
+        keep
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,153 @@
+use wiremock::ResponseTemplate;
+
+use crate::{
+    helpers::{TestApp, spawn_app},
+    newsletter::{create_confirmed_subscriber, when_sending_an_email},
+};
+
+async fn publish_issue(app: &TestApp, title: &str, keep_private: bool) -> String {
+    let mut body = serde_json::json!({
+        "title": title,
+        "text": "Newsletter body as plain text",
+        "html": "<html><body><p>Newsletter body as HTML</p></body></html>",
+        "idempotency_key": uuid::Uuid::new_v4()
+    });
+    if keep_private {
+        body["keep_private"] = "on".into();
+    }
+    let response = app.post_newsletters(&body).await;
+    assert_eq!(response.status().as_u16(), 303);
+    sqlx::query_scalar!("SELECT slug FROM newsletter_issues WHERE title = $1", title)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+async fn get_html(app: &TestApp, path: &str) -> (u16, String) {
+    let response = app
+        .api_client
+        .get(format!("{}{path}", &app.address))
+        .send()
+        .await
+        .unwrap();
+    (response.status().as_u16(), response.text().await.unwrap())
+}
+
+#[tokio::test]
+async fn public_issues_are_listed_and_rendered_in_the_archive() {
+    //Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let slug = publish_issue(&app, "Hello archive", false).await;
+    assert!(slug.starts_with("hello-archive-"));
+
+    //Act
+    let (_, archive) = get_html(&app, "/archive").await;
+    let (status, issue) = get_html(&app, &format!("/archive/{slug}")).await;
+
+    //Assert
+    assert!(archive.contains(&format!(r#"<a href="/archive/{slug}">Hello archive</a>"#)));
+    assert_eq!(status, 200);
+    assert!(issue.contains("<p>Newsletter body as HTML</p>"));
+}
+
+#[tokio::test]
+async fn titles_are_escaped_in_the_archive() {
+    //Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    publish_issue(&app, "<script>alert(1)</script>", false).await;
+
+    //Act
+    let (_, archive) = get_html(&app, "/archive").await;
+
+    //Assert
+    assert!(archive.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+    assert!(!archive.contains("<script>"));
+}
+
+#[tokio::test]
+async fn pages_far_beyond_the_archive_are_not_found() {
+    //Arrange
+    let app = spawn_app().await;
+
+    //Act
+    let (status, _) = get_html(&app, &format!("/archive?page={}", i64::MAX)).await;
+
+    //Assert
+    assert_eq!(status, 404);
+}
+
+#[tokio::test]
+async fn private_issues_are_kept_out_of_the_archive() {
+    //Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let slug = publish_issue(&app, "Members only", true).await;
+
+    //Act
+    let (_, archive) = get_html(&app, "/archive").await;
+    let (status, _) = get_html(&app, &format!("/archive/{slug}")).await;
+
+    //Assert
+    assert!(!archive.contains("Members only"));
+    assert_eq!(status, 404);
+}
+
+#[tokio::test]
+async fn the_archive_is_paginated() {
+    //Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    for i in 0..11 {
+        publish_issue(&app, &format!("Issue number {i}"), false).await;
+    }
+
+    //Act
+    let (_, first_page) = get_html(&app, "/archive").await;
+    let (_, second_page) = get_html(&app, "/archive?page=2").await;
+
+    //Assert
+    assert_eq!(first_page.matches("<li>").count(), 10);
+    assert!(first_page.contains("Issue number 10"));
+    assert!(first_page.contains(r#"href="/archive?page=2""#));
+    assert_eq!(second_page.matches("<li>").count(), 1);
+    assert!(second_page.contains("Issue number 0"));
+    assert!(second_page.contains(r#"href="/archive?page=1""#));
+    assert!(!second_page.contains(r#"href="/archive?page=3""#));
+}
+
+#[tokio::test]
+async fn emails_link_to_the_archived_issue() {
+    //Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let slug = publish_issue(&app, "In the browser", false).await;
+    let _mock_guard = when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    //Act
+    app.dispatch_all_pending_emails().await;
+
+    //Assert
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let url = format!("http://{}/archive/{slug}", app.base_url.0);
+    assert!(body["HtmlBody"].as_str().unwrap().contains(&url));
+    assert!(
+        body["TextBody"]
+            .as_str()
+            .unwrap()
+            .starts_with(&format!("View this issue in your browser: {url}"))
+    );
+}
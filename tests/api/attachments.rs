@@ -0,0 +1,201 @@
+use reqwest::multipart::{Form, Part};
+use wiremock::ResponseTemplate;
+use zero2prod::issue_delivery_worker::{AttachmentCache, try_execute_task};
+
+use crate::{
+    helpers::{assert_is_redirect_to, spawn_app},
+    newsletter::{create_confirmed_subscriber, when_sending_an_email},
+};
+
+const PDF: &[u8] = b"%PDF-1.7\n%%EOF\n";
+
+fn issue_form() -> Form {
+    Form::new()
+        .text("title", "Newsletter title")
+        .text("text", "Newsletter body as plain text")
+        .text("html", "<p>Newsletter body as HTML</p>")
+        .text("idempotency_key", uuid::Uuid::new_v4().to_string())
+}
+
+fn file(file_name: &str, content_type: &str, content: &[u8]) -> Part {
+    Part::bytes(content.to_vec())
+        .file_name(file_name.to_string())
+        .mime_str(content_type)
+        .unwrap()
+}
+
+#[tokio::test]
+async fn attachments_are_sent_with_the_issue() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let form = issue_form()
+        .part("attachments", file("report.pdf", "application/pdf", PDF))
+        .part("attachments", file("notes.txt", "text/plain", b"hello"));
+
+    // Act
+    let response = app.post_newsletters_multipart(form).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let _mock_guard = when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(
+        body["Attachments"],
+        serde_json::json!([
+            {
+                "Name": "report.pdf",
+                "Content": "JVBERi0xLjcKJSVFT0YK",
+                "ContentType": "application/pdf"
+            },
+            {
+                "Name": "notes.txt",
+                "Content": "aGVsbG8=",
+                "ContentType": "text/plain"
+            }
+        ])
+    );
+}
+
+#[tokio::test]
+async fn an_empty_file_input_is_ignored() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let form = issue_form().part("attachments", file("", "application/octet-stream", b""));
+
+    // Act
+    let response = app.post_newsletters_multipart(form).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("The newsletter issue has been published!"));
+}
+
+#[tokio::test]
+async fn unsupported_attachments_are_rejected_before_anything_is_stored() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let test_cases = [
+        (
+            file("tool.exe", "application/octet-stream", b"MZ\x90\x00"),
+            "tool.exe has an unsupported file type (application/octet-stream).",
+        ),
+        (
+            file("report.pdf", "application/pdf", b"MZ\x90\x00"),
+            "The content of report.pdf does not match its file type (application/pdf).",
+        ),
+    ];
+
+    for (part, message) in test_cases {
+        // Act
+        let response = app
+            .post_newsletters_multipart(issue_form().part("attachments", part))
+            .await;
+
+        // Assert
+        assert_is_redirect_to(&response, "/admin/newsletters");
+        let html_page = app.get_newsletter_html().await;
+        assert!(html_page.contains(message), "{message} was not reported");
+    }
+    let issues = sqlx::query_scalar!(r#"SELECT count(*) AS "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(issues, 0);
+}
+
+#[tokio::test]
+async fn attachments_are_loaded_once_per_issue() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let form = issue_form().part("attachments", file("notes.txt", "text/plain", b"hello"));
+    let response = app.post_newsletters_multipart(form).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.expand_all_audiences().await;
+    let _mock_guard = when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let mut attachments = AttachmentCache::default();
+    let execute_task = async |attachments: &mut AttachmentCache| {
+        try_execute_task(
+            &app.db_pool,
+            &app.email_client,
+            &app.base_url,
+            &app.tracking,
+            attachments,
+        )
+        .await
+        .unwrap()
+    };
+
+    // Act - the stored attachments go away after the first delivery
+    execute_task(&mut attachments).await;
+    sqlx::query!("DELETE FROM issue_attachments")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    execute_task(&mut attachments).await;
+
+    // Assert
+    let requests = app.email_server.received_requests().await.unwrap();
+    for email_request in &requests[requests.len() - 2..] {
+        let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+        assert_eq!(body["Attachments"][0]["Name"], "notes.txt");
+    }
+}
+
+#[tokio::test]
+async fn unreadable_attachments_are_named_in_the_error() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let csrf_token = app.csrf_token(&app.api_client).await;
+    let body = format!(
+        "--X\r\nContent-Disposition: form-data; name=\"csrf_token\"\r\n\r\n{csrf_token}\r\n\
+         --X\r\nContent-Disposition: form-data; name=\"attachments\"; filename=\"report.pdf\"\r\n\
+         Content-Type: application/pdf\r\n\r\n%PDF-1.7"
+    );
+
+    // Act - the body ends in the middle of the file
+    let response = app
+        .api_client
+        .post(format!("{}/admin/newsletters", &app.address))
+        .header("Content-Type", "multipart/form-data; boundary=X")
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(
+        response
+            .text()
+            .await
+            .unwrap()
+            .starts_with("Failed to read the attachment report.pdf:")
+    );
+}
@@ -0,0 +1,180 @@
+use wiremock::ResponseTemplate;
+use zero2prod::{
+    audience::try_expand_audience, issue_delivery_worker::ExecutionOutcome,
+    subject_tests::try_finalize_subject_test,
+};
+
+use crate::{
+    helpers::{TestApp, assert_is_redirect_to, spawn_app},
+    newsletter::{create_confirmed_subscriber, when_sending_an_email},
+};
+
+async fn publish(app: &TestApp) {
+    let response = app
+        .post_newsletters(&serde_json::json!({
+            "title": "Newsletter title",
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4()
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+}
+
+async fn queue_length(app: &TestApp) -> i64 {
+    sqlx::query_scalar!(r#"SELECT count(*) AS "count!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+/// Runs the expansion until the snapshot is complete and the first chunk of the
+/// audience is in the queue.
+async fn enqueue_first_chunk(app: &TestApp, chunk_size: i64) {
+    while queue_length(app).await == 0 {
+        try_expand_audience(&app.db_pool, chunk_size).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn publishing_leaves_the_expansion_to_the_background_job() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    publish(&app).await;
+
+    assert_eq!(queue_length(&app).await, 0);
+    let pending = sqlx::query_scalar!(
+        r#"SELECT count(*) AS "count!" FROM audience_expansions WHERE completed_at IS NULL"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(pending, 1);
+}
+
+#[tokio::test]
+async fn the_audience_is_enqueued_one_chunk_at_a_time() {
+    let app = spawn_app().await;
+    for _ in 0..5 {
+        create_confirmed_subscriber(&app).await;
+    }
+    app.test_user.login(&app).await;
+    publish(&app).await;
+
+    // The snapshot is copied first, then every chunk is committed with its
+    // cursor, a restart picks up from there.
+    for expected in [0, 0, 2, 4, 5] {
+        let outcome = try_expand_audience(&app.db_pool, 2).await.unwrap();
+        assert!(matches!(outcome, ExecutionOutcome::TaskCompleted));
+        assert_eq!(queue_length(&app).await, expected);
+    }
+    let outcome = try_expand_audience(&app.db_pool, 2).await.unwrap();
+    assert!(matches!(outcome, ExecutionOutcome::EmptyQueue));
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(5)
+        .mount(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn subscribers_who_join_after_publishing_are_not_in_the_audience() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    publish(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.expand_all_audiences().await;
+
+    assert_eq!(queue_length(&app).await, 1);
+}
+
+#[tokio::test]
+async fn chunks_enqueued_after_the_subject_test_get_the_winner() {
+    let app = spawn_app().await;
+    for _ in 0..10 {
+        create_confirmed_subscriber(&app).await;
+    }
+    app.test_user.login(&app).await;
+    app.post_newsletters(&serde_json::json!({
+        "title": "Subject A",
+        "text": "Newsletter body as plain text",
+        "html": "<p>Newsletter body as HTML</p>",
+        "subject_variants": "Subject B",
+        "test_percentage": "20",
+        "track_opens": "on",
+        "idempotency_key": uuid::Uuid::new_v4()
+    }))
+    .await;
+    // The first chunk is exactly the test slice
+    enqueue_first_chunk(&app, 2).await;
+    sqlx::query!("UPDATE subject_tests SET decide_after = now()")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    try_finalize_subject_test(&app.db_pool).await.unwrap();
+
+    app.expand_all_audiences().await;
+
+    let rows = sqlx::query!(
+        r#"
+        SELECT q.held, t.winning_variant_id = q.subject_variant_id AS "is_winner!"
+        FROM issue_delivery_queue q
+        JOIN subject_tests t USING (newsletter_issue_id)
+        "#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(rows.len(), 10);
+    assert!(rows.iter().all(|r| !r.held));
+    assert_eq!(rows.iter().filter(|r| r.is_winner).count(), 9);
+}
+
+#[tokio::test]
+async fn the_audience_is_fixed_on_the_first_chunk() {
+    let app = spawn_app().await;
+    for _ in 0..4 {
+        create_confirmed_subscriber(&app).await;
+    }
+    app.test_user.login(&app).await;
+    publish(&app).await;
+    enqueue_first_chunk(&app, 2).await;
+
+    // Somebody who is not enqueued yet leaves before the next chunk.
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = 'unsubscribed'
+        WHERE id = (
+            SELECT id FROM subscriptions
+            WHERE email NOT IN (SELECT subscriber_email FROM issue_delivery_queue)
+            LIMIT 1
+        )
+        "#
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.expand_all_audiences().await;
+
+    assert_eq!(queue_length(&app).await, 3);
+    let expansion = sqlx::query!(
+        r#"
+        SELECT audience_size AS "audience_size!", expanded,
+            (SELECT count(*) FROM audience_snapshots) AS "snapshot_rows!"
+        FROM audience_expansions
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(expansion.audience_size, 4);
+    assert_eq!(expansion.expanded, 4);
+    assert_eq!(expansion.snapshot_rows, 0);
+}
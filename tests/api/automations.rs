@@ -0,0 +1,257 @@
+use wiremock::ResponseTemplate;
+
+use crate::{
+    helpers::{TestApp, assert_is_redirect_to, spawn_app},
+    newsletter::{create_unconfirmed_subscriber, when_sending_an_email},
+};
+
+/// Creates an automation with an immediate welcome email and a follow up after two days.
+async fn create_welcome_sequence(app: &TestApp) -> uuid::Uuid {
+    let response = app
+        .post_form(
+            "/admin/automations",
+            &serde_json::json!({ "name": "Welcome" }),
+        )
+        .await;
+    let automation_id: uuid::Uuid = sqlx::query_scalar!("SELECT automation_id FROM automations")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, &format!("/admin/automations/{automation_id}"));
+
+    for (subject, delay, unit) in [
+        ("Thanks for joining", 0, "hours"),
+        ("Our best posts", 2, "days"),
+    ] {
+        let response = app
+            .post_form(
+                &format!("/admin/automations/{automation_id}/steps"),
+                &serde_json::json!({
+                    "subject": subject,
+                    "delay": delay,
+                    "delay_unit": unit,
+                    "html": format!("<p>{subject}</p>"),
+                    "text": "",
+                }),
+            )
+            .await;
+        assert_is_redirect_to(&response, &format!("/admin/automations/{automation_id}"));
+    }
+    automation_id
+}
+
+async fn pending_subjects(app: &TestApp) -> Vec<String> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT i.title
+        FROM issue_delivery_queue q
+        JOIN newsletter_issues i USING (newsletter_issue_id)
+        ORDER BY q.execute_after
+        "#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap()
+}
+
+/// The bodies of the emails sent after the confirmation email.
+async fn sent_emails(app: &TestApp) -> Vec<serde_json::Value> {
+    app.email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .skip(1)
+        .map(|r| serde_json::from_slice(&r.body).unwrap())
+        .collect()
+}
+
+async fn sent_subjects(app: &TestApp) -> Vec<String> {
+    sent_emails(app)
+        .await
+        .iter()
+        .map(|body| body["Subject"].as_str().unwrap().to_owned())
+        .collect()
+}
+
+#[tokio::test]
+async fn steps_are_sent_after_their_delay_once_a_subscriber_confirms() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_welcome_sequence(&app).await;
+    let confirmation_links = create_unconfirmed_subscriber(&app).await;
+
+    // Act - confirm
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let _mock_guard = when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert - only the immediate step went out
+    assert_eq!(sent_subjects(&app).await, ["Thanks for joining"]);
+    assert_eq!(pending_subjects(&app).await, ["Our best posts"]);
+
+    // Act - two days later
+    sqlx::query!("UPDATE issue_delivery_queue SET execute_after = now()")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_eq!(
+        sent_subjects(&app).await,
+        ["Thanks for joining", "Our best posts"]
+    );
+    assert!(pending_subjects(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn confirming_twice_does_not_restart_the_sequence() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_welcome_sequence(&app).await;
+    let confirmation_links = create_unconfirmed_subscriber(&app).await;
+    reqwest::get(confirmation_links.html.clone()).await.unwrap();
+    let _mock_guard = when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Act
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    assert_eq!(pending_subjects(&app).await, ["Our best posts"]);
+}
+
+#[tokio::test]
+async fn paused_automations_do_not_enroll_new_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let automation_id = create_welcome_sequence(&app).await;
+    app.post_form(
+        &format!("/admin/automations/{automation_id}/toggle"),
+        &serde_json::json!({ "is_active": false }),
+    )
+    .await;
+    let confirmation_links = create_unconfirmed_subscriber(&app).await;
+
+    // Act
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    assert!(pending_subjects(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn unsubscribing_cancels_the_pending_steps() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_welcome_sequence(&app).await;
+    let confirmation_links = create_unconfirmed_subscriber(&app).await;
+    reqwest::get(confirmation_links.html).await.unwrap();
+    let _mock_guard = when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+    let welcome = sent_emails(&app).await.remove(0);
+    let unsubscribe_link = linkify::LinkFinder::new()
+        .links(welcome["TextBody"].as_str().unwrap())
+        .map(|l| l.as_str().to_owned())
+        .find(|l| l.contains("/subscriptions/unsubscribe"))
+        .expect("No unsubscribe link in the welcome email");
+    let token = reqwest::Url::parse(&unsubscribe_link)
+        .unwrap()
+        .query_pairs()
+        .find(|(key, _)| key == "token")
+        .unwrap()
+        .1
+        .into_owned();
+
+    // Act
+    let response = app
+        .post_form(
+            "/subscriptions/unsubscribe",
+            &serde_json::json!({ "token": token }),
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(pending_subjects(&app).await.is_empty());
+    let status = sqlx::query_scalar!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(status, "unsubscribed");
+}
+
+#[tokio::test]
+async fn automation_messages_are_not_listed_as_issues() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_welcome_sequence(&app).await;
+
+    // Act
+    let archive = app
+        .api_client
+        .get(format!("{}/archive", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let issues = app
+        .api_client
+        .get(format!("{}/admin/issues", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(!archive.contains("Thanks for joining"));
+    assert!(!issues.contains("Thanks for joining"));
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_automations() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .post_form(
+            "/admin/automations",
+            &serde_json::json!({ "name": "Welcome" }),
+        )
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
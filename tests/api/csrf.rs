@@ -0,0 +1,109 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+#[tokio::test]
+async fn forms_carry_the_csrf_token_of_the_session() {
+    let app = spawn_app().await;
+    let token = app.csrf_token(&app.api_client).await;
+
+    let home = app
+        .api_client
+        .get(format!("{}/", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    assert!(home.contains(r#"action="/subscriptions""#));
+    assert!(home.contains(&format!(r#"name="csrf_token" value="{token}""#)));
+}
+
+#[tokio::test]
+async fn a_login_without_the_csrf_token_is_rejected() {
+    let app = spawn_app().await;
+    app.get_login_html().await;
+
+    let response = app
+        .api_client
+        .post(format!("{}/login", &app.address))
+        .form(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 403);
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/login");
+}
+
+#[tokio::test]
+async fn a_subscription_without_the_csrf_token_is_rejected() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions", &app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 403);
+    let subscribers = sqlx::query_scalar!(r#"SELECT count(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscribers, 0);
+}
+
+#[tokio::test]
+async fn the_token_of_another_session_is_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    // What a forged form would carry: a token the attacker got for themselves.
+    let other_client = reqwest::Client::builder()
+        .cookie_store(true)
+        .build()
+        .unwrap();
+    let other_token = app.csrf_token(&other_client).await;
+
+    let response = app
+        .api_client
+        .post(format!("{}/admin/logout", &app.address))
+        .form(&serde_json::json!({ "csrf_token": other_token }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn a_multipart_form_without_the_csrf_token_is_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let form = reqwest::multipart::Form::new()
+        .text("title", "Newsletter title")
+        .text("html", "<p>Newsletter body as HTML</p>")
+        .text("idempotency_key", uuid::Uuid::new_v4().to_string());
+
+    let response = app
+        .api_client
+        .post(format!("{}/admin/newsletters", &app.address))
+        .multipart(form)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 403);
+    let issues = sqlx::query_scalar!(r#"SELECT count(*) AS "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(issues, 0);
+}
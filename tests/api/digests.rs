@@ -0,0 +1,230 @@
+use chrono::{TimeDelta, Utc};
+use wiremock::ResponseTemplate;
+use zero2prod::digests::try_build_digest;
+use zero2prod::domain::DeliveryFrequency;
+use zero2prod::issue_delivery_worker::ExecutionOutcome;
+
+use crate::{
+    helpers::{TestApp, assert_is_redirect_to, spawn_app},
+    newsletter::{create_unconfirmed_subscriber, when_sending_an_email},
+};
+
+/// Creates a confirmed subscriber and returns their unsubscribe token.
+async fn create_subscriber(app: &TestApp, delivery_frequency: &str) -> String {
+    let confirmation_link = create_unconfirmed_subscriber(app).await;
+    reqwest::get(confirmation_link.html.clone())
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let subscription_token = confirmation_link
+        .html
+        .query_pairs()
+        .find(|(key, _)| key == "subscription_token")
+        .map(|(_, value)| value.into_owned())
+        .unwrap();
+    let token = sqlx::query_scalar!(
+        r#"
+        SELECT s.unsubscribe_token
+        FROM subscriptions s
+        JOIN subscription_tokens t ON t.subscriber_id = s.id
+        WHERE t.subscription_token = $1
+        "#,
+        subscription_token
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    let response = post_preferences(app, &token, delivery_frequency).await;
+    assert_eq!(response.status().as_u16(), 200);
+    token
+}
+
+async fn post_preferences(
+    app: &TestApp,
+    token: &str,
+    delivery_frequency: &str,
+) -> reqwest::Response {
+    app.post_form(
+        "/subscriptions/preferences",
+        &serde_json::json!({
+            "token": token,
+            "delivery_frequency": delivery_frequency,
+        }),
+    )
+    .await
+}
+
+async fn publish(app: &TestApp, title: &str) {
+    let response = app
+        .post_newsletters(&serde_json::json!({
+            "title": title,
+            "text": format!("{title} as plain text"),
+            "html": format!("<p>{title} as HTML</p>"),
+            "idempotency_key": uuid::Uuid::new_v4()
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+}
+
+async fn queued_subscribers(app: &TestApp) -> i64 {
+    app.expand_all_audiences().await;
+    sqlx::query_scalar!(r#"SELECT count(*) AS "count!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn digest_subscribers_are_left_out_of_immediate_sends() {
+    let app = spawn_app().await;
+    create_subscriber(&app, "immediate").await;
+    create_subscriber(&app, "weekly").await;
+    app.test_user.login(&app).await;
+
+    publish(&app, "First issue").await;
+
+    assert_eq!(queued_subscribers(&app).await, 1);
+}
+
+#[tokio::test]
+async fn the_digest_combines_the_issues_of_the_period() {
+    let app = spawn_app().await;
+    create_subscriber(&app, "daily").await;
+    create_subscriber(&app, "immediate").await;
+    app.test_user.login(&app).await;
+    // Two issues for the immediate subscriber, then the digest
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(3)
+        .mount(&app.email_server)
+        .await;
+    publish(&app, "First issue").await;
+    publish(&app, "Second issue").await;
+    app.dispatch_all_pending_emails().await;
+
+    try_build_digest(
+        &app.db_pool,
+        DeliveryFrequency::Daily,
+        Utc::now() + TimeDelta::days(1),
+    )
+    .await
+    .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&requests.last().unwrap().body).unwrap();
+    assert!(
+        body["Subject"]
+            .as_str()
+            .unwrap()
+            .starts_with("Daily digest: ")
+    );
+    let html = body["HtmlBody"].as_str().unwrap();
+    let first = html.find("<h2>First issue</h2>").unwrap();
+    let second = html.find("<h2>Second issue</h2>").unwrap();
+    assert!(first < second);
+    assert!(
+        body["TextBody"]
+            .as_str()
+            .unwrap()
+            .contains("Second issue as plain text")
+    );
+}
+
+#[tokio::test]
+async fn a_digest_is_built_once_per_period() {
+    let app = spawn_app().await;
+    create_subscriber(&app, "weekly").await;
+    app.test_user.login(&app).await;
+    publish(&app, "First issue").await;
+
+    let now = Utc::now() + TimeDelta::weeks(1);
+    try_build_digest(&app.db_pool, DeliveryFrequency::Weekly, now)
+        .await
+        .unwrap();
+    try_build_digest(&app.db_pool, DeliveryFrequency::Weekly, now)
+        .await
+        .unwrap();
+
+    assert_eq!(queued_subscribers(&app).await, 1);
+}
+
+#[tokio::test]
+async fn periods_missed_while_the_scheduler_was_down_are_caught_up() {
+    let app = spawn_app().await;
+    create_subscriber(&app, "daily").await;
+    app.test_user.login(&app).await;
+    // Yesterday's digest went out, nothing was published then.
+    try_build_digest(&app.db_pool, DeliveryFrequency::Daily, Utc::now())
+        .await
+        .unwrap();
+    publish(&app, "First issue").await;
+
+    // The scheduler comes back two days later.
+    let now = Utc::now() + TimeDelta::days(2);
+    let mut built = 0;
+    while let ExecutionOutcome::TaskCompleted =
+        try_build_digest(&app.db_pool, DeliveryFrequency::Daily, now)
+            .await
+            .unwrap()
+    {
+        built += 1;
+    }
+
+    // Today and tomorrow each got their digest, today's has the issue.
+    assert_eq!(built, 2);
+    assert_eq!(queued_subscribers(&app).await, 1);
+    let digests = sqlx::query_scalar!(r#"SELECT count(*) AS "count!" FROM digests"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(digests, 3);
+}
+
+#[tokio::test]
+async fn no_digest_is_sent_when_nothing_was_published() {
+    let app = spawn_app().await;
+    create_subscriber(&app, "daily").await;
+
+    try_build_digest(
+        &app.db_pool,
+        DeliveryFrequency::Daily,
+        Utc::now() + TimeDelta::days(1),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(queued_subscribers(&app).await, 0);
+}
+
+#[tokio::test]
+async fn the_preferences_page_shows_the_current_frequency() {
+    let app = spawn_app().await;
+    let token = create_subscriber(&app, "weekly").await;
+
+    let html = app
+        .api_client
+        .get(format!(
+            "{}/subscriptions/preferences?token={token}",
+            &app.address
+        ))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    assert!(html.contains(r#"value="weekly" checked"#));
+    assert!(!html.contains(r#"value="immediate" checked"#));
+}
+
+#[tokio::test]
+async fn unknown_tokens_cannot_change_preferences() {
+    let app = spawn_app().await;
+
+    let response = post_preferences(&app, "notarealtoken", "daily").await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -0,0 +1,115 @@
+use crate::helpers::{TestApp, spawn_app};
+
+/// Already sanitized, so it is stored unchanged.
+const HTML: &str = "<p>Fish &amp; chips ]]&gt; <b>bold</b></p>";
+
+async fn publish_issue(app: &TestApp, title: &str, keep_private: bool) -> uuid::Uuid {
+    let mut body = serde_json::json!({
+        "title": title,
+        "text": "Newsletter body as plain text",
+        "html": HTML,
+        "idempotency_key": uuid::Uuid::new_v4()
+    });
+    if keep_private {
+        body["keep_private"] = "on".into();
+    }
+    let response = app.post_newsletters(&body).await;
+    assert_eq!(response.status().as_u16(), 303);
+    sqlx::query_scalar!(
+        "SELECT newsletter_issue_id FROM newsletter_issues WHERE title = $1",
+        title
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+}
+
+async fn get_feed(app: &TestApp, path: &str) -> (String, Vec<u8>) {
+    let response = app
+        .api_client
+        .get(format!("{}{path}", &app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let content_type = response.headers()["Content-Type"]
+        .to_str()
+        .unwrap()
+        .to_owned();
+    (content_type, response.bytes().await.unwrap().to_vec())
+}
+
+#[tokio::test]
+async fn the_rss_feed_contains_public_issues() {
+    //Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let issue_id = publish_issue(&app, "Public issue", false).await;
+    publish_issue(&app, "Private issue", true).await;
+
+    //Act
+    let (content_type, body) = get_feed(&app, "/feed.rss").await;
+
+    //Assert
+    assert_eq!(content_type, "application/rss+xml; charset=utf-8");
+    let channel = rss::Channel::read_from(&body[..]).unwrap();
+    assert_eq!(channel.items().len(), 1);
+    let item = &channel.items()[0];
+    assert_eq!(item.title(), Some("Public issue"));
+    let guid = item.guid().unwrap();
+    assert_eq!(guid.value(), format!("urn:uuid:{issue_id}"));
+    assert!(!guid.is_permalink());
+    assert_eq!(item.description(), Some(HTML));
+    let published_at = chrono::DateTime::parse_from_rfc2822(item.pub_date().unwrap()).unwrap();
+    assert!(chrono::Utc::now() - published_at.to_utc() < chrono::Duration::minutes(1));
+    assert!(item.link().unwrap().contains("/archive/public-issue-"));
+}
+
+#[tokio::test]
+async fn the_atom_feed_contains_public_issues() {
+    //Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let issue_id = publish_issue(&app, "Public issue", false).await;
+    publish_issue(&app, "Private issue", true).await;
+
+    //Act
+    let (content_type, body) = get_feed(&app, "/feed.atom").await;
+
+    //Assert
+    assert_eq!(content_type, "application/atom+xml; charset=utf-8");
+    let feed = atom_syndication::Feed::read_from(&body[..]).unwrap();
+    assert_eq!(feed.entries().len(), 1);
+    let entry = &feed.entries()[0];
+    assert_eq!(entry.title().as_str(), "Public issue");
+    assert_eq!(entry.id(), format!("urn:uuid:{issue_id}"));
+    assert_eq!(entry.published(), Some(entry.updated()));
+    assert_eq!(feed.updated(), entry.updated());
+    let content = entry.content().unwrap();
+    assert_eq!(content.content_type(), Some("html"));
+    assert_eq!(content.value(), Some(HTML));
+}
+
+#[tokio::test]
+async fn feeds_are_valid_without_any_issues() {
+    //Arrange
+    let app = spawn_app().await;
+
+    //Act
+    let (_, rss_body) = get_feed(&app, "/feed.rss").await;
+    let (_, atom_body) = get_feed(&app, "/feed.atom").await;
+
+    //Assert
+    assert!(
+        rss::Channel::read_from(&rss_body[..])
+            .unwrap()
+            .items()
+            .is_empty()
+    );
+    assert!(
+        atom_syndication::Feed::read_from(&atom_body[..])
+            .unwrap()
+            .entries()
+            .is_empty()
+    );
+}
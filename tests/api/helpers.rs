@@ -5,14 +5,18 @@ use argon2::{
 };
 use once_cell::sync::Lazy;
 use reqwest::Url;
+use secrecy::ExposeSecret;
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 use uuid::Uuid;
 use wiremock::MockServer;
+use zero2prod::audience::{CHUNK_SIZE, try_expand_audience};
 use zero2prod::email_client::EmailClient;
-use zero2prod::issue_delivery_worker::{ExecutionOutcome, try_execute_task};
+use zero2prod::issue_delivery_worker::{AttachmentCache, ExecutionOutcome, try_execute_task};
 use zero2prod::{
-    configuration::{DatabaseSettings, get_configuration},
-    startup::{Application, get_connection_pool},
+    configuration::{
+        DatabaseSettings, Settings, TrackingSettings, WebhookSettings, get_configuration,
+    },
+    startup::{Application, ApplicationBaseUrl, get_connection_pool},
     telemetry::{get_subscriber, init_subscriber},
 };
 
@@ -37,9 +41,17 @@ pub struct TestApp {
     pub test_user: TestUser,
     pub api_client: reqwest::Client,
     pub email_client: EmailClient,
+    pub webhooks: WebhookSettings,
+    pub base_url: ApplicationBaseUrl,
+    pub tracking: TrackingSettings,
 }
 
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
+
+/// Like `spawn_app`, with a chance to change the configuration first.
+pub async fn spawn_app_with(configure: impl FnOnce(&mut Settings)) -> TestApp {
     Lazy::force(&TRACING);
 
     let email_server = MockServer::start().await;
@@ -48,6 +60,14 @@ pub async fn spawn_app() -> TestApp {
         c.database.database_name = Uuid::new_v4().to_string();
         c.application.port = 0;
         c.email_client.base_url = email_server.uri();
+        c.media.storage_path = std::env::temp_dir()
+            .join(Uuid::new_v4().to_string())
+            .to_string_lossy()
+            .into_owned();
+        // Every app counts its own failed logins, they all come from 127.0.0.1.
+        c.login_throttle.key_prefix = Uuid::new_v4().to_string();
+        c.password_reset_throttle.key_prefix = Uuid::new_v4().to_string();
+        configure(&mut c);
         c
     };
 
@@ -73,6 +93,9 @@ pub async fn spawn_app() -> TestApp {
         api_client: client,
         test_user: TestUser::generate(),
         email_client: configuration.email_client.client(),
+        webhooks: configuration.webhooks.clone(),
+        base_url: ApplicationBaseUrl(configuration.application.base_url.clone()),
+        tracking: configuration.tracking.clone(),
     };
     test_app.test_user.store(&test_app.db_pool).await;
 
@@ -103,12 +126,26 @@ pub async fn configure_database(config: &DatabaseSettings) -> PgPool {
 }
 
 impl TestApp {
+    pub async fn expand_all_audiences(&self) {
+        while let ExecutionOutcome::TaskCompleted = try_expand_audience(&self.db_pool, CHUNK_SIZE)
+            .await
+            .unwrap()
+        {}
+    }
+
     pub async fn dispatch_all_pending_emails(&self) {
+        self.expand_all_audiences().await;
+        let mut attachments = AttachmentCache::default();
         loop {
-            if let ExecutionOutcome::EmptyQueue =
-                try_execute_task(&self.db_pool, &self.email_client)
-                    .await
-                    .unwrap()
+            if let ExecutionOutcome::EmptyQueue = try_execute_task(
+                &self.db_pool,
+                &self.email_client,
+                &self.base_url,
+                &self.tracking,
+                &mut attachments,
+            )
+            .await
+            .unwrap()
             {
                 break;
             }
@@ -116,10 +153,11 @@ impl TestApp {
     }
 
     pub async fn post_subscriptions(&self, body: String) -> reqwest::Response {
+        let csrf_token = self.csrf_token(&self.api_client).await;
         self.api_client
             .post(format!("{}/subscriptions", &self.address))
             .header("Content-Type", "application/x-www-form-urlencoded")
-            .body(body)
+            .body(format!("{body}&csrf_token={csrf_token}"))
             .send()
             .await
             .expect("Failed to execute request")
@@ -149,27 +187,93 @@ impl TestApp {
         }
     }
 
+    /// The anti-forgery token of the session of `client`, as rendered into
+    /// every form.
+    pub async fn csrf_token(&self, client: &reqwest::Client) -> String {
+        let html = client
+            .get(format!("{}/login", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap();
+        let marker = r#"name="csrf_token" value=""#;
+        let start = html.find(marker).expect("No CSRF token in the form") + marker.len();
+        let end = start + html[start..].find('"').unwrap();
+        html[start..end].to_string()
+    }
+
+    /// Submits a form the way a browser would, with the CSRF token of the session.
+    pub async fn post_form<Body>(&self, path: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_form_with(&self.api_client, path, body).await
+    }
+
+    pub async fn post_form_with<Body>(
+        &self,
+        client: &reqwest::Client,
+        path: &str,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let mut form = serde_json::to_value(body).unwrap();
+        form["csrf_token"] = self.csrf_token(client).await.into();
+        client
+            .post(format!("{}{path}", &self.address))
+            .form(&form)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_newsletters(&self, body: &serde_json::Value) -> reqwest::Response {
+        self.post_form("/admin/newsletters", body).await
+    }
+
+    pub async fn post_newsletters_multipart(
+        &self,
+        form: reqwest::multipart::Form,
+    ) -> reqwest::Response {
         self.api_client
             .post(format!("{}/admin/newsletters", &self.address))
-            .form(&body)
+            .multipart(form.text("csrf_token", self.csrf_token(&self.api_client).await))
             .send()
             .await
             .expect("Failed to execute request")
     }
 
-    pub async fn post_login<Body>(&self, body: &Body) -> reqwest::Response
-    where
-        Body: serde::Serialize,
-    {
+    pub async fn post_media(&self, form: reqwest::multipart::Form) -> reqwest::Response {
         self.api_client
-            .post(format!("{}/login", &self.address))
-            .form(body)
+            .post(format!("{}/admin/media", &self.address))
+            .multipart(form.text("csrf_token", self.csrf_token(&self.api_client).await))
             .send()
             .await
             .expect("Failed to execute request")
     }
 
+    pub async fn get_media_page_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/media", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_login<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_form("/login", body).await
+    }
+
     pub async fn get_login_html(&self) -> String {
         self.api_client
             .get(format!("{}/login", &self.address))
@@ -205,33 +309,87 @@ impl TestApp {
     where
         Body: serde::Serialize,
     {
+        self.post_form("/admin/password", body).await
+    }
+
+    pub async fn get_change_password_html(&self) -> String {
+        self.get_change_password().await.text().await.unwrap()
+    }
+
+    pub async fn post_logout(&self) -> reqwest::Response {
+        self.post_form("/admin/logout", &serde_json::json!({}))
+            .await
+    }
+
+    pub async fn get_newsletter(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/newsletters", &self.address))
+            .send()
+            .await
+            .expect("failed to execute request.")
+    }
+    pub async fn post_postmark_webhook(&self, body: &serde_json::Value) -> reqwest::Response {
         self.api_client
-            .post(format!("{}/admin/password", &self.address))
-            .form(body)
+            .post(format!("{}/webhooks/postmark", &self.address))
+            .basic_auth(
+                &self.webhooks.username,
+                Some(self.webhooks.shared_secret.expose_secret()),
+            )
+            .json(body)
             .send()
             .await
             .expect("failed to execute request.")
     }
 
-    pub async fn get_change_password_html(&self) -> String {
-        self.get_change_password().await.text().await.unwrap()
+    pub async fn post_suppression<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_form("/admin/suppressions", body).await
     }
 
-    pub async fn post_logout(&self) -> reqwest::Response {
+    pub async fn post_suppression_import(&self, csv: &str) -> reqwest::Response {
+        self.post_form(
+            "/admin/suppressions/import",
+            &serde_json::json!({ "csv": csv }),
+        )
+        .await
+    }
+
+    pub async fn get_suppressions(&self) -> reqwest::Response {
         self.api_client
-            .post(format!("{}/admin/logout", &self.address))
+            .get(format!("{}/admin/suppressions", &self.address))
             .send()
             .await
             .expect("failed to execute request.")
     }
 
-    pub async fn get_newsletter(&self) -> reqwest::Response {
+    pub async fn get_suppressions_export(&self) -> reqwest::Response {
         self.api_client
-            .get(format!("{}/admin/newsletters", &self.address))
+            .get(format!("{}/admin/suppressions/export", &self.address))
             .send()
             .await
             .expect("failed to execute request.")
     }
+
+    pub async fn post_layout<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_form("/admin/layouts", body).await
+    }
+
+    pub async fn get_layouts_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/layouts", &self.address))
+            .send()
+            .await
+            .expect("failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
     pub async fn get_newsletter_html(&self) -> String {
         self.get_newsletter()
             .await
@@ -287,8 +445,8 @@ impl TestUser {
         .to_string();
 
         sqlx::query!(
-            "INSERT INTO users (user_id, username, password_hash)
-            VALUES ($1, $2, $3)",
+            "INSERT INTO users (user_id, username, password_hash, role)
+            VALUES ($1, $2, $3, 'owner')",
             self.user_id,
             self.username,
             password_hash,
@@ -0,0 +1,141 @@
+use crate::helpers::{TestApp, spawn_app};
+
+fn issue_body(html: &str) -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "text": "Newsletter body as plain text",
+        "html": html,
+        "idempotency_key": uuid::Uuid::new_v4()
+    })
+}
+
+async fn stored_html(app: &TestApp) -> Option<String> {
+    sqlx::query_scalar!("SELECT html_content FROM newsletter_issues")
+        .fetch_optional(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn styles_are_inlined_and_the_content_is_sanitized() {
+    //Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let html = r#"<html><head><title>Title</title><style>.note { color: gray }</style></head>
+        <body><p class="note" data-editor-id="7">Hello <b>world</b></p></body></html>"#;
+
+    //Act
+    let response = app.post_newsletters(&issue_body(html)).await;
+
+    //Assert
+    assert_eq!(response.status().as_u16(), 303);
+    let stored = stored_html(&app).await.unwrap();
+    assert!(stored.contains(r#"<p style="color: gray">Hello <b>world</b></p>"#));
+    assert!(!stored.contains("<style>"));
+    assert!(!stored.contains("Title"));
+}
+
+#[tokio::test]
+async fn disallowed_content_is_reported_and_nothing_is_published() {
+    //Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let html = r#"<p onmouseover="steal()">Hi</p><script>steal()</script><iframe src="https://example.com"></iframe>"#;
+
+    //Act - Part 1 - Try to publish
+    let response = app.post_newsletters(&issue_body(html)).await;
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(
+        response.headers().get("Location").unwrap(),
+        "/admin/newsletters"
+    );
+
+    //Act - Part 2 - Follow the redirect
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("Event handlers such as onmouseover are not allowed."));
+    assert!(html_page.contains("script elements are not allowed."));
+    assert!(html_page.contains("iframe elements are not allowed."));
+    assert!(!html_page.contains("The newsletter issue has been published!"));
+    assert_eq!(stored_html(&app).await, None);
+}
+
+#[tokio::test]
+async fn css_that_can_not_be_inlined_is_reported_as_a_warning() {
+    //Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let html = "<style>@media (max-width: 600px) { p { margin: 0 } }</style><p>Hi</p>";
+
+    //Act
+    app.post_newsletters(&issue_body(html)).await;
+
+    //Assert
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("The newsletter issue has been published!"));
+    assert!(html_page.contains("@media (max-width: 600px) can not be inlined and was dropped."));
+    assert_eq!(stored_html(&app).await.unwrap(), "<p>Hi</p>");
+}
+
+#[tokio::test]
+async fn css_warnings_are_escaped() {
+    //Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let html = r#"<style>a[title="<img src=x>"]:hover { color: red }</style><p>Hi</p>"#;
+
+    //Act
+    app.post_newsletters(&issue_body(html)).await;
+
+    //Assert
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains(
+        "The selector a[title=&quot;&lt;img src=x&gt;&quot;]:hover can not be inlined and was dropped."
+    ));
+    assert!(!html_page.contains("<img src=x>"));
+}
+
+#[tokio::test]
+async fn plain_text_is_generated_when_left_blank() {
+    //Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let mut body = issue_body(
+        r#"<p>Read <a href="https://example.com/post">the post</a>:</p><ol><li>One</li><li>Two</li></ol>"#,
+    );
+    body["text"] = "  \n".into();
+
+    //Act
+    app.post_newsletters(&body).await;
+
+    //Assert
+    let text = sqlx::query_scalar!("SELECT text_content FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(text.contains("[the post][1]"));
+    assert!(text.contains("1. One\n2. Two"));
+    assert!(text.contains("[1]: https://example.com/post"));
+}
+
+#[tokio::test]
+async fn authors_can_preview_the_generated_plain_text() {
+    //Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "text": "",
+        "html": "<p>Fish &amp; <b>chips</b></p>",
+        "idempotency_key": uuid::Uuid::new_v4()
+    });
+
+    //Act
+    let response = app.post_form("/admin/newsletters/preview", &body).await;
+
+    //Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("Generated from the HTML content"));
+    assert!(html_page.contains("<pre>Fish &amp; **chips**\n</pre>"));
+    assert_eq!(stored_html(&app).await, None);
+}
@@ -0,0 +1,179 @@
+use wiremock::ResponseTemplate;
+
+use crate::{
+    helpers::{TestApp, assert_is_redirect_to, spawn_app},
+    newsletter::{create_confirmed_subscriber, when_sending_an_email},
+};
+
+fn layout_body(name: &str, footer: &str) -> serde_json::Value {
+    serde_json::json!({
+        "name": name,
+        "html_template": format!("<div><h1>Header</h1>{{{{content}}}}<p>{footer}</p></div>"),
+        "text_template": format!("Header\n\n{{{{content}}}}\n\n{footer}"),
+        "is_default": "on",
+    })
+}
+
+async fn layout_id(app: &TestApp, name: &str) -> uuid::Uuid {
+    sqlx::query_scalar!("SELECT layout_id FROM email_layouts WHERE name = $1", name)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+/// Publishes an issue and returns the html and text bodies of the email that was sent.
+async fn publish_and_deliver_issue(app: &TestApp, extra: serde_json::Value) -> (String, String) {
+    let mut body = serde_json::json!({
+        "title": "Newsletter title",
+        "text": "Newsletter body as plain text",
+        "html": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4()
+    });
+    for (key, value) in extra.as_object().unwrap() {
+        body[key] = value.clone();
+    }
+    let response = app.post_newsletters(&body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    let _mock_guard = when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    (
+        body["HtmlBody"].as_str().unwrap().to_owned(),
+        body["TextBody"].as_str().unwrap().to_owned(),
+    )
+}
+
+#[tokio::test]
+async fn the_default_layout_wraps_issues_at_send_time() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let response = app
+        .post_layout(&layout_body("Main", "Our legal address"))
+        .await;
+    assert_is_redirect_to(&response, "/admin/layouts");
+
+    // Act
+    let (html, text) = publish_and_deliver_issue(&app, serde_json::json!({})).await;
+
+    // Assert
+    assert!(html.contains(
+        "<div><h1>Header</h1><p>Newsletter body as HTML</p><p>Our legal address</p></div>"
+    ));
+    assert!(text.contains("Header\n\nNewsletter body as plain text\n\nOur legal address"));
+    let stored = sqlx::query!("SELECT html_content FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(stored.html_content, "<p>Newsletter body as HTML</p>");
+}
+
+#[tokio::test]
+async fn editing_a_layout_changes_emails_sent_afterwards() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    app.post_layout(&layout_body("Main", "Old footer")).await;
+    let layout_id = layout_id(&app, "Main").await;
+
+    // Act
+    let response = app
+        .post_form(
+            &format!("/admin/layouts/{layout_id}"),
+            &layout_body("Main", "New footer"),
+        )
+        .await;
+    assert_is_redirect_to(&response, &format!("/admin/layouts/{layout_id}"));
+    let (html, _) = publish_and_deliver_issue(&app, serde_json::json!({})).await;
+
+    // Assert
+    assert!(html.contains("New footer"));
+    assert!(!html.contains("Old footer"));
+}
+
+#[tokio::test]
+async fn an_issue_can_be_sent_without_a_layout() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    app.post_layout(&layout_body("Main", "Our legal address"))
+        .await;
+
+    // Act
+    let (html, _) = publish_and_deliver_issue(&app, serde_json::json!({ "layout_id": "" })).await;
+
+    // Assert
+    assert!(!html.contains("Our legal address"));
+}
+
+#[tokio::test]
+async fn a_layout_without_a_content_slot_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let response = app
+        .post_layout(&serde_json::json!({
+            "name": "Broken",
+            "html_template": "<p>Footer</p>",
+            "text_template": "{{content}}",
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/layouts");
+    let html_page = app.get_layouts_html().await;
+    assert!(html_page.contains("The HTML layout must contain {{content}}."));
+    let count = sqlx::query_scalar!(r#"SELECT count(*) AS "count!" FROM email_layouts"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 0);
+}
+
+#[tokio::test]
+async fn the_default_layout_is_preselected_on_the_publish_form() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_layout(&layout_body("Main", "Our legal address"))
+        .await;
+    let layout_id = layout_id(&app, "Main").await;
+
+    // Act
+    let html_page = app.get_newsletter_html().await;
+
+    // Assert
+    assert!(html_page.contains(&format!(
+        r#"<option value="{layout_id}" selected>Main</option>"#
+    )));
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_layouts() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.post_layout(&layout_body("Main", "Footer")).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
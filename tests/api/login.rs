@@ -1,4 +1,7 @@
-use crate::helpers::{assert_is_redirect_to, spawn_app};
+use std::time::Duration;
+use zero2prod::configuration::ThrottlePolicy;
+
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app, spawn_app_with};
 
 #[tokio::test]
 async fn an_error_flash_message_is_set_on_failure() {
@@ -42,3 +45,109 @@ async fn redirect_to_admin_dashboard_after_login_success() {
     let html_page = app.get_admin_dashboard_html().await;
     assert!(html_page.contains(&format!("Welcome {}", &app.test_user.username)));
 }
+
+async fn login_with(app: &TestApp, username: &str, password: &str) -> reqwest::Response {
+    app.post_login(&serde_json::json!({
+        "username": username,
+        "password": password
+    }))
+    .await
+}
+
+#[tokio::test]
+async fn repeated_failures_slow_down_further_attempts() {
+    let app = spawn_app_with(|c| {
+        c.login_throttle.username = ThrottlePolicy {
+            free_attempts: 0,
+            lockout_threshold: 10,
+        }
+    })
+    .await;
+    let username = app.test_user.username.clone();
+
+    login_with(&app, &username, "wrong password").await;
+    login_with(&app, &username, "wrong password").await;
+    assert!(
+        app.get_login_html()
+            .await
+            .contains("Please wait 2 seconds before trying again.")
+    );
+
+    // Even the right password is refused until the delay is over
+    let response = login_with(&app, &username, &app.test_user.password).await;
+    assert_is_redirect_to(&response, "/login");
+    assert!(
+        app.get_login_html()
+            .await
+            .contains("Too many failed login attempts. Please try again in")
+    );
+
+    tokio::time::sleep(Duration::from_millis(2100)).await;
+    let response = login_with(&app, &username, &app.test_user.password).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn too_many_failures_lock_the_username() {
+    let app = spawn_app_with(|c| {
+        c.login_throttle.username = ThrottlePolicy {
+            free_attempts: 1,
+            lockout_threshold: 2,
+        }
+    })
+    .await;
+    let username = app.test_user.username.clone();
+
+    login_with(&app, &username, "wrong password").await;
+    login_with(&app, &username, "wrong password").await;
+    assert!(app.get_login_html().await.contains(
+        "Logging in has been locked because of too many failed attempts. Please try again in 15 minutes."
+    ));
+
+    let response = login_with(&app, &username, &app.test_user.password).await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn failures_are_counted_per_address_across_usernames() {
+    let app = spawn_app_with(|c| {
+        c.login_throttle.address = ThrottlePolicy {
+            free_attempts: 1,
+            lockout_threshold: 3,
+        }
+    })
+    .await;
+
+    for username in ["alice", "bob", "carol"] {
+        login_with(&app, username, "wrong password").await;
+    }
+
+    let response = login_with(&app, &app.test_user.username, &app.test_user.password).await;
+    assert_is_redirect_to(&response, "/login");
+    assert!(
+        app.get_login_html()
+            .await
+            .contains("Too many failed login attempts.")
+    );
+}
+
+#[tokio::test]
+async fn a_successful_login_resets_the_failures_of_the_username() {
+    let app = spawn_app_with(|c| {
+        c.login_throttle.username = ThrottlePolicy {
+            free_attempts: 1,
+            lockout_threshold: 10,
+        }
+    })
+    .await;
+    let username = app.test_user.username.clone();
+    login_with(&app, &username, "wrong password").await;
+    login_with(&app, &username, &app.test_user.password).await;
+    app.post_logout().await;
+
+    login_with(&app, &username, "wrong password").await;
+
+    let html = app.get_login_html().await;
+    assert!(html.contains("Authentication failed"));
+    assert!(!html.contains("Please wait"));
+}
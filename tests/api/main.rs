@@ -1,8 +1,28 @@
 mod admin_dashboard;
+mod archive;
+mod attachments;
+mod audience;
+mod automations;
 mod change_password;
+mod csrf;
+mod digests;
+mod feeds;
 mod health_check;
 mod helpers;
+mod issue_content;
+mod layouts;
 mod login;
+mod media;
 mod newsletter;
+mod password_reset;
+mod roles;
+mod sessions;
+mod subject_tests;
 mod subscription_confirm;
 mod subscriptions;
+mod suppressions;
+mod timezones;
+mod tracking;
+mod two_factor;
+mod users;
+mod webhooks;
@@ -0,0 +1,195 @@
+use reqwest::multipart::{Form, Part};
+
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app};
+
+const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR";
+
+fn upload(file_name: &str, content: &[u8]) -> Form {
+    Form::new().part(
+        "file",
+        Part::bytes(content.to_vec()).file_name(file_name.to_string()),
+    )
+}
+
+async fn upload_png(app: &TestApp) -> uuid::Uuid {
+    let response = app.post_media(upload("logo.png", PNG)).await;
+    assert_is_redirect_to(&response, "/admin/media");
+    sqlx::query_scalar!("SELECT media_id FROM media")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn uploaded_images_are_served_with_caching_headers() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let media_id = upload_png(&app).await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/media/{media_id}", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let headers = response.headers();
+    assert_eq!(headers["Content-Type"], "image/png");
+    assert_eq!(
+        headers["Cache-Control"],
+        "public, max-age=31536000, immutable"
+    );
+    assert_eq!(headers["ETag"], format!("\"{media_id}\"").as_str());
+    assert_eq!(response.bytes().await.unwrap(), PNG);
+    let page = app.get_media_page_html().await;
+    assert!(page.contains(&format!("/media/{media_id}")));
+}
+
+#[tokio::test]
+async fn a_cached_image_is_not_sent_again() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let media_id = upload_png(&app).await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/media/{media_id}", &app.address))
+        .header("If-None-Match", format!("\"{media_id}\""))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 304);
+}
+
+#[tokio::test]
+async fn only_images_can_be_uploaded() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let svg = br#"<svg xmlns="http://www.w3.org/2000/svg"><script>alert(1)</script></svg>"#;
+
+    // Act
+    let response = app.post_media(upload("logo.svg", svg)).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/media");
+    let page = app.get_media_page_html().await;
+    assert!(page.contains("logo.svg is not a PNG, JPEG, GIF or WebP image."));
+    let count = sqlx::query_scalar!(r#"SELECT count(*) AS "count!" FROM media"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 0);
+}
+
+#[tokio::test]
+async fn file_names_are_escaped_in_messages() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let response = app
+        .post_media(upload("<img src=x onerror=alert(1)>.png", PNG))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/media");
+    let page = app.get_media_page_html().await;
+    assert!(page.contains("&lt;img src=x onerror=alert(1)&gt;.png has been uploaded"));
+    assert!(!page.contains("<img src=x"));
+}
+
+#[tokio::test]
+async fn images_over_the_size_limit_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let mut content = PNG.to_vec();
+    content.resize(5 * 1024 * 1024 + 1, 0);
+
+    // Act
+    let response = app.post_media(upload("huge.png", &content)).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/media");
+    let page = app.get_media_page_html().await;
+    assert!(page.contains("huge.png is larger than 5120 KB."));
+}
+
+#[tokio::test]
+async fn unknown_and_deleted_images_are_not_found() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let media_id = upload_png(&app).await;
+    let response = app
+        .post_form(
+            &format!("/admin/media/{media_id}/delete"),
+            &serde_json::json!({}),
+        )
+        .await;
+    assert_is_redirect_to(&response, "/admin/media");
+
+    for id in [media_id, uuid::Uuid::new_v4()] {
+        // Act
+        let response = app
+            .api_client
+            .get(format!("{}/media/{id}", &app.address))
+            .send()
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 404);
+    }
+}
+
+#[tokio::test]
+async fn image_urls_are_made_absolute_when_an_issue_is_published() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let media_id = upload_png(&app).await;
+
+    // Act
+    let response = app
+        .post_newsletters(&serde_json::json!({
+            "title": "Newsletter title",
+            "text": "Newsletter body as plain text",
+            "html": format!(r#"<p><img src="/media/{media_id}" alt="Logo"></p>"#),
+            "idempotency_key": uuid::Uuid::new_v4()
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    // Assert
+    let html_content = sqlx::query_scalar!("SELECT html_content FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(html_content.contains(&format!(
+        r#"src="http://{}/media/{media_id}""#,
+        app.base_url.0
+    )));
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_upload_images() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.post_media(upload("logo.png", PNG)).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
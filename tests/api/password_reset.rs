@@ -0,0 +1,259 @@
+use std::time::Duration;
+use wiremock::ResponseTemplate;
+
+use crate::{
+    helpers::{TestApp, assert_is_redirect_to, spawn_app},
+    newsletter::when_sending_an_email,
+};
+
+const NEW_PASSWORD: &str = "a brand new password";
+const CONFIRMATION: &str = "If the username belongs to an account with an email address, a link to reset the password has been sent to it.";
+
+async fn give_test_user_an_email(app: &TestApp) {
+    sqlx::query!(
+        "UPDATE users SET email = 'owner@example.com' WHERE username = $1",
+        app.test_user.username
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+async fn request_reset(app: &TestApp, username: &str) -> reqwest::Response {
+    app.post_form(
+        "/password-reset",
+        &serde_json::json!({ "username": username }),
+    )
+    .await
+}
+
+/// The email is sent in the background, wait for it to arrive.
+async fn received_emails(app: &TestApp, expected: usize) -> Vec<wiremock::Request> {
+    for _ in 0..50 {
+        let requests = app.email_server.received_requests().await.unwrap();
+        if requests.len() >= expected {
+            return requests;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    app.email_server.received_requests().await.unwrap()
+}
+
+async fn reset_link(app: &TestApp) -> reqwest::Url {
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    request_reset(app, &app.test_user.username).await;
+    let requests = received_emails(app, 1).await;
+    app.get_confirmation_links(&requests[0]).html
+}
+
+async fn reset(app: &TestApp, link: &reqwest::Url, password: &str) -> reqwest::Response {
+    let token = link
+        .query_pairs()
+        .find(|(key, _)| key == "token")
+        .unwrap()
+        .1
+        .into_owned();
+    app.post_form(
+        "/password-reset/confirm",
+        &serde_json::json!({
+            "token": token,
+            "new_password": password,
+            "new_password_check": password,
+        }),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn the_login_page_links_to_the_password_reset() {
+    let app = spawn_app().await;
+
+    let html = app.get_login_html().await;
+
+    assert!(html.contains(r#"<a href="/password-reset">"#));
+}
+
+#[tokio::test]
+async fn the_response_does_not_reveal_whether_the_user_exists() {
+    let app = spawn_app().await;
+    give_test_user_an_email(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    for username in [app.test_user.username.as_str(), "nobody"] {
+        let response = request_reset(&app, username).await;
+        assert_is_redirect_to(&response, "/login");
+        assert!(app.get_login_html().await.contains(CONFIRMATION));
+    }
+
+    assert_eq!(received_emails(&app, 1).await.len(), 1);
+}
+
+#[tokio::test]
+async fn a_reset_link_replaces_the_password_once() {
+    let app = spawn_app().await;
+    give_test_user_an_email(&app).await;
+    let link = reset_link(&app).await;
+
+    let form = app.api_client.get(link.clone()).send().await.unwrap();
+    assert_eq!(form.status().as_u16(), 200);
+    let response = reset(&app, &link, NEW_PASSWORD).await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": NEW_PASSWORD
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let response = reset(&app, &link, "yet another new password").await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn the_new_password_follows_the_password_rules() {
+    let app = spawn_app().await;
+    give_test_user_an_email(&app).await;
+    let link = reset_link(&app).await;
+
+    let response = reset(&app, &link, "too short").await;
+
+    let form_path = format!("{}?{}", link.path(), link.query().unwrap());
+    assert_is_redirect_to(&response, &form_path);
+    let html = app
+        .api_client
+        .get(format!("{}{form_path}", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html.contains("<p><i>Password length must be > 12  and < 128</i></p>"));
+}
+
+#[tokio::test]
+async fn expired_reset_links_are_rejected() {
+    let app = spawn_app().await;
+    give_test_user_an_email(&app).await;
+    let link = reset_link(&app).await;
+    sqlx::query!("UPDATE password_resets SET expires_at = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let form = app.api_client.get(link.clone()).send().await.unwrap();
+    assert_eq!(form.status().as_u16(), 401);
+    let response = reset(&app, &link, NEW_PASSWORD).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn requesting_a_new_link_clears_out_the_expired_ones() {
+    let app = spawn_app().await;
+    give_test_user_an_email(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    request_reset(&app, &app.test_user.username).await;
+    received_emails(&app, 1).await;
+    sqlx::query!("UPDATE password_resets SET expires_at = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    request_reset(&app, &app.test_user.username).await;
+    received_emails(&app, 2).await;
+
+    let resets = sqlx::query_scalar!(
+        r#"SELECT count(*) AS "count!" FROM password_resets WHERE expires_at <= now()"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(resets, 0);
+}
+
+#[tokio::test]
+async fn too_many_requests_are_throttled() {
+    let app = spawn_app().await;
+
+    for _ in 0..3 {
+        let response = request_reset(&app, "nobody").await;
+        assert_is_redirect_to(&response, "/login");
+    }
+    let response = request_reset(&app, "nobody").await;
+
+    assert_is_redirect_to(&response, "/password-reset");
+    let html = app
+        .api_client
+        .get(format!("{}/password-reset", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html.contains("Too many password reset requests."));
+}
+
+#[tokio::test]
+async fn usernames_are_escaped_in_the_reset_email() {
+    let app = spawn_app().await;
+    give_test_user_an_email(&app).await;
+    sqlx::query!(
+        "UPDATE users SET username = '<b>owner</b>' WHERE username = $1",
+        app.test_user.username
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    request_reset(&app, "<b>owner</b>").await;
+
+    let requests = received_emails(&app, 1).await;
+    let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+    let html = body["HtmlBody"].as_str().unwrap();
+    assert!(html.contains("<p>Hi &lt;b&gt;owner&lt;/b&gt;,</p>"));
+}
+
+#[tokio::test]
+async fn the_retry_link_keeps_odd_tokens_intact() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_form(
+            "/password-reset/confirm",
+            &serde_json::json!({
+                "token": "a&b=c#d",
+                "new_password": "too short",
+                "new_password_check": "too short",
+            }),
+        )
+        .await;
+
+    assert_is_redirect_to(&response, "/password-reset/confirm?token=a%26b%3Dc%23d");
+}
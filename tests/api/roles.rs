@@ -0,0 +1,140 @@
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app};
+
+async fn set_role(app: &TestApp, role: &str) {
+    sqlx::query!(
+        "UPDATE users SET role = $1 WHERE username = $2",
+        role,
+        app.test_user.username
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+async fn post_role(app: &TestApp, user_id: uuid::Uuid, role: &str) -> reqwest::Response {
+    app.post_form(
+        &format!("/admin/users/{user_id}/role"),
+        &serde_json::json!({ "role": role }),
+    )
+    .await
+}
+
+async fn role_of(app: &TestApp, username: &str) -> String {
+    sqlx::query_scalar!("SELECT role FROM users WHERE username = $1", username)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn viewers_can_read_the_dashboard_but_not_publish() {
+    let app = spawn_app().await;
+    set_role(&app, "viewer").await;
+    app.test_user.login(&app).await;
+
+    let dashboard = app.get_admin_dashboard_html().await;
+    assert!(dashboard.contains("You are signed in as: Viewer"));
+    assert!(!dashboard.contains("/admin/newsletters"));
+
+    let response = app
+        .post_newsletters(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(app.get_newsletter().await.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn editors_cannot_manage_users() {
+    let app = spawn_app().await;
+    set_role(&app, "editor").await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/admin/users", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(app.get_newsletter().await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn disabled_users_are_logged_out_on_their_next_request() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    sqlx::query!(
+        "UPDATE users SET is_active = false WHERE username = $1",
+        app.test_user.username
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let response = app.get_admin_dashboard().await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn owners_can_change_the_role_of_a_user() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let admin_id = sqlx::query_scalar!("SELECT user_id FROM users WHERE username = 'admin'")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+
+    let response = post_role(&app, admin_id, "viewer").await;
+
+    assert_is_redirect_to(&response, "/admin/users");
+    assert_eq!(role_of(&app, "admin").await, "viewer");
+}
+
+#[tokio::test]
+async fn the_last_active_owner_cannot_be_demoted() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    sqlx::query!("DELETE FROM users WHERE username = 'admin'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let own_id = sqlx::query_scalar!(
+        "SELECT user_id FROM users WHERE username = $1",
+        app.test_user.username
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+
+    post_role(&app, own_id, "editor").await;
+
+    let html = app
+        .api_client
+        .get(format!("{}/admin/users", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html.contains("The last active owner has to remain an owner."));
+    assert_eq!(role_of(&app, &app.test_user.username).await, "owner");
+}
+
+#[tokio::test]
+async fn viewers_cannot_export_the_suppression_list() {
+    let app = spawn_app().await;
+    set_role(&app, "viewer").await;
+    app.test_user.login(&app).await;
+
+    let response = app.get_suppressions_export().await;
+
+    assert_eq!(response.status().as_u16(), 403);
+}
@@ -0,0 +1,227 @@
+use zero2prod::configuration::SameSitePolicy;
+
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app, spawn_app_with};
+
+/// Logs the test user in from a second browser.
+async fn log_in_elsewhere(app: &TestApp) -> reqwest::Client {
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .user_agent("Second browser")
+        .build()
+        .unwrap();
+    let response = app
+        .post_form_with(
+            &client,
+            "/login",
+            &serde_json::json!({
+                "username": &app.test_user.username,
+                "password": &app.test_user.password
+            }),
+        )
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    client
+}
+
+async fn get_dashboard(app: &TestApp, client: &reqwest::Client) -> reqwest::Response {
+    client
+        .get(format!("{}/admin/dashboard", &app.address))
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn get_sessions_html(app: &TestApp) -> String {
+    app.api_client
+        .get(format!("{}/admin/sessions", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn the_sessions_of_the_user_are_listed() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    log_in_elsewhere(&app).await;
+
+    let html = get_sessions_html(&app).await;
+
+    assert!(html.contains("This session"));
+    assert!(html.contains("Second browser"));
+    assert!(html.contains("127.0.0.1"));
+    assert_eq!(html.matches("/revoke").count(), 1);
+}
+
+#[tokio::test]
+async fn a_revoked_session_is_logged_out() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let other = log_in_elsewhere(&app).await;
+    let html = get_sessions_html(&app).await;
+    let revoke_path = html
+        .split(r#"action=""#)
+        .nth(1)
+        .and_then(|rest| rest.split('"').next())
+        .unwrap()
+        .to_string();
+
+    let response = app.post_form(&revoke_path, &serde_json::json!({})).await;
+
+    assert_is_redirect_to(&response, "/admin/sessions");
+    assert!(
+        get_sessions_html(&app)
+            .await
+            .contains("The session has been revoked.")
+    );
+    assert_is_redirect_to(&get_dashboard(&app, &other).await, "/login");
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn changing_the_password_logs_out_the_other_sessions() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let other = log_in_elsewhere(&app).await;
+    let new_password = uuid::Uuid::new_v4().to_string();
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/password");
+    assert_is_redirect_to(&get_dashboard(&app, &other).await, "/login");
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn sessions_are_tied_to_the_password_they_logged_in_with() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Even if the row of the session is left behind, a new password ends it.
+    sqlx::query!(
+        "UPDATE users SET password_hash = 'replaced' WHERE username = $1",
+        app.test_user.username
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/login");
+}
+
+#[tokio::test]
+async fn logging_out_ends_the_session() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    app.post_logout().await;
+
+    let sessions = sqlx::query_scalar!(r#"SELECT count(*) AS "count!" FROM user_sessions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(sessions, 0);
+}
+
+async fn login_cookie(app: &TestApp, remember_me: bool) -> String {
+    let mut body = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password
+    });
+    if remember_me {
+        body["remember_me"] = "on".into();
+    }
+    let response = app.post_login(&body).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    response
+        .headers()
+        .get_all("set-cookie")
+        .iter()
+        .map(|value| value.to_str().unwrap().to_string())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[tokio::test]
+async fn the_session_cookie_follows_the_configuration() {
+    let app = spawn_app().await;
+
+    let cookie = login_cookie(&app, false).await;
+
+    assert!(cookie.starts_with("zero2prod_session="));
+    assert!(cookie.contains("HttpOnly"));
+    assert!(cookie.contains("SameSite=Strict"));
+    assert!(cookie.contains("Max-Age=1800"));
+    assert!(!cookie.contains("Secure"));
+}
+
+#[tokio::test]
+async fn session_cookies_can_be_restricted_to_https() {
+    let app = spawn_app_with(|c| {
+        c.session.secure = true;
+        c.session.same_site = SameSitePolicy::Lax;
+        c.session.domain = Some("127.0.0.1".into());
+    })
+    .await;
+
+    let cookie = login_cookie(&app, false).await;
+
+    assert!(cookie.contains("Secure"));
+    assert!(cookie.contains("SameSite=Lax"));
+    assert!(cookie.contains("Domain=127.0.0.1"));
+}
+
+#[tokio::test]
+async fn remember_me_extends_the_session() {
+    let app = spawn_app().await;
+
+    let cookie = login_cookie(&app, true).await;
+
+    assert!(cookie.contains("Max-Age=2592000"));
+    let remembered = sqlx::query_scalar!(
+        r#"
+        SELECT expires_at > now() + interval '29 days' AS "remembered!"
+        FROM user_sessions
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert!(remembered);
+}
+
+#[tokio::test]
+async fn sessions_end_after_their_absolute_lifetime() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    sqlx::query!("UPDATE user_sessions SET expires_at = now() - interval '1 second'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/login");
+}
+
+#[tokio::test]
+async fn sessions_end_after_a_period_of_inactivity() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    sqlx::query!("UPDATE user_sessions SET last_seen_at = now() - interval '31 minutes'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/login");
+}
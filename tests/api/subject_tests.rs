@@ -0,0 +1,186 @@
+use wiremock::ResponseTemplate;
+use zero2prod::{
+    issue_delivery_worker::ExecutionOutcome, subject_tests::try_finalize_subject_test,
+};
+
+use crate::{
+    helpers::{TestApp, spawn_app, spawn_app_with},
+    newsletter::{create_confirmed_subscriber, when_sending_an_email},
+};
+
+fn subject_test_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Subject A",
+        "text": "Newsletter body as plain text",
+        "html": "<html><body><p>Newsletter body as HTML</p></body></html>",
+        "subject_variants": "Subject B\n\n",
+        "test_percentage": "20",
+        "test_window_hours": "4",
+        "winning_metric": "opens",
+        "track_opens": "on",
+        "idempotency_key": uuid::Uuid::new_v4()
+    })
+}
+
+/// Delivers everything that is not held and returns the sent email bodies.
+async fn deliver(app: &TestApp, expected: u64) -> Vec<serde_json::Value> {
+    let already_received = app.email_server.received_requests().await.unwrap().len();
+    let _mock_guard = when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(expected)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+    app.email_server.received_requests().await.unwrap()[already_received..]
+        .iter()
+        .map(|r| serde_json::from_slice(&r.body).unwrap())
+        .collect()
+}
+
+async fn end_test_window(app: &TestApp) {
+    sqlx::query!("UPDATE subject_tests SET decide_after = now()")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn the_winning_subject_is_sent_to_the_rest_of_the_audience() {
+    //Arrange
+    let app = spawn_app().await;
+    for _ in 0..10 {
+        create_confirmed_subscriber(&app).await;
+    }
+    app.test_user.login(&app).await;
+    let response = app.post_newsletters(&subject_test_body()).await;
+    assert_eq!(response.status().as_u16(), 303);
+
+    //Act - Part 1 - Only the test slice is sent, one email per variant
+    let test_emails = deliver(&app, 2).await;
+    let mut subjects: Vec<_> = test_emails
+        .iter()
+        .map(|e| e["Subject"].as_str().unwrap())
+        .collect();
+    subjects.sort();
+    assert_eq!(subjects, ["Subject A", "Subject B"]);
+
+    //Act - Part 2 - Only the recipient of the second variant opens the email
+    let opened = test_emails
+        .iter()
+        .find(|e| e["Subject"] == "Subject B")
+        .unwrap();
+    let html = opened["HtmlBody"].as_str().unwrap();
+    let start = html.find("/o/").unwrap();
+    let pixel_path = &html[start..start + html[start..].find('"').unwrap()];
+    app.api_client
+        .get(format!("{}{pixel_path}", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    //Act - Part 3 - The winner is picked once the window has passed
+    end_test_window(&app).await;
+    let outcome = try_finalize_subject_test(&app.db_pool).await.unwrap();
+    assert!(matches!(outcome, ExecutionOutcome::TaskCompleted));
+
+    //Assert
+    let remaining_emails = deliver(&app, 8).await;
+    assert!(remaining_emails.iter().all(|e| e["Subject"] == "Subject B"));
+}
+
+#[tokio::test]
+async fn no_winner_is_picked_before_the_window_has_passed() {
+    //Arrange
+    let app = spawn_app().await;
+    for _ in 0..5 {
+        create_confirmed_subscriber(&app).await;
+    }
+    app.test_user.login(&app).await;
+    app.post_newsletters(&subject_test_body()).await;
+    // 20% of five subscribers
+    deliver(&app, 1).await;
+
+    //Act
+    let outcome = try_finalize_subject_test(&app.db_pool).await.unwrap();
+
+    //Assert
+    assert!(matches!(outcome, ExecutionOutcome::EmptyQueue));
+    deliver(&app, 0).await;
+}
+
+#[tokio::test]
+async fn issues_without_alternative_subjects_are_sent_to_everybody() {
+    //Arrange
+    let app = spawn_app().await;
+    for _ in 0..3 {
+        create_confirmed_subscriber(&app).await;
+    }
+    app.test_user.login(&app).await;
+    let mut body = subject_test_body();
+    body["subject_variants"] = " \n".into();
+
+    //Act
+    app.post_newsletters(&body).await;
+
+    //Assert
+    let emails = deliver(&app, 3).await;
+    assert!(emails.iter().all(|e| e["Subject"] == "Subject A"));
+    let tests = sqlx::query!("SELECT count(*) AS \"count!\" FROM subject_tests")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(tests.count, 0);
+}
+
+#[tokio::test]
+async fn subject_tests_are_validated_before_publishing() {
+    //Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let test_cases = [
+        ("test_percentage", "0", "between 1 and 99 percent"),
+        ("test_percentage", "100", "between 1 and 99 percent"),
+        ("test_window_hours", "0", "at least one hour"),
+        (
+            "winning_metric",
+            "clicks",
+            "Tracking clicks has to be enabled",
+        ),
+    ];
+
+    for (field, value, error) in test_cases {
+        let mut body = subject_test_body();
+        body[field] = value.into();
+
+        //Act
+        let response = app.post_newsletters(&body).await;
+
+        //Assert
+        assert_eq!(response.status().as_u16(), 400);
+        assert!(response.text().await.unwrap().contains(error));
+    }
+    deliver(&app, 0).await;
+}
+
+#[tokio::test]
+async fn metrics_that_are_not_tracked_on_this_newsletter_are_rejected() {
+    //Arrange
+    let app = spawn_app_with(|c| c.tracking.allow_open_tracking = false).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    //Act
+    let response = app.post_newsletters(&subject_test_body()).await;
+
+    //Assert
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(
+        response
+            .text()
+            .await
+            .unwrap()
+            .contains("Tracking opens is turned off")
+    );
+    deliver(&app, 0).await;
+}
@@ -3,7 +3,7 @@ use wiremock::{
     matchers::{method, path},
 };
 
-use crate::helpers::spawn_app;
+use crate::{helpers::spawn_app, newsletter::create_unconfirmed_subscriber};
 
 #[tokio::test]
 async fn confirmations_without_token_are_rejected_with_a_400() {
@@ -71,3 +71,96 @@ async fn clicking_on_the_confirmation_link_confirms_a_subscriber() {
     assert_eq!(saved.name, "le guin");
     assert_eq!(saved.status, "confirmed");
 }
+
+#[tokio::test]
+async fn following_the_confirmation_link_again_does_not_resubscribe() {
+    // Arrange
+    let app = spawn_app().await;
+    let confirmation_links = create_unconfirmed_subscriber(&app).await;
+    reqwest::get(confirmation_links.html.clone())
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let token = sqlx::query_scalar!("SELECT unsubscribe_token FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let response = app
+        .post_form(
+            "/subscriptions/unsubscribe",
+            &serde_json::json!({ "token": token }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Act
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let status = sqlx::query_scalar!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(status, "unsubscribed");
+}
+
+#[tokio::test]
+async fn the_confirmation_token_can_not_unsubscribe() {
+    // Arrange
+    let app = spawn_app().await;
+    let confirmation_links = create_unconfirmed_subscriber(&app).await;
+    let subscription_token = confirmation_links
+        .html
+        .query_pairs()
+        .find(|(key, _)| key == "subscription_token")
+        .unwrap()
+        .1
+        .into_owned();
+
+    // Act
+    let response = app
+        .post_form(
+            "/subscriptions/unsubscribe",
+            &serde_json::json!({ "token": subscription_token }),
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn bounced_subscribers_stay_bounced_after_unsubscribing() {
+    // Arrange
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber(&app).await;
+    sqlx::query!("UPDATE subscriptions SET status = 'bounced'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let token = sqlx::query_scalar!("SELECT unsubscribe_token FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let response = app
+        .post_form(
+            "/subscriptions/unsubscribe",
+            &serde_json::json!({ "token": token }),
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let status = sqlx::query_scalar!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(status, "bounced");
+}
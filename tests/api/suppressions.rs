@@ -0,0 +1,188 @@
+use wiremock::{Mock, ResponseTemplate, matchers::any};
+
+use crate::{
+    helpers::{assert_is_redirect_to, spawn_app},
+    newsletter::{create_confirmed_subscriber, when_sending_an_email},
+};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_suppressions() {
+    //Arrange
+    let app = spawn_app().await;
+
+    //Act
+    let page = app.get_suppressions().await;
+    let export = app.get_suppressions_export().await;
+    let add = app
+        .post_suppression(&serde_json::json!({
+            "target": "ursula_le_guin@gmail.com",
+            "reason": "test"
+        }))
+        .await;
+
+    //Assert
+    assert_is_redirect_to(&page, "/login");
+    assert_is_redirect_to(&export, "/login");
+    assert_is_redirect_to(&add, "/login");
+}
+
+#[tokio::test]
+async fn suppressed_addresses_do_not_receive_a_confirmation_email() {
+    //Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let response = app
+        .post_suppression(&serde_json::json!({
+            "target": "ursula_le_guin@gmail.com",
+            "reason": "asked to be left alone"
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/suppressions");
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    //Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    //Assert - the sender is not told the address is suppressed
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn issues_are_not_delivered_to_suppressed_domains() {
+    //Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let email = sqlx::query_scalar!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let domain = email.split_once('@').unwrap().1.to_uppercase();
+    app.test_user.login(&app).await;
+    app.post_suppression(&serde_json::json!({ "target": domain, "reason": "" }))
+        .await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    //Act
+    app.post_newsletters(&serde_json::json!({
+        "title": "Newsletter title",
+        "text": "Newsletter body as plain text",
+        "html": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    //Assert - the task is consumed without an error
+    let n_pending = sqlx::query_scalar!(r#"SELECT count(*) AS "n!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_pending, 0);
+}
+
+#[tokio::test]
+async fn invalid_targets_are_rejected_with_a_flash_message() {
+    //Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    //Act
+    let response = app
+        .post_suppression(&serde_json::json!({ "target": "not a domain", "reason": "" }))
+        .await;
+
+    //Assert
+    assert_is_redirect_to(&response, "/admin/suppressions");
+    let html_page = app.get_suppressions().await.text().await.unwrap();
+    assert!(html_page.contains("is neither a valid email nor a valid domain"));
+}
+
+#[tokio::test]
+async fn imported_suppressions_show_up_in_the_export() {
+    //Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    //Act
+    let response = app
+        .post_suppression_import(
+            "email,domain,reason\nursula_le_guin@gmail.com,,complained by phone\n,example.com,spam trap\n",
+        )
+        .await;
+    assert_is_redirect_to(&response, "/admin/suppressions");
+    let html_page = app.get_suppressions().await.text().await.unwrap();
+    let export = app.get_suppressions_export().await;
+
+    //Assert
+    assert!(html_page.contains("Imported 2 new suppressions (0 already present)."));
+    assert_eq!(
+        export.headers().get("Content-Type").unwrap(),
+        "text/csv; charset=utf-8"
+    );
+    let csv = export.text().await.unwrap();
+    assert!(csv.starts_with("email,domain,reason,source,created_at"));
+    assert!(csv.contains("ursula_le_guin@gmail.com,,complained by phone,import,"));
+    assert!(csv.contains(",example.com,spam trap,import,"));
+}
+
+#[tokio::test]
+async fn a_removed_suppression_no_longer_blocks_emails() {
+    //Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_suppression(&serde_json::json!({
+        "target": "ursula_le_guin@gmail.com",
+        "reason": "bounced"
+    }))
+    .await;
+    let suppression_id = sqlx::query_scalar!("SELECT suppression_id FROM suppressions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+
+    //Act
+    let response = app
+        .post_form(
+            "/admin/suppressions/delete",
+            &serde_json::json!({ "suppression_id": suppression_id }),
+        )
+        .await;
+    assert_is_redirect_to(&response, "/admin/suppressions");
+
+    //Assert
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+}
+
+#[tokio::test]
+async fn suppressions_are_escaped_on_the_list() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_suppression(&serde_json::json!({
+        "target": "ursula_le_guin@gmail.com",
+        "reason": "<script>alert(1)</script>"
+    }))
+    .await;
+
+    let html = app.get_suppressions().await.text().await.unwrap();
+
+    assert!(html.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+    assert!(!html.contains("<script>"));
+}
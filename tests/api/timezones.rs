@@ -0,0 +1,191 @@
+use chrono::{DateTime, NaiveTime, TimeDelta, Utc};
+use fake::{Fake, faker::internet::en::SafeEmail};
+use wiremock::ResponseTemplate;
+
+use crate::{
+    helpers::{TestApp, assert_is_redirect_to, spawn_app},
+    newsletter::when_sending_an_email,
+};
+
+/// Signs up and confirms a subscriber, returns their email address.
+async fn create_subscriber(app: &TestApp, timezone: &str) -> String {
+    let email: String = SafeEmail().fake();
+    let body = serde_urlencoded::to_string(serde_json::json!({
+        "name": "Ursula",
+        "email": email,
+        "timezone": timezone,
+    }))
+    .unwrap();
+    let _mock_guard = when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_subscriptions(body)
+        .await
+        .error_for_status()
+        .unwrap();
+    let email_request = &app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    email
+}
+
+async fn stored_timezone(app: &TestApp, email: &str) -> Option<String> {
+    sqlx::query_scalar!("SELECT timezone FROM subscriptions WHERE email = $1", email)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+async fn execute_after(app: &TestApp, email: &str) -> DateTime<Utc> {
+    app.expand_all_audiences().await;
+    sqlx::query_scalar!(
+        "SELECT execute_after FROM issue_delivery_queue WHERE subscriber_email = $1",
+        email
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+}
+
+fn newsletter(send_date: &str, send_time: &str) -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "text": "Newsletter body as plain text",
+        "html": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4(),
+        "send_date": send_date,
+        "send_time": send_time,
+    })
+}
+
+#[tokio::test]
+async fn the_timezone_is_captured_at_signup() {
+    let app = spawn_app().await;
+
+    let known = create_subscriber(&app, "Europe/Rome").await;
+    let unknown = create_subscriber(&app, "Mars/Olympus_Mons").await;
+
+    assert_eq!(
+        stored_timezone(&app, &known).await.as_deref(),
+        Some("Europe/Rome")
+    );
+    assert_eq!(stored_timezone(&app, &unknown).await, None);
+}
+
+#[tokio::test]
+async fn issues_are_delivered_at_the_local_time_of_each_subscriber() {
+    let app = spawn_app().await;
+    // Neither zone observes daylight saving time, the offsets are fixed.
+    let tokyo = create_subscriber(&app, "Asia/Tokyo").await;
+    let bogota = create_subscriber(&app, "America/Bogota").await;
+    let unset = create_subscriber(&app, "").await;
+    app.test_user.login(&app).await;
+    let date = (Utc::now() + TimeDelta::days(3)).date_naive();
+
+    let response = app
+        .post_newsletters(&newsletter(&date.format("%Y-%m-%d").to_string(), "09:00"))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let nine_utc = date
+        .and_time(NaiveTime::from_hms_opt(9, 0, 0).unwrap())
+        .and_utc();
+    assert_eq!(
+        execute_after(&app, &tokyo).await,
+        nine_utc - TimeDelta::hours(9)
+    );
+    assert_eq!(
+        execute_after(&app, &bogota).await,
+        nine_utc + TimeDelta::hours(5)
+    );
+    assert_eq!(execute_after(&app, &unset).await, nine_utc);
+}
+
+#[tokio::test]
+async fn without_a_date_the_next_occurrence_of_the_time_is_used() {
+    let app = spawn_app().await;
+    let email = create_subscriber(&app, "Asia/Tokyo").await;
+    app.test_user.login(&app).await;
+    let before = Utc::now();
+
+    app.post_newsletters(&newsletter("", "09:00")).await;
+
+    let scheduled = execute_after(&app, &email).await;
+    assert!(scheduled > before);
+    assert!(scheduled <= before + TimeDelta::days(1));
+    assert_eq!(scheduled.format("%H:%M").to_string(), "00:00");
+}
+
+#[tokio::test]
+async fn scheduled_issues_are_not_sent_early() {
+    let app = spawn_app().await;
+    create_subscriber(&app, "Asia/Tokyo").await;
+    app.test_user.login(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    let date = (Utc::now() + TimeDelta::days(3)).date_naive();
+
+    app.post_newsletters(&newsletter(&date.format("%Y-%m-%d").to_string(), "09:00"))
+        .await;
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn subscribers_can_change_their_timezone() {
+    let app = spawn_app().await;
+    let email = create_subscriber(&app, "").await;
+    let token: String = sqlx::query_scalar!(
+        r#"SELECT unsubscribe_token FROM subscriptions WHERE email = $1"#,
+        email
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+
+    for (timezone, status) in [("America/Bogota", 200), ("Nowhere/Special", 400)] {
+        let response = app
+            .post_form(
+                "/subscriptions/preferences",
+                &serde_json::json!({
+                    "token": token,
+                    "delivery_frequency": "immediate",
+                    "timezone": timezone,
+                }),
+            )
+            .await;
+        assert_eq!(response.status().as_u16(), status);
+    }
+
+    assert_eq!(
+        stored_timezone(&app, &email).await.as_deref(),
+        Some("America/Bogota")
+    );
+}
+
+#[tokio::test]
+async fn subject_tests_cannot_be_delivered_at_a_local_time() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let mut body = newsletter("", "09:00");
+    body["subject_variants"] = "Another subject".into();
+    body["track_opens"] = "on".into();
+
+    let response = app.post_newsletters(&body).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
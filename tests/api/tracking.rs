@@ -0,0 +1,338 @@
+use wiremock::ResponseTemplate;
+
+use crate::{
+    helpers::{TestApp, spawn_app},
+    newsletter::{create_confirmed_subscriber, when_sending_an_email},
+};
+
+async fn publish_and_deliver_issue(app: &TestApp, tracking: &[&str]) -> String {
+    app.test_user.login(app).await;
+    let mut body = serde_json::json!({
+        "title": "Newsletter title",
+        "text": "Newsletter body as plain text",
+        "html": r#"<html><body><p>Newsletter body as <a href="https://example.com/post?a=1&amp;b=2">HTML</a></p></body></html>"#,
+        "idempotency_key": uuid::Uuid::new_v4()
+    });
+    for checkbox in tracking {
+        body[*checkbox] = "on".into();
+    }
+    let response = app.post_newsletters(&body).await;
+    assert_eq!(response.status().as_u16(), 303);
+
+    let _mock_guard = when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    body["HtmlBody"].as_str().unwrap().to_owned()
+}
+
+fn get_pixel_path(html: &str) -> Option<String> {
+    get_path(html, "/o/")
+}
+
+fn get_redirect_path(html: &str) -> Option<String> {
+    get_path(html, "/r/")
+}
+
+fn get_path(html: &str, prefix: &str) -> Option<String> {
+    let start = html.find(prefix)?;
+    let end = start + html[start..].find('"')?;
+    Some(html[start..end].to_owned())
+}
+
+#[tokio::test]
+async fn opens_are_recorded_when_tracking_is_enabled_for_the_issue() {
+    //Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let html = publish_and_deliver_issue(&app, &["track_opens"]).await;
+    let pixel_path = get_pixel_path(&html).expect("No tracking pixel in the email");
+    assert!(html.contains(r#"width="1" height="1""#));
+
+    //Act - open the email twice
+    for _ in 0..2 {
+        let response = app
+            .api_client
+            .get(format!("{}{pixel_path}", &app.address))
+            .header("User-Agent", "Thunderbird")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.headers().get("Content-Type").unwrap(), "image/gif");
+    }
+
+    //Assert
+    let recipient = sqlx::query!(
+        "SELECT open_count, first_opened_at, last_opened_at, user_agent FROM issue_recipients"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(recipient.open_count, 2);
+    assert!(recipient.first_opened_at.unwrap() <= recipient.last_opened_at.unwrap());
+    assert_eq!(recipient.user_agent.as_deref(), Some("Thunderbird"));
+}
+
+#[tokio::test]
+async fn no_pixel_is_injected_when_tracking_is_disabled_for_the_issue() {
+    //Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    //Act
+    let html = publish_and_deliver_issue(&app, &[]).await;
+
+    //Assert
+    assert_eq!(get_pixel_path(&html), None);
+}
+
+#[tokio::test]
+async fn the_privacy_setting_overrides_the_issue_setting() {
+    //Arrange
+    let mut app = spawn_app().await;
+    app.tracking.allow_open_tracking = false;
+    create_confirmed_subscriber(&app).await;
+
+    //Act
+    let html = publish_and_deliver_issue(&app, &["track_opens"]).await;
+
+    //Assert
+    assert_eq!(get_pixel_path(&html), None);
+}
+
+#[tokio::test]
+async fn unknown_recipients_still_get_a_pixel() {
+    //Arrange
+    let app = spawn_app().await;
+
+    //Act
+    let response = app
+        .api_client
+        .get(format!("{}/o/{}", &app.address, uuid::Uuid::new_v4()))
+        .send()
+        .await
+        .unwrap();
+
+    //Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers().get("Content-Type").unwrap(), "image/gif");
+    assert_eq!(response.bytes().await.unwrap().len(), 43);
+}
+
+#[tokio::test]
+async fn opens_show_up_in_the_issue_list() {
+    //Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let html = publish_and_deliver_issue(&app, &["track_opens"]).await;
+    let pixel_path = get_pixel_path(&html).unwrap();
+    app.api_client
+        .get(format!("{}{pixel_path}", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    //Act
+    let html_page = app
+        .api_client
+        .get(format!("{}/admin/issues", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    //Assert
+    assert!(html_page.contains("Newsletter title"));
+    assert!(html_page.contains("1 of 1 (1 total)"));
+}
+
+#[tokio::test]
+async fn clicks_are_recorded_and_redirect_to_the_original_link() {
+    //Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let html = publish_and_deliver_issue(&app, &["track_clicks"]).await;
+    let redirect_path = get_redirect_path(&html).expect("No rewritten link in the email");
+    assert!(!html.contains("https://example.com"));
+
+    //Act
+    for _ in 0..2 {
+        let response = app
+            .api_client
+            .get(format!("{}{redirect_path}", &app.address))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 302);
+        assert_eq!(
+            response.headers().get("Location").unwrap(),
+            "https://example.com/post?a=1&b=2"
+        );
+    }
+
+    //Assert
+    let click = sqlx::query!(
+        "SELECT l.url, c.click_count FROM issue_clicks c JOIN issue_links l USING (link_id)"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(click.url, "https://example.com/post?a=1&b=2");
+    assert_eq!(click.click_count, 2);
+}
+
+#[tokio::test]
+async fn tampered_redirect_tokens_are_rejected() {
+    //Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let html = publish_and_deliver_issue(&app, &["track_clicks"]).await;
+    let redirect_path = get_redirect_path(&html).unwrap();
+    let (payload, signature) = redirect_path["/r/".len()..].split_once('.').unwrap();
+    let forged_payload = {
+        use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+        let decoded = String::from_utf8(URL_SAFE_NO_PAD.decode(payload).unwrap()).unwrap();
+        let (recipient_id, _) = decoded.split_once('|').unwrap();
+        URL_SAFE_NO_PAD.encode(format!("{recipient_id}|https://evil.example.com"))
+    };
+
+    for token in [
+        format!("{forged_payload}.{signature}"),
+        forged_payload,
+        "not-a-token".to_string(),
+    ] {
+        //Act
+        let response = app
+            .api_client
+            .get(format!("{}/r/{token}", &app.address))
+            .send()
+            .await
+            .unwrap();
+
+        //Assert
+        assert_eq!(response.status().as_u16(), 404);
+    }
+}
+
+#[tokio::test]
+async fn links_are_left_alone_when_click_tracking_is_disabled_for_the_issue() {
+    //Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    //Act
+    let html = publish_and_deliver_issue(&app, &[]).await;
+
+    //Assert
+    assert_eq!(get_redirect_path(&html), None);
+    assert!(html.contains("https://example.com/post?a=1&amp;b=2"));
+}
+
+#[tokio::test]
+async fn per_link_clicks_show_up_in_the_issue_stats() {
+    //Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let html = publish_and_deliver_issue(&app, &["track_clicks"]).await;
+    let redirect_path = get_redirect_path(&html).unwrap();
+    app.api_client
+        .get(format!("{}{redirect_path}", &app.address))
+        .send()
+        .await
+        .unwrap();
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+
+    //Act
+    let html_page = app
+        .api_client
+        .get(format!("{}/admin/issues/{issue_id}", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    //Assert
+    assert!(
+        html_page.contains("<td>https://example.com/post?a=1&amp;b=2</td><td>1</td><td>1</td>")
+    );
+}
+
+#[tokio::test]
+async fn links_show_up_in_the_issue_stats_before_anyone_clicks() {
+    //Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    publish_and_deliver_issue(&app, &["track_clicks"]).await;
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+
+    //Act
+    let html_page = app
+        .api_client
+        .get(format!("{}/admin/issues/{issue_id}", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    //Assert
+    assert!(
+        html_page.contains("<td>https://example.com/post?a=1&amp;b=2</td><td>0</td><td>0</td>")
+    );
+}
+
+#[tokio::test]
+async fn issues_from_the_publish_form_are_listed_with_escaped_titles() {
+    //Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    //Act - with the field names of the publish form
+    let response = app
+        .post_newsletters(&serde_json::json!({
+            "title": "<b>Big</b> news",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4()
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 303);
+
+    //Assert
+    let html_page = app
+        .api_client
+        .get(format!("{}/admin/issues", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("&lt;b&gt;Big&lt;/b&gt; news"));
+    assert!(!html_page.contains("<b>Big</b>"));
+}
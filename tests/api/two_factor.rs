@@ -0,0 +1,265 @@
+use totp_rs::{Algorithm, Secret, TOTP};
+use zero2prod::configuration::ThrottlePolicy;
+
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app, spawn_app_with};
+
+async fn get_two_factor_html(app: &TestApp) -> String {
+    app.api_client
+        .get(format!("{}/admin/two-factor", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap()
+}
+
+async fn post_code(app: &TestApp, path: &str, code: &str) -> reqwest::Response {
+    app.post_form(path, &serde_json::json!({ "code": code }))
+        .await
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Enrols the test user and returns their authenticator and recovery codes.
+async fn enable_two_factor(app: &TestApp) -> (TOTP, Vec<String>) {
+    app.test_user.login(app).await;
+    let response = app
+        .post_form("/admin/two-factor/enrol", &serde_json::json!({}))
+        .await;
+    assert_is_redirect_to(&response, "/admin/two-factor");
+
+    let html = get_two_factor_html(app).await;
+    assert!(html.contains("<svg"));
+    let secret = html
+        .split("<code>")
+        .nth(1)
+        .and_then(|rest| rest.split("</code>").next())
+        .unwrap();
+    let totp = TOTP::new_unchecked(
+        Algorithm::SHA1,
+        6,
+        1,
+        30,
+        Secret::Encoded(secret.to_string()).to_bytes().unwrap(),
+        None,
+        String::new(),
+    );
+
+    let response = post_code(app, "/admin/two-factor/verify", &totp.generate(now())).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["Cache-Control"], "no-store");
+    let html = response.text().await.unwrap();
+    let recovery_codes: Vec<String> = html
+        .split("<li><code>")
+        .skip(1)
+        .filter_map(|rest| rest.split("</code>").next())
+        .map(String::from)
+        .collect();
+    // They are shown once, not carried over to the next page.
+    let html = get_two_factor_html(app).await;
+    assert!(html.contains("Two-factor authentication is enabled."));
+    assert!(!html.contains(&recovery_codes[0]));
+    (totp, recovery_codes)
+}
+
+async fn stored_secret(app: &TestApp) -> Vec<u8> {
+    sqlx::query_scalar!(
+        r#"SELECT totp_secret AS "totp_secret!" FROM users WHERE username = $1"#,
+        app.test_user.username
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+}
+
+async fn log_in_again(app: &TestApp) -> reqwest::Response {
+    app.post_logout().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password
+    }))
+    .await
+}
+
+#[tokio::test]
+async fn enrolment_stores_an_encrypted_secret_and_recovery_codes() {
+    let app = spawn_app().await;
+
+    let (totp, recovery_codes) = enable_two_factor(&app).await;
+
+    assert_eq!(recovery_codes.len(), 8);
+    let stored = sqlx::query!(
+        r#"
+        SELECT totp_secret AS "totp_secret!",
+            (SELECT count(*) FROM recovery_codes) AS "codes!"
+        FROM users WHERE username = $1
+        "#,
+        app.test_user.username
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert!(!stored.totp_secret.windows(20).any(|w| w == totp.secret));
+    assert_eq!(stored.codes, 8);
+}
+
+#[tokio::test]
+async fn a_wrong_code_does_not_enable_two_factor_authentication() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_form("/admin/two-factor/enrol", &serde_json::json!({}))
+        .await;
+
+    post_code(&app, "/admin/two-factor/verify", "000000").await;
+
+    let html = get_two_factor_html(&app).await;
+    assert!(html.contains("The code is not valid, please try again."));
+    assert!(!html.contains("Two-factor authentication is enabled."));
+}
+
+#[tokio::test]
+async fn users_with_two_factor_authentication_need_a_code_to_log_in() {
+    let app = spawn_app().await;
+    let (totp, _) = enable_two_factor(&app).await;
+
+    let response = log_in_again(&app).await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/login");
+
+    let response = post_code(&app, "/login/two-factor", "000000").await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+
+    // The code used for the enrolment cannot be replayed, the next one works.
+    let response = post_code(&app, "/login/two-factor", &totp.generate(now() + 30)).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn a_totp_code_cannot_be_used_twice() {
+    let app = spawn_app().await;
+    let (totp, _) = enable_two_factor(&app).await;
+    let code = totp.generate(now() + 30);
+    log_in_again(&app).await;
+    post_code(&app, "/login/two-factor", &code).await;
+
+    log_in_again(&app).await;
+    let response = post_code(&app, "/login/two-factor", &code).await;
+
+    assert_is_redirect_to(&response, "/login/two-factor");
+}
+
+#[tokio::test]
+async fn recovery_codes_can_be_used_once() {
+    let app = spawn_app().await;
+    let (_, recovery_codes) = enable_two_factor(&app).await;
+
+    log_in_again(&app).await;
+    let typed = recovery_codes[0].to_uppercase();
+    let response = post_code(&app, "/login/two-factor", &typed).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    assert!(
+        get_two_factor_html(&app)
+            .await
+            .contains("7 unused recovery codes are left.")
+    );
+
+    log_in_again(&app).await;
+    let response = post_code(&app, "/login/two-factor", &recovery_codes[0]).await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+}
+
+#[tokio::test]
+async fn too_many_wrong_codes_start_the_login_over() {
+    // The limit of the login throttle is not the one under test here.
+    let app = spawn_app_with(|c| {
+        c.login_throttle.username = ThrottlePolicy {
+            free_attempts: 10,
+            lockout_threshold: 20,
+        }
+    })
+    .await;
+    enable_two_factor(&app).await;
+    log_in_again(&app).await;
+
+    for _ in 0..4 {
+        let response = post_code(&app, "/login/two-factor", "000000").await;
+        assert_is_redirect_to(&response, "/login/two-factor");
+    }
+    let response = post_code(&app, "/login/two-factor", "000000").await;
+
+    assert_is_redirect_to(&response, "/login");
+    assert!(
+        app.get_login_html()
+            .await
+            .contains("Too many invalid codes, please log in again.")
+    );
+}
+
+#[tokio::test]
+async fn wrong_codes_lock_the_username_across_logins() {
+    let app = spawn_app_with(|c| {
+        c.login_throttle.username = ThrottlePolicy {
+            free_attempts: 2,
+            lockout_threshold: 3,
+        }
+    })
+    .await;
+    let (totp, _) = enable_two_factor(&app).await;
+
+    // A correct password must not clear the failures of the second factor.
+    for _ in 0..3 {
+        log_in_again(&app).await;
+        post_code(&app, "/login/two-factor", "000000").await;
+    }
+    assert!(app.get_login_html().await.contains(
+        "Logging in has been locked because of too many failed attempts. Please try again in 15 minutes."
+    ));
+
+    let response = log_in_again(&app).await;
+    assert_is_redirect_to(&response, "/login");
+    let response = post_code(&app, "/login/two-factor", &totp.generate(now() + 30)).await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn an_enabled_second_factor_cannot_be_replaced() {
+    let app = spawn_app().await;
+    enable_two_factor(&app).await;
+    let secret_before = stored_secret(&app).await;
+
+    let response = app
+        .post_form("/admin/two-factor/enrol", &serde_json::json!({}))
+        .await;
+    assert_is_redirect_to(&response, "/admin/two-factor");
+    let response = post_code(&app, "/admin/two-factor/verify", "000000").await;
+    assert_is_redirect_to(&response, "/admin/two-factor");
+
+    let html = get_two_factor_html(&app).await;
+    assert!(html.contains("Two-factor authentication is already enabled"));
+    let secret_after = stored_secret(&app).await;
+    assert_eq!(secret_before, secret_after);
+}
+
+#[tokio::test]
+async fn two_factor_authentication_can_be_disabled_with_a_code() {
+    let app = spawn_app().await;
+    let (_, recovery_codes) = enable_two_factor(&app).await;
+
+    let response = post_code(&app, "/admin/two-factor/disable", &recovery_codes[1]).await;
+    assert_is_redirect_to(&response, "/admin/two-factor");
+    assert!(
+        get_two_factor_html(&app)
+            .await
+            .contains("Two-factor authentication is disabled.")
+    );
+
+    let response = log_in_again(&app).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
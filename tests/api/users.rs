@@ -0,0 +1,286 @@
+use wiremock::ResponseTemplate;
+
+use crate::{
+    helpers::{TestApp, assert_is_redirect_to, spawn_app},
+    newsletter::when_sending_an_email,
+};
+
+const NEW_PASSWORD: &str = "a long enough password";
+
+async fn invite(app: &TestApp, username: &str, email: &str) -> reqwest::Response {
+    app.post_form(
+        "/admin/users",
+        &serde_json::json!({ "username": username, "email": email, "role": "editor" }),
+    )
+    .await
+}
+
+async fn get_users_html(app: &TestApp) -> String {
+    app.api_client
+        .get(format!("{}/admin/users", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap()
+}
+
+async fn user_id(app: &TestApp, username: &str) -> uuid::Uuid {
+    sqlx::query_scalar!("SELECT user_id FROM users WHERE username = $1", username)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+async fn post_user_action(app: &TestApp, user_id: uuid::Uuid, action: &str) -> reqwest::Response {
+    app.post_form(
+        &format!("/admin/users/{user_id}/{action}"),
+        &serde_json::json!({ "is_active": "false" }),
+    )
+    .await
+}
+
+/// Invites a colleague and returns the link from the invitation email.
+async fn invite_colleague(app: &TestApp) -> reqwest::Url {
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let response = invite(app, "ursula", "ursula@example.com").await;
+    assert_is_redirect_to(&response, "/admin/users");
+    let requests = app.email_server.received_requests().await.unwrap();
+    app.get_confirmation_links(&requests[0]).html
+}
+
+async fn accept(app: &TestApp, link: &reqwest::Url, password: &str) -> reqwest::Response {
+    let token = link
+        .query_pairs()
+        .find(|(key, _)| key == "token")
+        .map(|(_, value)| value.into_owned())
+        .unwrap();
+    app.post_form(
+        "/invitations/accept",
+        &serde_json::json!({
+            "token": token,
+            "password": password,
+            "password_check": password,
+        }),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_users() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/admin/users", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn invited_users_can_set_their_password_and_log_in() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let link = invite_colleague(&app).await;
+    assert!(get_users_html(&app).await.contains("Invited"));
+    let form = reqwest::get(link.clone()).await.unwrap();
+    assert_eq!(form.status().as_u16(), 200);
+    assert!(form.text().await.unwrap().contains("Welcome ursula!"));
+
+    let response = accept(&app, &link, NEW_PASSWORD).await;
+    assert_is_redirect_to(&response, "/login");
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": "ursula",
+            "password": NEW_PASSWORD
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    // The invitation can only be used once
+    let response = accept(&app, &link, NEW_PASSWORD).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn invitations_follow_the_password_rules() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let link = invite_colleague(&app).await;
+
+    let response = accept(&app, &link, "too short").await;
+
+    let form_path = format!("{}?{}", link.path(), link.query().unwrap());
+    assert_is_redirect_to(&response, &form_path);
+    let html = app
+        .api_client
+        .get(format!("{}{form_path}", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html.contains("<p><i>Password length must be > 12  and < 128</i></p>"));
+}
+
+#[tokio::test]
+async fn the_retry_link_keeps_odd_tokens_intact() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_form(
+            "/invitations/accept",
+            &serde_json::json!({
+                "token": "a&b=c#d",
+                "password": "too short",
+                "password_check": "too short",
+            }),
+        )
+        .await;
+
+    assert_is_redirect_to(&response, "/invitations/accept?token=a%26b%3Dc%23d");
+}
+
+#[tokio::test]
+async fn usernames_are_escaped_in_the_invitation_email() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = invite(&app, "<b>ursula</b>", "ursula@example.com").await;
+
+    assert_is_redirect_to(&response, "/admin/users");
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+    let html = body["HtmlBody"].as_str().unwrap();
+    assert!(html.contains("<p>Hi &lt;b&gt;ursula&lt;/b&gt;,</p>"));
+    assert!(
+        body["TextBody"]
+            .as_str()
+            .unwrap()
+            .starts_with("Hi <b>ursula</b>,")
+    );
+}
+
+#[tokio::test]
+async fn unknown_invitations_are_rejected() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!(
+            "{}/invitations/accept?token=notarealtoken",
+            &app.address
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn invalid_invitations_are_not_stored() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    for (username, email) in [("", "ursula@example.com"), ("ursula", "not-an-email")] {
+        let response = invite(&app, username, email).await;
+        assert_is_redirect_to(&response, "/admin/users");
+    }
+    // The seeded admin already exists
+    invite(&app, "admin", "admin@example.com").await;
+
+    let users = sqlx::query_scalar!(r#"SELECT count(*) AS "count!" FROM users"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(users, 2);
+    assert!(get_users_html(&app).await.contains("already exists"));
+}
+
+#[tokio::test]
+async fn disabled_users_cannot_log_in() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let link = invite_colleague(&app).await;
+    accept(&app, &link, NEW_PASSWORD).await;
+
+    let response = post_user_action(&app, user_id(&app, "ursula").await, "toggle").await;
+    assert_is_redirect_to(&response, "/admin/users");
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": "ursula",
+            "password": NEW_PASSWORD
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn users_can_be_deleted() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = post_user_action(&app, user_id(&app, "admin").await, "delete").await;
+
+    assert_is_redirect_to(&response, "/admin/users");
+    assert!(
+        get_users_html(&app)
+            .await
+            .contains("The user has been deleted.")
+    );
+    let admins =
+        sqlx::query_scalar!(r#"SELECT count(*) AS "count!" FROM users WHERE username = 'admin'"#)
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(admins, 0);
+}
+
+#[tokio::test]
+async fn the_last_active_owner_cannot_be_deleted_or_disabled() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    post_user_action(&app, user_id(&app, "admin").await, "delete").await;
+    let own_id = user_id(&app, &app.test_user.username).await;
+
+    post_user_action(&app, own_id, "delete").await;
+    assert!(
+        get_users_html(&app)
+            .await
+            .contains("The last active owner cannot be deleted.")
+    );
+    post_user_action(&app, own_id, "toggle").await;
+    assert!(
+        get_users_html(&app)
+            .await
+            .contains("The last active owner cannot be disabled.")
+    );
+
+    let is_active = sqlx::query_scalar!("SELECT is_active FROM users WHERE user_id = $1", own_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(is_active);
+}
@@ -0,0 +1,188 @@
+use secrecy::ExposeSecret;
+use wiremock::{Mock, ResponseTemplate, matchers::any};
+
+use crate::{
+    helpers::{TestApp, spawn_app},
+    newsletter::create_confirmed_subscriber,
+};
+
+async fn subscriber(app: &TestApp) -> (String, String) {
+    let saved = sqlx::query!("SELECT email, status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscriber");
+    (saved.email, saved.status)
+}
+
+#[tokio::test]
+async fn a_hard_bounce_marks_the_subscriber_as_bounced() {
+    //Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let (email, _) = subscriber(&app).await;
+
+    //Act
+    let response = app
+        .post_postmark_webhook(&serde_json::json!({
+            "RecordType": "Bounce",
+            "Type": "HardBounce",
+            "TypeCode": 1,
+            "Email": email,
+        }))
+        .await;
+
+    //Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let (_, status) = subscriber(&app).await;
+    assert_eq!(status, "bounced");
+}
+
+#[tokio::test]
+async fn a_spam_complaint_marks_the_subscriber_as_complained() {
+    //Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let (email, _) = subscriber(&app).await;
+
+    //Act
+    let response = app
+        .post_postmark_webhook(&serde_json::json!({
+            "RecordType": "SpamComplaint",
+            "Type": "SpamComplaint",
+            "TypeCode": 512,
+            "Email": email,
+        }))
+        .await;
+
+    //Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let (_, status) = subscriber(&app).await;
+    assert_eq!(status, "complained");
+    let suppression = sqlx::query!("SELECT email, reason, source FROM suppressions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("The address was not suppressed");
+    assert_eq!(suppression.email.as_deref(), Some(email.as_str()));
+    assert_eq!(suppression.reason, "complained");
+    assert_eq!(suppression.source, "postmark");
+}
+
+#[tokio::test]
+async fn a_soft_bounce_does_not_change_the_subscriber_status() {
+    //Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let (email, _) = subscriber(&app).await;
+
+    //Act
+    let response = app
+        .post_postmark_webhook(&serde_json::json!({
+            "RecordType": "Bounce",
+            "Type": "SoftBounce",
+            "TypeCode": 4096,
+            "Email": email,
+        }))
+        .await;
+
+    //Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let (_, status) = subscriber(&app).await;
+    assert_eq!(status, "confirmed");
+}
+
+#[tokio::test]
+async fn webhooks_without_valid_credentials_are_rejected() {
+    //Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let (email, _) = subscriber(&app).await;
+    let body = serde_json::json!({
+        "RecordType": "SpamComplaint",
+        "Email": email,
+    });
+
+    //Act
+    let without_credentials = app
+        .api_client
+        .post(format!("{}/webhooks/postmark", &app.address))
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    let with_wrong_secret = app
+        .api_client
+        .post(format!("{}/webhooks/postmark", &app.address))
+        .header("X-Webhook-Secret", "not-the-secret")
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+
+    //Assert
+    assert_eq!(without_credentials.status().as_u16(), 401);
+    assert_eq!(with_wrong_secret.status().as_u16(), 401);
+    let (_, status) = subscriber(&app).await;
+    assert_eq!(status, "confirmed");
+}
+
+#[tokio::test]
+async fn credentials_are_checked_before_the_payload_is_parsed() {
+    let app = spawn_app().await;
+
+    let without_credentials = app
+        .api_client
+        .post(format!("{}/webhooks/postmark", &app.address))
+        .body("not json")
+        .send()
+        .await
+        .unwrap();
+    let with_credentials = app
+        .api_client
+        .post(format!("{}/webhooks/postmark", &app.address))
+        .basic_auth(
+            &app.webhooks.username,
+            Some(app.webhooks.shared_secret.expose_secret()),
+        )
+        .body("not json")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(without_credentials.status().as_u16(), 401);
+    assert_eq!(with_credentials.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn queued_issues_are_not_delivered_to_bounced_subscribers() {
+    //Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let (email, _) = subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_newsletters(&serde_json::json!({
+            "title": "Newsletter title",
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4()
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 303);
+
+    //Act - the address bounces before the worker picks up the task
+    app.post_postmark_webhook(&serde_json::json!({
+        "RecordType": "Bounce",
+        "Type": "HardBounce",
+        "Email": email,
+    }))
+    .await;
+
+    //Assert
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+}